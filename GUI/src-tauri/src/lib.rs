@@ -1,82 +1,134 @@
 use depo_core::build::{BuildSystem, CMake};
+use depo_core::config::Config;
 use depo_core::dependency::Dependency;
-use depo_core::package::Package;
+use depo_core::error::DepoError;
+use depo_core::lock::ProjectLock;
+use depo_core::package::{InstallMode, Package};
 use depo_core::serialization;
+use depo_core::transaction;
+use serde::Serialize;
+
+/// An error returned to the frontend
+///
+/// It carries the fields of the `DepoError`, including its `kind`, so the frontend can
+/// handle specific cases, and in `description` the error message to show for all others.
+#[derive(Serialize)]
+struct CommandError {
+    #[serde(flatten)]
+    error: DepoError,
+    description: String,
+}
+
+impl From<DepoError> for CommandError {
+    fn from(error: DepoError) -> Self {
+        CommandError {
+            description: error.to_string(),
+            error,
+        }
+    }
+}
+
+impl From<anyhow::Error> for CommandError {
+    fn from(error: anyhow::Error) -> Self {
+        DepoError::from(error).into()
+    }
+}
+
+/// Load the package, first rolling back any operation that was interrupted
+fn load_package(path: &str) -> Result<Package, CommandError> {
+    transaction::recover(path)?;
+    Ok(serialization::load_package(path)?)
+}
+
+/// Lock the project for a command that changes it, from loading the package to saving it
+fn lock_project(path: &str) -> Result<ProjectLock, CommandError> {
+    Ok(ProjectLock::acquire(path)?)
+}
 
 #[tauri::command]
-fn init(path: &str) -> Result<Package, String> {
-    Package::init(path).map_err(|e| e.to_string())
+fn init(path: &str) -> Result<Package, CommandError> {
+    Ok(Package::init(path)?)
 }
 
 #[tauri::command]
-fn get_project_deps(path: &str) -> Result<Vec<Dependency>, String> {
-    let pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
+fn get_project_deps(path: &str) -> Result<Vec<Dependency>, CommandError> {
+    let pkg = load_package(path)?;
     Ok(pkg.dependencies)
 }
 
 #[tauri::command]
-async fn find_dependency(path: &str, name: &str) -> Result<Vec<Dependency>, String> {
-    let pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.find_dependency(name).await.map_err(|e| e.to_string())
+async fn find_dependency(path: &str, name: &str) -> Result<Vec<Dependency>, CommandError> {
+    let pkg = load_package(path)?;
+    let config = Config::load()?;
+    Ok(pkg.find_dependency(name, path, &config).await?)
 }
 
 #[tauri::command]
-fn add_dependency(path: &str, dep: Dependency) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.add_dependency(dep, &path).map_err(|e| e.to_string())?;
-    serialization::save_package(&pkg, &path).map_err(|e| e.to_string())?;
+fn dependency_from_url(url: &str, name: Option<&str>) -> Result<Dependency, CommandError> {
+    Ok(Dependency::from_url(url, name)?)
+}
+
+#[tauri::command]
+fn add_dependency(path: &str, dep: Dependency) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.add_dependency(dep, &path, &Config::load()?)?;
+    serialization::save_package(&pkg, &path)?;
     Ok(())
 }
 
 #[tauri::command]
-fn delete_dependency(path: &str, name: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.remove_dependency(name, &path).map_err(|e| e.to_string())?;
-    serialization::save_package(&pkg, &path).map_err(|e| e.to_string())?;
+fn delete_dependency(path: &str, name: &str) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.remove_dependency(name, &path)?;
+    serialization::save_package(&pkg, &path)?;
     Ok(())
 }
 
 #[tauri::command]
-fn install_dependencies(path: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    for dep in pkg.dependencies.iter_mut() {
-        dep.install(&path).map_err(|e| e.to_string())?;
-    }
+fn install_dependencies(path: &str, mode: Option<InstallMode>) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.install(path, mode.unwrap_or_default(), &Config::load()?)?;
     Ok(())
 }
 
 #[tauri::command]
-fn update_dependency(path: &str, name: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.update_dependency(name, path).map_err(|e| e.to_string())?;
-    serialization::save_package(&pkg, path).map_err(|e| e.to_string())?;
+fn update_dependency(path: &str, name: &str) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.update_dependency(name, path, &Config::load()?)?;
+    serialization::save_package(&pkg, path)?;
     Ok(())
 }
 #[tauri::command]
-fn modify_dependency_constraint(path: &str, name: &str, new_constraint: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.modify_dependency_constraint(name, new_constraint, path)
-        .map_err(|e| e.to_string())?;
-    serialization::save_package(&pkg, path).map_err(|e| e.to_string())?;
+fn modify_dependency_constraint(path: &str, name: &str, new_constraint: &str) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.modify_dependency_constraint(name, new_constraint, path)?;
+    serialization::save_package(&pkg, path)?;
     Ok(())
 }
 
 #[tauri::command]
-fn remove_dependency_constraint(path: &str, name: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    pkg.remove_dependency_constraint(name, path)
-        .map_err(|e| e.to_string())?;
-    serialization::save_package(&pkg, path).map_err(|e| e.to_string())?;
+fn remove_dependency_constraint(path: &str, name: &str) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let mut pkg = load_package(path)?;
+    pkg.remove_dependency_constraint(name, path)?;
+    serialization::save_package(&pkg, path)?;
     Ok(())
 }
 
 #[tauri::command]
-fn build_dependencies(path: &str) -> Result<(), String> {
-    let mut pkg = serialization::load_package(path).map_err(|e| e.to_string())?;
-    for dep in pkg.dependencies.iter_mut() {
-        CMake::build_dependency(dep, &path).map_err(|e| e.to_string())?;
+fn build_dependencies(path: &str) -> Result<(), CommandError> {
+    let _lock = lock_project(path)?;
+    let pkg = load_package(path)?;
+    let deps = pkg.build_order(path)?;
+    for dep in &deps {
+        CMake::build_dependency(dep, &path)?;
     }
-    CMake::generate_dependency_bridge(&pkg.dependencies, &path).map_err(|e| e.to_string())?;
+    CMake::generate_dependency_bridge(&deps, &path)?;
     Ok(())
 }
 pub fn run() {
@@ -87,6 +139,7 @@ pub fn run() {
             init,
             get_project_deps,
             find_dependency,
+            dependency_from_url,
             add_dependency,
             delete_dependency,
             install_dependencies,
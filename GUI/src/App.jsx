@@ -6,6 +6,7 @@ import InfoBar from "./components/InfoBar/InfoBar.jsx"
 import styles from './App.module.css'
 import {createContext, useEffect, useState} from "react"
 import { invoke } from "@tauri-apps/api/core"
+import { errorMessage } from "./errors.js"
 
 export const PackagesData = createContext(null);
 
@@ -21,8 +22,8 @@ function App() {
             setPackageData(data);
             console.log("data fechted");
         }catch(e){
-            console.log("filed to fetch data : "  + e);
-            setError(e);
+            console.log("filed to fetch data : "  + errorMessage(e));
+            setError(errorMessage(e));
         }
     }
     useEffect(() => {
@@ -5,6 +5,7 @@ import {PackagesData} from "../../App.jsx";
 import addIcon from "../../assets/add.png";
 import searchIcon from "../../assets/search.png";
 import closeIcon from "../../assets/delete.png";
+import { errorMessage } from "../../errors.js";
 
 
 function AddWindow({isVisible, setIsVisible}) {
@@ -37,8 +38,8 @@ function AddWindow({isVisible, setIsVisible}) {
             setDependency(dependency);
         }
         catch(e){
-            console.log("dependency not found : " + e);
-            alert(e);
+            console.log("dependency not found : " + errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -55,8 +56,8 @@ function AddWindow({isVisible, setIsVisible}) {
             console.log("dependency added ! with version :"  + depSelected.version);
             fetchData();
         }catch(e){
-            console.log("something went wrong with adding dependency : " + e);
-            alert(e);
+            console.log("something went wrong with adding dependency : " + errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -2,6 +2,7 @@ import styles from'./Build.module.css';
 import {useContext} from "react";
 import {PackagesData} from "../../App.jsx";
 import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../../errors.js";
 
 function Build() {
     const {path}= useContext(PackagesData);
@@ -11,8 +12,8 @@ function Build() {
             await invoke('build_dependencies' , {path:path});
             console.log("Building dependencies");
         }catch(e){
-            console.log("filed building dependencies : " + e);
-            alert(e);
+            console.log("filed building dependencies : " + errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -2,6 +2,7 @@ import styles from'./Install.module.css';
 import { PackagesData } from '../../App.jsx';
 import {useContext} from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../../errors.js";
 
 function Install() {
 
@@ -12,8 +13,8 @@ function Install() {
             await invoke('install_dependencies', {path:path});
             console.log("Install dependencies");
         }catch(e){
-            console.log("problem with installing dependancy :  " + e);
-            alert(e);
+            console.log("problem with installing dependancy :  " + errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -5,6 +5,7 @@ import {useContext, useEffect, useState} from "react";
 import { invoke } from "@tauri-apps/api/core";
 import settingsIcon from "../../assets/settings.png"
 import SettingsWindow from "./SettingsWindow.jsx";
+import { errorMessage } from "../../errors.js";
 
 function List() {
 
@@ -17,8 +18,8 @@ function List() {
             console.log("deleted dependency " + name);
             fetchData();
         }catch(e){
-            console.log(" problem with deleting  dependency " + name + " : " + e);
-            alert(e);
+            console.log(" problem with deleting  dependency " + name + " : " + errorMessage(e));
+            alert(errorMessage(e));
         }
 
     }
@@ -29,8 +30,8 @@ function List() {
             console.log("updated dependency " + name );
             fetchData();
         }catch(e){
-            console.log(" problem with updating  dependency " + name + " : " +e);
-            alert(e);
+            console.log(" problem with updating  dependency " + name + " : " + errorMessage(e));
+            alert(errorMessage(e));
         }
     }
     const openSettings = () => {
@@ -3,6 +3,7 @@ import closeIcon from "../../assets/delete.png";
 import {useContext, useEffect, useState} from "react";
 import {PackagesData} from "../../App.jsx";
 import {invoke} from "@tauri-apps/api/core";
+import { errorMessage } from "../../errors.js";
 
 
 function AddWindow({isSettingsVisible, setIsSettingsVisible, Package}) {
@@ -22,8 +23,8 @@ function AddWindow({isSettingsVisible, setIsSettingsVisible, Package}) {
             console.log("modify succeed " + Package.name);
             fetchData();
         }catch(e){
-            console.log("error while modifying dependency constraint " + Package.name + " : "+ e);
-            alert(e);
+            console.log("error while modifying dependency constraint " + Package.name + " : "+ errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -33,8 +34,8 @@ function AddWindow({isSettingsVisible, setIsSettingsVisible, Package}) {
             console.log("remove succeed " + Package.name);
             fetchData();
         }catch(e){
-            console.log("error while remove constraint " + Package.name + " : "+ e);
-            alert(e);
+            console.log("error while remove constraint " + Package.name + " : "+ errorMessage(e));
+            alert(errorMessage(e));
         }
     }
 
@@ -3,6 +3,7 @@ import { open } from "@tauri-apps/plugin-dialog";
 import { invoke } from "@tauri-apps/api/core";
 import {useContext} from "react";
 import {PackagesData} from "../../App.jsx";
+import { errorMessage } from "../../errors.js";
 
 function SelectProject() {
 
@@ -22,9 +23,9 @@ function SelectProject() {
             console.log("selected project :" + path);
             setPath(path);
         }catch(e){
-            console.log("somthing went wrong! with selecting project path : " + e);
-            setError(e);
-            alert(e);
+            console.log("somthing went wrong! with selecting project path : " + errorMessage(e));
+            setError(errorMessage(e));
+            alert(errorMessage(e));
 
         }
     }
@@ -0,0 +1,17 @@
+// Turn an error returned by a Tauri command into the text shown to the user.
+// Errors carry the `kind` of the core error and its fields; cases the user can act on
+// get their own message, all others show the error's description.
+export function errorMessage(e) {
+    switch (e?.kind) {
+        case "ManifestNotFound":
+            return "This folder has no package file yet. Use Init to create one.";
+        case "RateLimited":
+            return `The ${e.service} rate limit was reached. ${e.hint}.`;
+        case "ProjectLocked":
+            return `Another depo process (pid ${e.pid ?? "unknown"}) is changing this project. Try again once it has finished.`;
+        case "BuildFailed":
+            return `Building ${e.name} failed in the ${e.stage} step:\n\n${e.log}`;
+        default:
+            return e?.description ?? String(e);
+    }
+}
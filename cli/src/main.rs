@@ -7,9 +7,15 @@
 use clap::{Parser, Subcommand};
 use depo_core::{
     build::{BuildSystem, CMake},
+    cache::{Cache, parse_size},
     config::Config,
-    package::Package,
-    serialization,
+    dependency::{self, Dependency},
+    error::DepoError,
+    lock::ProjectLock,
+    migration::SCHEMA_VERSION,
+    package::{InstallMode, Package},
+    serialization::{self, ManifestFormat},
+    transaction,
 };
 use std::env;
 
@@ -21,6 +27,10 @@ use std::env;
 #[command(name = "pkg")]
 #[command(about = "A simple C++ package manager", long_about = None)]
 struct Cli {
+    /// Work from the download cache only, without network access
+    #[arg(long, global = true)]
+    offline: bool,
+
     /// The command to execute
     #[command(subcommand)]
     command: Commands,
@@ -36,12 +46,24 @@ enum Commands {
     /// Add a new dependency to the package
     #[command(about = "Add a dependency to your project")]
     Add {
-        /// Name of the dependency to add
-        #[arg(help = "Dependency name")]
+        /// Name of the dependency to search for, or a Git URL to add directly
+        #[arg(help = "Dependency name to search for, or a Git URL")]
         name: String,
+        /// Explicit name for a dependency added by URL
+        #[arg(long = "name", value_name = "NAME", help = "Name to use for a dependency added by URL")]
+        as_name: Option<String>,
         /// Optional version constraint for the dependency
         #[arg(short, long, help = "Optional version constraint")]
         version: Option<String>,
+        /// Pin the dependency to an exact commit SHA or ref
+        #[arg(long, conflicts_with_all = ["version", "tag"], help = "Pin to an exact commit SHA or ref")]
+        rev: Option<String>,
+        /// Pin the dependency to a tag
+        #[arg(long, conflicts_with = "version", help = "Pin to a tag, e.g. release-2024-05")]
+        tag: Option<String>,
+        /// Only look the dependency up in this registry
+        #[arg(long, help = "Add the dependency from this registry")]
+        registry: Option<String>,
     },
 
     /// Remove a dependency from the package
@@ -54,7 +76,14 @@ enum Commands {
 
     /// Install all dependencies defined in the package
     #[command(about = "Install all dependencies for the project")]
-    Install,
+    Install {
+        /// Fail if depo.lock does not match the package file
+        #[arg(long, help = "Fail instead of resolving anything not in depo.lock")]
+        locked: bool,
+        /// Like --locked, and never access the network
+        #[arg(long, help = "Like --locked, and only use already installed dependencies")]
+        frozen: bool,
+    },
 
     /// Update a specific dependency to its latest version
     #[command(about = "Update a dependency to the latest version")]
@@ -72,6 +101,37 @@ enum Commands {
     #[command(about = "List all dependencies of the project")]
     List,
 
+    /// Copy the sources of all installed dependencies into the project
+    #[command(about = "Vendor dependency sources into the project")]
+    Vendor {
+        /// Directory to vendor into, recorded in the package file
+        #[arg(long, help = "Vendor directory (defaults to the configured one or vendor/)")]
+        dir: Option<String>,
+    },
+
+    /// Publish the current version of the package to a registry
+    #[command(about = "Publish this package's version to a registry")]
+    Publish {
+        /// Registry to publish to, required when several are configured
+        #[arg(long, help = "Registry to publish to")]
+        registry: Option<String>,
+        /// Git URL consumers fetch the package from
+        #[arg(long, help = "Git URL of the package (defaults to the origin remote)")]
+        url: Option<String>,
+    },
+
+    /// Upgrade the package file to the current schema version
+    #[command(about = "Upgrade the package file to the current format")]
+    Migrate,
+
+    /// Convert between package.yaml and depo.toml
+    #[command(about = "Convert the package file between package.yaml and depo.toml")]
+    Convert {
+        /// Format to convert to
+        #[arg(long, value_parser = ["yaml", "toml"], help = "Format to convert to (defaults to the other one)")]
+        to: Option<String>,
+    },
+
     /// Manage version constraints for dependencies
     #[command(about = "Modify version constraints of a dependency")]
     Constraint {
@@ -81,8 +141,14 @@ enum Commands {
         /// New version constraint to set
         #[arg(short, long, help = "Set a new version constraint")]
         new: Option<String>,
+        /// Pin to an exact commit SHA or ref instead of a version constraint
+        #[arg(long, conflicts_with_all = ["new", "tag", "remove"], help = "Pin to an exact commit SHA or ref")]
+        rev: Option<String>,
+        /// Pin to a tag instead of a version constraint
+        #[arg(long, conflicts_with_all = ["new", "remove"], help = "Pin to a tag")]
+        tag: Option<String>,
         /// Remove the existing version constraint
-        #[arg(long, help = "Remove the existing version constraint")]
+        #[arg(long, help = "Remove the existing version constraint or pin")]
         remove: bool,
     },
 
@@ -93,6 +159,14 @@ enum Commands {
         #[command(subcommand)]
         action: TokenAction,
     },
+
+    /// Manage the download cache shared by all projects
+    #[command(about = "Manage the global download cache")]
+    Cache {
+        /// Cache management action to perform
+        #[command(subcommand)]
+        action: CacheAction,
+    },
 }
 
 /// Available token management actions
@@ -113,17 +187,81 @@ enum TokenAction {
     Remove,
 }
 
+/// Available download cache actions
+#[derive(Subcommand, PartialEq)]
+enum CacheAction {
+    /// List all cached commits
+    #[command(about = "List cached dependencies")]
+    List,
+    /// Remove everything from the cache
+    #[command(about = "Remove all cached dependencies")]
+    Clean,
+    /// Evict least recently used entries until the cache fits a size limit
+    #[command(about = "Shrink the cache to a size limit")]
+    Gc {
+        /// The size limit, e.g. 500M or 10G
+        #[arg(long, help = "Size limit (defaults to DEPO_CACHE_MAX_SIZE or 10G)")]
+        max_size: Option<String>,
+    },
+}
+
+/// Format a size in bytes for display
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let working_dir = env::current_dir()?;
 
     if let Commands::Init = cli.command {
-        Package::init(&working_dir.to_str().unwrap())?;
+        Package::init(working_dir.to_str().unwrap())?;
         println!("Initialized new package in {}", working_dir.display());
         return Ok(());
     }
 
+    if let Commands::Migrate = cli.command {
+        let path = working_dir.to_str().unwrap();
+        let original = serialization::migrate_package(path)?;
+        let (file, _) = serialization::package_file(path)?;
+        let file = file.file_name().unwrap().to_string_lossy();
+        if original < SCHEMA_VERSION {
+            println!(
+                "Migrated {} from schema version {} to {}",
+                file, original, SCHEMA_VERSION
+            );
+        } else {
+            println!("{} is already at schema version {}", file, SCHEMA_VERSION);
+        }
+        return Ok(());
+    }
+
+    if let Commands::Convert { to } = &cli.command {
+        let path = working_dir.to_str().unwrap();
+        let (file, from) = serialization::package_file(path)?;
+        let to = match to.as_deref() {
+            Some("yaml") => ManifestFormat::Yaml,
+            Some("toml") => ManifestFormat::Toml,
+            _ if from == ManifestFormat::Yaml => ManifestFormat::Toml,
+            _ => ManifestFormat::Yaml,
+        };
+        let target = serialization::convert_package(path, to)?;
+        println!(
+            "Converted {} to {}",
+            file.file_name().unwrap().to_string_lossy(),
+            target.file_name().unwrap().to_string_lossy()
+        );
+        return Ok(());
+    }
+
     if let Commands::Token { .. } = cli.command {
         match cli.command {
             Commands::Token { action } => match action {
@@ -153,71 +291,157 @@ async fn main() -> anyhow::Result<()> {
         }
         return Ok(());
     }
-    let mut pkg = match serialization::load_package(&working_dir.to_str().unwrap()) {
-        Ok(pkg) => pkg,
-        Err(e) => {
-            if e.to_string().contains("Package file not found") {
-                println!("Package file not found. Use the `init` command to create one.");
-                return Ok(());
+
+    let mut config = Config::load()?;
+    config.offline |= cli.offline;
+
+    if let Commands::Cache { action } = cli.command {
+        let cache = Cache::open(&config)?;
+        match action {
+            CacheAction::List => {
+                let entries = cache.entries()?;
+                if entries.is_empty() {
+                    println!("Cache at {} is empty.", cache.root().display());
+                } else {
+                    println!("Cache at {}:", cache.root().display());
+                    for entry in &entries {
+                        println!(
+                            "  {} {} ({})",
+                            entry.url,
+                            &entry.commit[..entry.commit.len().min(12)],
+                            format_size(entry.size)
+                        );
+                    }
+                    let total: u64 = entries.iter().map(|e| e.size).sum();
+                    println!("Total: {}", format_size(total));
+                }
+            }
+            CacheAction::Clean => {
+                let freed = cache.clean()?;
+                println!("Removed {} from {}", format_size(freed), cache.root().display());
+            }
+            CacheAction::Gc { max_size } => {
+                let max_size = match max_size {
+                    Some(size) => parse_size(&size)?,
+                    None => cache.max_size(),
+                };
+                let evicted = cache.gc(max_size)?;
+                let freed: u64 = evicted.iter().map(|e| e.size).sum();
+                println!(
+                    "Evicted {} entries, freed {}",
+                    evicted.len(),
+                    format_size(freed)
+                );
             }
-            return Err(e.into());
         }
+        return Ok(());
+    }
+
+    // Commands that change the project hold the lock from loading the package until they
+    // are done, so no other depo process changes it in between. `add` only takes it once
+    // the dependency is chosen, as searching and prompting can take a while.
+    let mut _lock = match cli.command {
+        Commands::List | Commands::Add { .. } => None,
+        _ => Some(ProjectLock::acquire(working_dir.to_str().unwrap())?),
+    };
+    transaction::recover(working_dir.to_str().unwrap())?;
+    let mut pkg = match serialization::load_package(working_dir.to_str().unwrap()) {
+        Ok(pkg) => pkg,
+        Err(DepoError::ManifestNotFound { .. }) => {
+            println!("Package file not found. Use the `init` command to create one.");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
     };
 
     match cli.command {
-        Commands::Add { name, version } => {
-            let mut candidates = pkg.find_dependency(&name).await?;
+        Commands::Add { name, as_name, version, rev, tag, registry } => {
+            let mut chosen = if dependency::is_git_url(&name) {
+                if registry.is_some() {
+                    anyhow::bail!("--registry cannot be used when adding a dependency by Git URL");
+                }
+                Dependency::from_url(&name, as_name.as_deref())?
+            } else if as_name.is_some() {
+                anyhow::bail!("--name can only be used when adding a dependency by Git URL");
+            } else if let Some(found) =
+                pkg.find_in_registries(&name, registry.as_deref(), working_dir.to_str().unwrap(), &config)?
+            {
+                println!(
+                    "Found '{}' in registry '{}'",
+                    found.name,
+                    found.registry.as_deref().unwrap_or_default()
+                );
+                found
+            } else if let Some(registry) = registry {
+                anyhow::bail!("'{}' is not published in registry '{}'", name, registry);
+            } else {
+                let mut candidates = pkg.find_dependency(&name, working_dir.to_str().unwrap(), &config).await?;
 
-            if candidates.is_empty() {
-                println!("No dependencies found for '{}'", name);
-                return Ok(());
-            }
+                if candidates.is_empty() {
+                    println!("No dependencies found for '{}'", name);
+                    return Ok(());
+                }
 
-            let options: Vec<String> = candidates.iter().map(|c| c.full_name.clone()).collect();
+                let options: Vec<String> = candidates.iter().map(|c| c.full_name.clone()).collect();
 
-            let selection = dialoguer::Select::new()
-                .with_prompt("Select a dependency")
-                .items(&options)
-                .default(0)
-                .interact()?;
+                let selection = dialoguer::Select::new()
+                    .with_prompt("Select a dependency")
+                    .items(&options)
+                    .default(0)
+                    .interact()?;
 
-            let mut chosen = candidates.remove(selection);
+                candidates.remove(selection)
+            };
 
             if let Some(version_constraint) = version {
                 chosen.version_constraint = Some(version_constraint);
             }
+            chosen.rev = rev;
+            chosen.tag = tag;
 
-            pkg.add_dependency(chosen, &working_dir.to_str().unwrap())?;
-            println!("Added dependency: {}", name);
+            _lock = Some(ProjectLock::acquire(working_dir.to_str().unwrap())?);
+            pkg = serialization::load_package(working_dir.to_str().unwrap())?;
+            let added = chosen.name.clone();
+            pkg.add_dependency(chosen, working_dir.to_str().unwrap(), &config)?;
+            println!("Added dependency: {}", added);
         }
         Commands::Delete { name } => {
-            match pkg.remove_dependency(&name, &working_dir.to_str().unwrap()) {
+            match pkg.remove_dependency(&name, working_dir.to_str().unwrap()) {
                 Ok(()) => println!("Deleted dependency: {}", name),
                 Err(e) => eprintln!("Failed to delete dependency '{}': {}", name, e),
             }
         }
-        Commands::Install => {
-            for dep in pkg.dependencies.iter_mut() {
-                match dep.install(&working_dir.to_str().unwrap()) {
-                    Ok(_) => println!("Installed dependency '{}'", dep.name),
-                    Err(e) => eprintln!("Failed to install dependency '{}': {}", dep.name, e),
-                }
-            }
+        Commands::Install { locked, frozen } => {
+            let mode = if frozen {
+                InstallMode::Frozen
+            } else if locked {
+                InstallMode::Locked
+            } else {
+                InstallMode::Normal
+            };
+            pkg.install(working_dir.to_str().unwrap(), mode, &config)?;
         }
         Commands::Update { name } => {
-            match pkg.update_dependency(&name, &working_dir.to_str().unwrap()) {
+            match pkg.update_dependency(&name, working_dir.to_str().unwrap(), &config) {
                 Ok(_) => println!("Dependency '{}' updated successfully!", name),
                 Err(e) => eprintln!("Failed to update dependency '{}': {}", name, e),
             }
         }
         Commands::Build => {
-            for dep in &pkg.dependencies {
-                match CMake::build_dependency(dep, &working_dir.to_str().unwrap()) {
+            let deps = pkg.build_order(working_dir.to_str().unwrap())?;
+            for dep in &deps {
+                match CMake::build_dependency(dep, working_dir.to_str().unwrap()) {
                     Ok(_) => println!("Built dependency '{}'", dep.name),
+                    // CMake's output has already been shown while it ran
+                    Err(DepoError::BuildFailed { stage, .. }) => eprintln!(
+                        "Failed to build dependency '{}': CMake {} step failed",
+                        dep.name,
+                        stage
+                    ),
                     Err(e) => eprintln!("Failed to build dependency '{}': {}", dep.name, e),
                 }
             }
-            CMake::generate_dependency_bridge(&pkg.dependencies, &working_dir.to_str().unwrap())?;
+            CMake::generate_dependency_bridge(&deps, working_dir.to_str().unwrap())?;
         }
         Commands::List => {
             if pkg.dependencies.is_empty() {
@@ -229,14 +453,45 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
-        Commands::Constraint { name, new, remove } => {
-            if remove {
-                match pkg.remove_dependency_constraint(&name, &working_dir.to_str().unwrap()) {
+        Commands::Vendor { dir } => {
+            let manifest = pkg.vendor(working_dir.to_str().unwrap(), dir.as_deref())?;
+            println!(
+                "Vendored {} dependencies into {}",
+                manifest.dependencies.len(),
+                pkg.vendor_path(working_dir.to_str().unwrap()).display()
+            );
+        }
+        Commands::Publish { registry, url } => {
+            let published = pkg.publish(
+                working_dir.to_str().unwrap(),
+                registry.as_deref(),
+                url.as_deref(),
+            )?;
+            println!(
+                "Published {} {} ({} at {})",
+                pkg.name.as_deref().unwrap_or_default(),
+                published.version,
+                published.tag,
+                published.commit
+            );
+        }
+        Commands::Constraint { name, new, rev, tag, remove } => {
+            if rev.is_some() || tag.is_some() {
+                let pin = rev.as_deref().map_or_else(
+                    || format!("tag '{}'", tag.as_deref().unwrap_or_default()),
+                    |rev| format!("rev '{}'", rev),
+                );
+                match pkg.pin_dependency(&name, rev.as_deref(), tag.as_deref(), working_dir.to_str().unwrap()) {
+                    Ok(_) => println!("Dependency '{}' pinned to {}", name, pin),
+                    Err(e) => eprintln!("Failed to pin dependency '{}': {}", name, e),
+                }
+            } else if remove {
+                match pkg.remove_dependency_constraint(&name, working_dir.to_str().unwrap()) {
                     Ok(_) => println!("Removed constraint for dependency '{}'", name),
                     Err(e) => eprintln!("Failed to remove constraint for dependency '{}': {}", name, e),
                 }
             } else if let Some(new_constraint) = new {
-                match pkg.modify_dependency_constraint(&name, &new_constraint, &working_dir.to_str().unwrap()) {
+                match pkg.modify_dependency_constraint(&name, &new_constraint, working_dir.to_str().unwrap()) {
                     Ok(_) => println!(
                         "Dependency '{}' constraint updated to '{}'",
                         name, new_constraint
@@ -247,16 +502,25 @@ async fn main() -> anyhow::Result<()> {
                     ),
                 }
             } else {
-                eprintln!("Error: must provide either --new <constraint> or --remove");
+                eprintln!("Error: must provide one of --new <constraint>, --rev <rev>, --tag <tag> or --remove");
             }
         }
         Commands::Token { .. } => {
             unreachable!("Token commands should be handled before this match")
         }
+        Commands::Cache { .. } => {
+            unreachable!("Cache commands should be handled before this match")
+        }
         Commands::Init => {
             unreachable!("Init command should be handled before this match")
         }
+        Commands::Migrate => {
+            unreachable!("Migrate command should be handled before this match")
+        }
+        Commands::Convert { .. } => {
+            unreachable!("Convert command should be handled before this match")
+        }
     }
-    serialization::save_package(&pkg, working_dir.to_str().unwrap())?;
+    // Commands that change the package save it themselves, so reading it never rewrites it
     Ok(())
 }
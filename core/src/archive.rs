@@ -0,0 +1,258 @@
+//! # Archive Sources
+//!
+//! This module downloads, verifies and unpacks release archives used as dependency
+//! sources. `.tar.gz`, `.tar.xz` and `.zip` archives are supported, from `http(s)://`
+//! as well as `file://` URLs. Every archive must match the SHA-256 checksum declared
+//! in `package.yaml` before it is unpacked.
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use xz2::read::XzDecoder;
+
+/// The container format of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A gzip compressed tarball (`.tar.gz` or `.tgz`)
+    TarGz,
+    /// An xz compressed tarball (`.tar.xz` or `.txz`)
+    TarXz,
+    /// A zip archive
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detect the archive format from a URL's file extension
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the archive
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(ArchiveFormat)` for supported extensions, `None` otherwise.
+    pub fn from_url(url: &str) -> Option<ArchiveFormat> {
+        let file = file_name(url).to_lowercase();
+        if file.ends_with(".tar.gz") || file.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if file.ends_with(".tar.xz") || file.ends_with(".txz") {
+            Some(ArchiveFormat::TarXz)
+        } else if file.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Download an archive
+///
+/// `file://` URLs are read from disk. Other URLs are downloaded with reqwest on a
+/// separate thread, so this function can be called from within an async runtime.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the archive
+/// * `user_agent` - The `User-Agent` header to send
+///
+/// # Returns
+///
+/// Returns a `Result<Vec<u8>>` with the archive contents.
+///
+/// # Errors
+///
+/// This function will return an error if the file cannot be read or the download fails.
+pub fn download(url: &str, user_agent: &str) -> anyhow::Result<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return fs::read(path).with_context(|| format!("Failed to read archive '{}'", path));
+    }
+
+    let owned = url.to_string();
+    let user_agent = user_agent.to_string();
+    std::thread::spawn(move || -> anyhow::Result<Vec<u8>> {
+        let response = reqwest::blocking::Client::new()
+            .get(&owned)
+            .header("User-Agent", user_agent)
+            .send()?
+            .error_for_status()?;
+        Ok(response.bytes()?.to_vec())
+    })
+    .join()
+    .map_err(|_| anyhow::anyhow!("Download thread panicked"))?
+    .with_context(|| format!("Failed to download archive '{}'", url))
+}
+
+/// Compute the SHA-256 checksum of some data
+///
+/// # Arguments
+///
+/// * `data` - The data to hash
+///
+/// # Returns
+///
+/// Returns the checksum as a lowercase hex string.
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Verify that data matches an expected SHA-256 checksum
+///
+/// # Arguments
+///
+/// * `data` - The downloaded archive
+/// * `expected` - The expected checksum as a hex string
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating whether the checksum matches.
+///
+/// # Errors
+///
+/// This function will return an error naming both checksums if they differ.
+pub fn verify(data: &[u8], expected: &str) -> anyhow::Result<()> {
+    let actual = sha256(data);
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        anyhow::bail!("Checksum mismatch: expected sha256 {}, got {}", expected, actual);
+    }
+    Ok(())
+}
+
+/// Get the directory an archive is unpacked into before it is moved to `dest`
+///
+/// # Arguments
+///
+/// * `dest` - The directory the archive is unpacked to
+///
+/// # Returns
+///
+/// Returns the sibling `<dest>.unpack` directory.
+pub fn staging_path(dest: &Path) -> PathBuf {
+    dest.with_file_name(format!(
+        "{}.unpack",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+/// Unpack an archive into a directory
+///
+/// When every entry of the archive is inside a single top-level directory, as is
+/// usual for release tarballs, that directory is stripped.
+///
+/// # Arguments
+///
+/// * `data` - The archive contents
+/// * `format` - The archive format
+/// * `dest` - The directory to unpack into, created if missing
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the unpacking.
+pub fn unpack(data: &[u8], format: ArchiveFormat, dest: &Path) -> anyhow::Result<()> {
+    let staging = staging_path(dest);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+
+    match format {
+        ArchiveFormat::TarGz => unpack_tar(GzDecoder::new(data), &staging)?,
+        ArchiveFormat::TarXz => unpack_tar(XzDecoder::new(data), &staging)?,
+        ArchiveFormat::Zip => zip::ZipArchive::new(Cursor::new(data))?
+            .extract(&staging)
+            .context("Failed to unpack zip archive")?,
+    }
+
+    let entries: Vec<_> = fs::read_dir(&staging)?.collect::<Result<_, _>>()?;
+    let root = match entries.as_slice() {
+        [single] if single.file_type()?.is_dir() => single.path(),
+        _ => staging.clone(),
+    };
+
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::rename(&root, dest)?;
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(())
+}
+
+fn unpack_tar(reader: impl Read, dest: &Path) -> anyhow::Result<()> {
+    tar::Archive::new(reader)
+        .unpack(dest)
+        .context("Failed to unpack tar archive")
+}
+
+/// Get the last path segment of a URL, without query or fragment
+///
+/// # Arguments
+///
+/// * `url` - The URL
+///
+/// # Returns
+///
+/// Returns the file name the URL points at.
+pub fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Derive the version of an archive from its file name
+///
+/// # Arguments
+///
+/// * `url` - The URL of the archive
+/// * `checksum` - The archive's SHA-256 checksum, used if the file name has no version
+///
+/// # Returns
+///
+/// Returns the longest run of numbers separated by `.` or `_` that ends the file name and
+/// follows a `-`, `_` or `.` (or starts it), with underscores turned into dots: `10.2.1`
+/// for `fmt-10.2.1.tar.gz` and `1.84.0` for `boost_1_84_0.tar.gz`. A `v` right before the
+/// run is kept, as in `v1.0.0` for `v1.0.0.zip`. File names without a version give the
+/// first 12 characters of the checksum.
+pub fn version(url: &str, checksum: &str) -> String {
+    let file = file_name(url);
+    let lower = file.to_lowercase();
+    let stem = [".tar.gz", ".tgz", ".tar.xz", ".txz", ".zip"]
+        .iter()
+        .find(|ext| lower.ends_with(*ext))
+        .map_or(file, |ext| &file[..file.len() - ext.len()]);
+
+    let bytes = stem.as_bytes();
+    let is_boundary = |index: usize| index == 0 || matches!(bytes[index - 1], b'-' | b'_' | b'.');
+    let is_prefixed = |index: usize| index > 0 && bytes[index - 1] == b'v' && is_boundary(index - 1);
+
+    // Walk back over the numbers of the trailing run, remembering the earliest one that
+    // can start a version
+    let mut start = None;
+    let mut end = bytes.len();
+    loop {
+        let digits = bytes[..end].iter().rev().take_while(|b| b.is_ascii_digit()).count();
+        if digits == 0 {
+            break;
+        }
+        let number = end - digits;
+        if is_boundary(number) || is_prefixed(number) {
+            start = Some(number);
+        }
+        match number.checked_sub(2) {
+            Some(before) if matches!(bytes[number - 1], b'.' | b'_') && bytes[before].is_ascii_digit() => {
+                end = number - 1;
+            }
+            _ => break,
+        }
+    }
+
+    match start {
+        Some(start) => {
+            let prefix = if is_prefixed(start) { "v" } else { "" };
+            format!("{}{}", prefix, stem[start..].replace('_', "."))
+        }
+        None => checksum.chars().take(12).collect(),
+    }
+}
@@ -4,11 +4,15 @@
 //! Currently supports CMake build system with plans for additional build systems.
 
 use crate::dependency::Dependency;
+use crate::error::{BuildStage, DepoError, Result};
+use crate::serialization;
 use std::fs;
 use std::fs::File;
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
 
 /// Trait defining the interface for build system implementations
 ///
@@ -26,7 +30,12 @@ pub trait BuildSystem {
     /// # Returns
     ///
     /// Returns a `Result<()>` indicating success or failure of the build.
-    fn build_dependency(pkg: &Dependency, working_dir: &str) -> anyhow::Result<()>;
+    ///
+    /// # Errors
+    ///
+    /// This method will return `DepoError::BuildFailed` with the output of the failed step
+    /// if the dependency cannot be configured or built.
+    fn build_dependency(pkg: &Dependency, working_dir: &str) -> Result<()>;
     
     /// Generate bridge files to integrate dependencies with the main project
     ///
@@ -38,7 +47,7 @@ pub trait BuildSystem {
     /// # Returns
     ///
     /// Returns a `Result<()>` indicating success or failure of bridge generation.
-    fn generate_dependency_bridge(deps: &[Dependency], working_dir: &str) -> anyhow::Result<()>;
+    fn generate_dependency_bridge(deps: &[Dependency], working_dir: &str) -> Result<()>;
 }
 
 /// CMake build system implementation
@@ -47,46 +56,33 @@ pub trait BuildSystem {
 /// It handles building dependencies using CMake and generating CMake integration files.
 pub struct CMake;
 impl BuildSystem for CMake {
-    fn build_dependency(dep: &Dependency, working_dir: &str) -> anyhow::Result<()> {
-        let dep_path = Path::new(working_dir).join("deps").join(format!("{}@{}", &dep.name, &dep.version));
+    fn build_dependency(dep: &Dependency, working_dir: &str) -> Result<()> {
+        let dep_path = dep.source_path(working_dir);
         let cmake_file = dep_path.join("CMakeLists.txt");
 
         if !cmake_file.exists() {
-            anyhow::bail!(
-                "Build file not found for {}, path: {}",
-                dep.name,
-                cmake_file.display()
-            );
+            let log = format!("Build file not found: {}", cmake_file.display());
+            eprintln!("{}", log);
+            return Err(DepoError::BuildFailed {
+                name: dep.name.clone(),
+                stage: BuildStage::Configure,
+                log,
+            });
         }
 
-        let build_dir = dep_path.join("build");
+        let build_dir = dep.build_path(working_dir);
         fs::create_dir_all(&build_dir)?;
 
-        let status = Command::new("cmake")
-            .arg("..")
-            .current_dir(&build_dir)
-            .status()
-            .map_err(|e| anyhow::anyhow!("Failed to run CMake for {}: {}", dep.name, e))?;
-
-        if !status.success() {
-            anyhow::bail!("CMake configure failed for {}", dep.name);
-        }
-
-        let status = Command::new("cmake")
-            .arg("--build")
-            .arg(".")
-            .current_dir(&build_dir)
-            .status()
-            .map_err(|e| anyhow::anyhow!("Failed to build {}: {}", dep.name, e))?;
+        let mut configure = Command::new("cmake");
+        configure.arg(&dep_path).current_dir(&build_dir);
+        run_cmake(dep, BuildStage::Configure, &mut configure)?;
 
-        if !status.success() {
-            anyhow::bail!("CMake build failed for {}", dep.name);
-        }
-
-        Ok(())
+        let mut build = Command::new("cmake");
+        build.arg("--build").arg(".").current_dir(&build_dir);
+        run_cmake(dep, BuildStage::Build, &mut build)
     }
 
-    fn generate_dependency_bridge(deps: &[Dependency], working_dir: &str) -> anyhow::Result<()> {
+    fn generate_dependency_bridge(deps: &[Dependency], working_dir: &str) -> Result<()> {
         let deps_dir = Path::new(working_dir).join("deps");
         fs::create_dir_all(&deps_dir)?;
 
@@ -97,10 +93,21 @@ impl BuildSystem for CMake {
         let mut links_file = File::create(&links_path)?;
 
         for dep in deps {
-            let dep_path = deps_dir.join(format!("{}@{}", &dep.name, &dep.version).as_str());
+            let dep_path = dep.source_path(working_dir);
             let dep_path_str = dep_path.to_string_lossy().replace("\\", "/");
 
-            writeln!(include_file, "add_subdirectory({})", dep_path_str)?;
+            if dep_path != dep.install_path(working_dir) {
+                // Sources outside deps/ need an explicit binary directory
+                let binary_dir = dep.install_path(working_dir);
+                writeln!(
+                    include_file,
+                    "add_subdirectory({} {})",
+                    dep_path_str,
+                    binary_dir.to_string_lossy().replace("\\", "/")
+                )?;
+            } else {
+                writeln!(include_file, "add_subdirectory({})", dep_path_str)?;
+            }
             writeln!(
                 include_file,
                 "include_directories({}/include)",
@@ -109,10 +116,74 @@ impl BuildSystem for CMake {
             writeln!(
                 links_file,
                 "target_link_libraries(main PRIVATE {})",
-                &dep.name,
+                exported_targets(dep, working_dir).join(" "),
             )?;
         }
 
         Ok(())
     }
 }
+
+/// Run one step of a CMake build
+///
+/// CMake's output is streamed to the terminal while it runs and collected at the same
+/// time, so that a failed step can return it as the log of `DepoError::BuildFailed`.
+fn run_cmake(dep: &Dependency, stage: BuildStage, command: &mut Command) -> Result<()> {
+    let failed = |log: String| DepoError::BuildFailed {
+        name: dep.name.clone(),
+        stage,
+        log,
+    };
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(format!("Failed to run CMake: {}", e)))?;
+    let log = Mutex::new(Vec::new());
+    let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+    thread::scope(|scope| {
+        if let Some(stdout) = stdout {
+            scope.spawn(|| tee(stdout, io::stdout(), &log));
+        }
+        if let Some(stderr) = stderr {
+            scope.spawn(|| tee(stderr, io::stderr(), &log));
+        }
+    });
+
+    let status = child
+        .wait()
+        .map_err(|e| failed(format!("Failed to run CMake: {}", e)))?;
+    if !status.success() {
+        let log = log.into_inner().unwrap_or_else(|e| e.into_inner());
+        return Err(failed(String::from_utf8_lossy(&log).into_owned()));
+    }
+    Ok(())
+}
+
+/// Copy a process's output to the terminal line by line, appending each line to `log`
+fn tee(output: impl Read, mut terminal: impl Write, log: &Mutex<Vec<u8>>) {
+    let mut reader = BufReader::new(output);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+        let _ = terminal.write_all(&line);
+        let _ = terminal.flush();
+        log.lock().unwrap_or_else(|e| e.into_inner()).extend_from_slice(&line);
+        line.clear();
+    }
+}
+
+/// Get the CMake targets a dependency exports
+///
+/// These are the `targets` declared in the dependency's own `package.yaml`, or the
+/// dependency's name if it declares none.
+fn exported_targets(dep: &Dependency, working_dir: &str) -> Vec<String> {
+    let source = dep.source_path(working_dir);
+    let source = source.to_string_lossy();
+    serialization::package_exists(&source)
+        .then(|| serialization::load_package(&source).ok())
+        .flatten()
+        .map(|pkg| pkg.targets)
+        .filter(|targets| !targets.is_empty())
+        .unwrap_or_else(|| vec![dep.name.clone()])
+}
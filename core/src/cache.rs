@@ -0,0 +1,483 @@
+//! # Download Cache
+//!
+//! This module implements the user-level download cache shared by all projects.
+//! Every downloaded dependency is stored once, keyed by repository URL and commit,
+//! under `depo` in the user's cache directory (`$XDG_CACHE_HOME/depo` on Linux).
+//! Installs materialise cached sources into `deps/name@version` by copying them
+//! or, when enabled, by hard linking them.
+//!
+//! ## Layout
+//!
+//! ```text
+//! <cache>/git/<name>-<url hash>/refs.yaml
+//! <cache>/git/<name>-<url hash>/<commit>/entry.yaml
+//! <cache>/git/<name>-<url hash>/<commit>/src/
+//! ```
+//!
+//! `refs.yaml` records the references the remote advertised the last time it was
+//! listed, which lets offline mode resolve versions without any network access.
+
+use crate::config::Config;
+use crate::fsutil;
+use crate::git::{self, RemoteRefs, RemoteTag};
+use git2::Oid;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Size limit applied when none is configured (10 GiB)
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Metadata of a single cached commit
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheEntry {
+    /// The Git URL the sources were downloaded from
+    pub url: String,
+    /// The full commit OID of the sources
+    pub commit: String,
+    /// The size of the cached sources in bytes
+    pub size: u64,
+    /// When the entry was last stored or materialised, in seconds since the Unix epoch
+    pub last_used: u64,
+    /// The directory holding the entry
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedRefs {
+    url: String,
+    head: Option<String>,
+    tags: Vec<(String, String)>,
+    branches: Vec<(String, String)>,
+}
+
+/// The download cache
+#[derive(Debug, Clone)]
+pub struct Cache {
+    root: PathBuf,
+    max_size: u64,
+    hardlinks: bool,
+    offline: bool,
+}
+
+impl Cache {
+    /// Open the cache at the configured location
+    ///
+    /// The location, size limit, materialisation mode and offline mode are taken from `config`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the running operation
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Cache>` for the configured cache directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if no cache directory is configured and the user's
+    /// cache directory is unknown.
+    pub fn open(config: &Config) -> anyhow::Result<Cache> {
+        let root = match &config.cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Could not determine the user cache directory; set DEPO_CACHE_DIR")
+                })?
+                .join("depo"),
+        };
+
+        Ok(Cache {
+            root,
+            max_size: config.cache_max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            hardlinks: config.cache_hardlinks,
+            offline: config.offline,
+        })
+    }
+
+    /// Check if offline mode is enabled
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the network must not be accessed, `false` otherwise.
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// List the references of a remote repository
+    ///
+    /// Online, the remote is listed and the result recorded in the cache. In offline mode
+    /// the references recorded the last time the remote was listed are returned instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the remote repository
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<RemoteRefs>` with the remote's `HEAD`, tags and branches.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The remote cannot be contacted
+    /// - Offline mode is enabled and the remote has never been listed
+    pub fn list_refs(&self, url: &str) -> anyhow::Result<RemoteRefs> {
+        let path = self.repo_dir(url).join("refs.yaml");
+
+        if self.offline {
+            if !path.exists() {
+                anyhow::bail!(
+                    "Offline mode: references of '{}' are not cached; run once online to cache them",
+                    url
+                );
+            }
+            let cached: CachedRefs = serde_yaml::from_str(&fs::read_to_string(&path)?)?;
+            return Ok(RemoteRefs {
+                head: cached.head.map(|oid| Oid::from_str(&oid)).transpose()?,
+                tags: cached
+                    .tags
+                    .into_iter()
+                    .map(|(name, commit)| Ok(RemoteTag { name, commit: Oid::from_str(&commit)? }))
+                    .collect::<anyhow::Result<_>>()?,
+                branches: cached
+                    .branches
+                    .into_iter()
+                    .map(|(name, commit)| Ok((name, Oid::from_str(&commit)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            });
+        }
+
+        let refs = git::list_remote(url)?;
+        let cached = CachedRefs {
+            url: url.to_string(),
+            head: refs.head.map(|oid| oid.to_string()),
+            tags: refs
+                .tags
+                .iter()
+                .map(|t| (t.name.clone(), t.commit.to_string()))
+                .collect(),
+            branches: refs
+                .branches
+                .iter()
+                .map(|(name, oid)| (name.clone(), oid.to_string()))
+                .collect(),
+        };
+        fs::create_dir_all(self.repo_dir(url))?;
+        fsutil::write_atomic(&path, serde_yaml::to_string(&cached)?)?;
+        Ok(refs)
+    }
+
+    /// List the URLs of all repositories known to the cache
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<String>>` with every repository that has been listed or cached.
+    pub fn repositories(&self) -> anyhow::Result<Vec<String>> {
+        let git_dir = self.root.join("git");
+        let mut urls = Vec::new();
+        if !git_dir.exists() {
+            return Ok(urls);
+        }
+
+        for repo_dir in fs::read_dir(&git_dir)? {
+            let refs = repo_dir?.path().join("refs.yaml");
+            if let Ok(content) = fs::read_to_string(&refs)
+                && let Ok(cached) = serde_yaml::from_str::<CachedRefs>(&content)
+            {
+                urls.push(cached.url);
+            }
+        }
+
+        for entry in self.entries()? {
+            if !urls.contains(&entry.url) {
+                urls.push(entry.url);
+            }
+        }
+
+        Ok(urls)
+    }
+
+    /// Get the directory holding the cached sources of a commit
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the repository
+    /// * `commit` - The full commit OID
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(PathBuf)` if the commit is cached, `None` otherwise.
+    pub fn source_path(&self, url: &str, commit: &str) -> Option<PathBuf> {
+        self.contains(url, commit)
+            .then(|| self.entry_dir(url, commit).join("src"))
+    }
+
+    /// Get the root directory of the cache
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Check if the sources of a commit are cached
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the repository
+    /// * `commit` - The full commit OID
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the commit is cached, `false` otherwise.
+    pub fn contains(&self, url: &str, commit: &str) -> bool {
+        self.entry_dir(url, commit).join("src").exists()
+    }
+
+    /// Store the sources of a commit in the cache
+    ///
+    /// Storing an already cached commit does nothing. After storing, least recently
+    /// used entries are evicted until the cache fits its size limit again.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the repository
+    /// * `commit` - The full commit OID
+    /// * `src` - The directory containing the checked out sources
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the operation.
+    pub fn store(&self, url: &str, commit: &str, src: &Path) -> anyhow::Result<()> {
+        if self.contains(url, commit) {
+            return Ok(());
+        }
+
+        let entry_dir = self.entry_dir(url, commit);
+        let temp_dir = entry_dir.with_extension(format!("tmp-{}", std::process::id()));
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir)?;
+        }
+
+        fsutil::copy_dir_all(src, &temp_dir.join("src"))?;
+        let entry = CacheEntry {
+            url: url.to_string(),
+            commit: commit.to_string(),
+            size: fsutil::dir_size(&temp_dir.join("src"))?,
+            last_used: now(),
+            path: entry_dir.clone(),
+        };
+        fsutil::write_atomic(&temp_dir.join("entry.yaml"), serde_yaml::to_string(&entry)?)?;
+
+        if fs::rename(&temp_dir, &entry_dir).is_err() {
+            // Another process stored the same commit first
+            fs::remove_dir_all(&temp_dir)?;
+        }
+
+        self.evict(self.max_size, Some(&entry_dir))?;
+        Ok(())
+    }
+
+    /// Materialise the cached sources of a commit into a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the repository
+    /// * `commit` - The full commit OID
+    /// * `dest` - The directory to create
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the operation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The commit is not cached
+    /// - The sources cannot be copied or linked
+    pub fn materialize(&self, url: &str, commit: &str, dest: &Path) -> anyhow::Result<()> {
+        if !self.contains(url, commit) {
+            anyhow::bail!("Commit {} of '{}' is not cached", commit, url);
+        }
+
+        let entry_dir = self.entry_dir(url, commit);
+        if self.hardlinks {
+            fsutil::link_dir_all(&entry_dir.join("src"), dest)?;
+        } else {
+            fsutil::copy_dir_all(&entry_dir.join("src"), dest)?;
+        }
+
+        if let Ok(mut entry) = read_entry(&entry_dir) {
+            entry.last_used = now();
+            fsutil::write_atomic(&entry_dir.join("entry.yaml"), serde_yaml::to_string(&entry)?)?;
+        }
+        Ok(())
+    }
+
+    /// List all cached commits
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<CacheEntry>>` with every entry, least recently used first.
+    pub fn entries(&self) -> anyhow::Result<Vec<CacheEntry>> {
+        let git_dir = self.root.join("git");
+        let mut entries = Vec::new();
+        if !git_dir.exists() {
+            return Ok(entries);
+        }
+
+        for repo_dir in fs::read_dir(&git_dir)? {
+            let repo_dir = repo_dir?.path();
+            if !repo_dir.is_dir() {
+                continue;
+            }
+            for entry_dir in fs::read_dir(&repo_dir)? {
+                let entry_dir = entry_dir?.path();
+                if let Ok(entry) = read_entry(&entry_dir) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by_key(|e| e.last_used);
+        Ok(entries)
+    }
+
+    /// Remove everything from the cache
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<u64>` with the number of bytes freed.
+    pub fn clean(&self) -> anyhow::Result<u64> {
+        if !self.root.exists() {
+            return Ok(0);
+        }
+        let size = fsutil::dir_size(&self.root)?;
+        fs::remove_dir_all(&self.root)?;
+        Ok(size)
+    }
+
+    /// Evict least recently used entries until the cache fits a size limit
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The size limit in bytes
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<CacheEntry>>` with the evicted entries.
+    pub fn gc(&self, max_size: u64) -> anyhow::Result<Vec<CacheEntry>> {
+        self.evict(max_size, None)
+    }
+
+    /// Get the configured size limit in bytes
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    fn evict(&self, max_size: u64, keep: Option<&Path>) -> anyhow::Result<Vec<CacheEntry>> {
+        let entries = self.entries()?;
+        let mut total: u64 = entries.iter().map(|e| e.size).sum();
+        let mut evicted = Vec::new();
+
+        for entry in entries {
+            if total <= max_size {
+                break;
+            }
+            if keep == Some(entry.path.as_path()) {
+                continue;
+            }
+            fs::remove_dir_all(&entry.path)?;
+            if let Some(repo_dir) = entry.path.parent()
+                && fs::read_dir(repo_dir)?.next().is_none()
+            {
+                fs::remove_dir(repo_dir)?;
+            }
+            total -= entry.size;
+            evicted.push(entry);
+        }
+
+        Ok(evicted)
+    }
+
+    /// Get the directory a registry index is cloned into
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The Git URL of the index repository
+    ///
+    /// # Returns
+    ///
+    /// Returns the `registries/<key>` directory inside the cache.
+    pub fn registry_dir(&self, url: &str) -> PathBuf {
+        self.root.join("registries").join(cache_key(url))
+    }
+
+    fn repo_dir(&self, url: &str) -> PathBuf {
+        self.root.join("git").join(cache_key(url))
+    }
+
+    fn entry_dir(&self, url: &str, commit: &str) -> PathBuf {
+        self.repo_dir(url).join(commit)
+    }
+}
+
+/// Parse a size such as `1048576`, `512K`, `500M` or `10G` into bytes
+///
+/// # Arguments
+///
+/// * `size` - The size to parse; suffixes are binary multiples and case-insensitive
+///
+/// # Returns
+///
+/// Returns a `Result<u64>` with the size in bytes.
+///
+/// # Errors
+///
+/// This function will return an error if the size is not a number with an optional suffix,
+/// or does not fit in 64 bits.
+pub fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let size = size.trim();
+    let (number, multiplier) = match size.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&size[..size.len() - 1], 1024),
+        Some('M') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    let number: u64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {}", size))?;
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("Size too large: {}", size))
+}
+
+fn read_entry(entry_dir: &Path) -> anyhow::Result<CacheEntry> {
+    let content = fs::read_to_string(entry_dir.join("entry.yaml"))?;
+    let mut entry: CacheEntry = serde_yaml::from_str(&content)?;
+    entry.path = entry_dir.to_path_buf();
+    Ok(entry)
+}
+
+fn cache_key(url: &str) -> String {
+    let name: String = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or("repo")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    format!("{}-{}", name, &hash[..16])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
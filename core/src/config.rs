@@ -1,22 +1,69 @@
 //! # Configuration Management
 //!
 //! This module handles configuration loading and management for the package manager.
-//! It supports loading configuration from environment files and managing GitHub API tokens.
+//! It supports loading configuration from environment files and managing GitHub API tokens,
+//! the GitHub API location, download cache settings, offline mode and the project lock timeout.
 
+use crate::cache;
 use anyhow::Result;
 use std::fs;
 use std::path::Path;
 
+/// The GitHub API used unless `DEPO_GITHUB_API_URL` is set
+pub const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// The `User-Agent` sent with HTTP requests unless `DEPO_USER_AGENT` is set
+pub const DEFAULT_USER_AGENT: &str = "rust-client";
+
+/// How many seconds to wait for the project lock unless `DEPO_LOCK_TIMEOUT` is set
+pub const DEFAULT_LOCK_TIMEOUT: u64 = 30;
+
 /// Configuration structure for the package manager
 ///
 /// Contains settings and credentials needed for package management operations.
-/// Currently supports GitHub API token configuration for enhanced API access.
+/// Supports GitHub API token configuration for enhanced API access, the GitHub API location
+/// and `User-Agent`, download cache settings, offline mode and the project lock timeout.
+///
+/// Operations that access the network or the download cache take the `Config` from their
+/// caller, which may adjust it after loading, e.g. to enable offline mode.
+#[derive(Debug, Clone)]
 pub struct Config {
     /// Optional GitHub API token for authenticated requests
     ///
     /// When provided, this token allows for higher rate limits and access to private repositories.
     /// The token should be a personal access token with appropriate permissions.
     pub github_token: Option<String>,
+
+    /// Base URL of the GitHub API (`DEPO_GITHUB_API_URL`)
+    ///
+    /// Defaults to `https://api.github.com`. For GitHub Enterprise Server use
+    /// `https://<host>/api/v3`; tests can point it at a local mock server.
+    pub github_api_url: String,
+
+    /// `User-Agent` header sent with every HTTP request (`DEPO_USER_AGENT`)
+    pub user_agent: String,
+
+    /// Optional location of the download cache (`DEPO_CACHE_DIR`)
+    ///
+    /// Defaults to `depo` inside the user's cache directory (`$XDG_CACHE_HOME` on Linux).
+    pub cache_dir: Option<String>,
+
+    /// Optional size limit of the download cache in bytes (`DEPO_CACHE_MAX_SIZE`)
+    ///
+    /// Accepts plain bytes or a suffixed size such as `500M` or `10G`.
+    pub cache_max_size: Option<u64>,
+
+    /// Materialise cached dependencies with hard links instead of copies (`DEPO_CACHE_HARDLINKS`)
+    pub cache_hardlinks: bool,
+
+    /// Never access the network and work from the download cache only (`DEPO_OFFLINE`)
+    ///
+    /// Front ends set this after loading the configuration to honour their own switch,
+    /// such as the CLI's `--offline` flag.
+    pub offline: bool,
+
+    /// Seconds to wait for another depo process to release the project lock (`DEPO_LOCK_TIMEOUT`)
+    pub lock_timeout: u64,
 }
 
 impl Config {
@@ -33,13 +80,15 @@ impl Config {
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use pkgcore::config::Config;
-    ///
+    /// ```rust,no_run
+    /// use depo_core::config::Config;
+    /// # fn main() -> anyhow::Result<()> {
     /// let config = Config::load()?;
     /// if config.has_token() {
     ///     println!("GitHub token is configured");
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn load() -> Result<Config> {
         if Path::new(".env").exists() {
@@ -51,8 +100,39 @@ impl Config {
         }
 
         let github_token = std::env::var("GITHUB_TOKEN").ok();
+        let github_api_url = std::env::var("DEPO_GITHUB_API_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_GITHUB_API_URL.to_string());
+        let user_agent = std::env::var("DEPO_USER_AGENT")
+            .ok()
+            .filter(|agent| !agent.is_empty())
+            .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string());
+        let cache_dir = std::env::var("DEPO_CACHE_DIR").ok();
+        let cache_max_size = std::env::var("DEPO_CACHE_MAX_SIZE")
+            .ok()
+            .map(|size| cache::parse_size(&size))
+            .transpose()?;
+        let cache_hardlinks = env_flag("DEPO_CACHE_HARDLINKS");
+        let offline = env_flag("DEPO_OFFLINE");
+        let lock_timeout = match std::env::var("DEPO_LOCK_TIMEOUT") {
+            Ok(timeout) => timeout.trim().parse().map_err(|_| {
+                anyhow::anyhow!("Invalid DEPO_LOCK_TIMEOUT '{}': expected a number of seconds", timeout)
+            })?,
+            Err(_) => DEFAULT_LOCK_TIMEOUT,
+        };
 
-        Ok(Config { github_token })
+        Ok(Config {
+            github_token,
+            github_api_url,
+            user_agent,
+            cache_dir,
+            cache_max_size,
+            cache_hardlinks,
+            offline,
+            lock_timeout,
+        })
     }
 
     /// Create a new environment file with the provided GitHub token
@@ -69,10 +149,12 @@ impl Config {
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use pkgcore::config::Config;
-    ///
+    /// ```rust,no_run
+    /// use depo_core::config::Config;
+    /// # fn main() -> anyhow::Result<()> {
     /// Config::create_env_file("ghp_your_token_here")?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn create_env_file(token: &str) -> Result<()> {
         let env_content = format!("GITHUB_TOKEN={}\n", token);
@@ -99,13 +181,15 @@ impl Config {
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use pkgcore::config::Config;
-    ///
+    /// ```rust,no_run
+    /// use depo_core::config::Config;
+    /// # fn main() -> anyhow::Result<()> {
     /// let config = Config::load()?;
     /// if let Some(auth_header) = config.get_auth_header() {
     ///     // Use auth_header in HTTP requests
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn get_auth_header(&self) -> Option<String> {
         self.github_token
@@ -113,3 +197,7 @@ impl Config {
             .map(|token| format!("Bearer {}", token))
     }
 }
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
@@ -1,15 +1,19 @@
 //! # Dependency Management
 //!
-//! This module handles individual dependency management including installation,
-//! version resolution, and constraint validation.
+//! This module handles individual dependency management including where a dependency
+//! is installed, its nested dependencies, and constraint validation. Fetching the
+//! sources is done by the implementations of `Source` in the `source` module.
 
-use std::fs;
-use git2::Repository;
-use semver::{Version, VersionReq};
+use semver::VersionReq;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
-use anyhow::Context;
-use tempfile::TempDir;
+use std::path::{Component, Path, PathBuf};
+use crate::error::{DepoError, Result};
+use crate::lockfile::LockedDependency;
+use crate::serialization;
+use crate::vendor;
+
+/// The version recorded for local path dependencies
+pub const LOCAL_VERSION: &str = "local";
 
 /// Represents a single dependency with its metadata and version information
 ///
@@ -20,13 +24,38 @@ pub struct Dependency {
     /// The short name of the dependency (repository name)
     pub name: String,
     /// The full repository name (owner/repository)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub full_name: String,
     /// The Git clone URL for the repository
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub url: String,
+    /// A local directory used in place of a Git repository, relative to the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The URL of a release archive (`.tar.gz`, `.tar.xz` or `.zip`) used in place of a
+    /// Git repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    /// The SHA-256 checksum the archive must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// The registry the dependency is published in, by its name in `registries`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
     /// Optional version constraint (e.g., ">=1.0.0", "~2.1.0")
     pub version_constraint: Option<String>,
+    /// An exact commit SHA or ref the dependency is pinned to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// A tag the dependency is pinned to, which does not have to be a semantic version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
     /// The currently installed version
     pub version: String,
+    /// Directory the sources are used from instead of `deps/name@version`, such as a
+    /// vendored copy
+    #[serde(skip)]
+    pub source_dir: Option<PathBuf>,
 }
 
 impl Dependency {
@@ -55,248 +84,359 @@ impl Dependency {
             name: name.to_string(),
             full_name: full_name.to_string(),
             url: url.to_string(),
+            path: None,
+            archive: None,
+            sha256: None,
+            registry: None,
             version_constraint,
+            rev: None,
+            tag: None,
             version: version.to_string(),
+            source_dir: None,
         }
     }
 
-    /// Install the dependency to the local filesystem
+    /// Create a dependency from a Git URL without searching for it
     ///
-    /// This method clones the repository, applies version constraints if specified,
-    /// and installs the dependency to the deps directory with version information.
+    /// Any URL git understands can be used, including other hosts than GitHub, `file://`
+    /// paths and SSH remotes such as `git@example.com:owner/repo.git`.
     ///
     /// # Arguments
     ///
-    /// * `working_dir` - The working directory where dependencies should be installed
+    /// * `url` - The Git clone URL of the repository
+    /// * `name` - The name to use for the dependency, derived from the URL if `None`
     ///
     /// # Returns
     ///
-    /// Returns a `Result<()>` indicating success or failure of the installation.
+    /// Returns a `Result<Dependency>` with no version constraint and no installed version.
     ///
     /// # Errors
     ///
-    /// This method will return an error if:
-    /// - The repository cannot be cloned
-    /// - Version constraints cannot be resolved
-    /// - File system operations fail
-    pub fn install(&mut self, working_dir: &str) -> anyhow::Result<()> {
-        let deps_dir = Path::new(working_dir).join("deps");
-        fs::create_dir_all(&deps_dir)?;
-
-        let temp_path = deps_dir.join(format!("{}@temp", self.name));
-        let final_path = self.get_final_path(&deps_dir);
-
-        if final_path.exists() {
-            let repo = Repository::open(&final_path)?;
-            self.version = self.detect_checked_out_version(&repo)?;
-            return Ok(());
+    /// This function will return an error if the URL is not a Git URL, or no valid name is
+    /// given or can be derived from it.
+    pub fn from_url(url: &str, name: Option<&str>) -> Result<Dependency> {
+        if !is_git_url(url) {
+            return Err(DepoError::other(format!("'{}' is not a Git URL", url)));
         }
 
-        self.cleanup_path(&temp_path)?;
-        let mut repo = self.clone_repo(&temp_path)?;
-        self.apply_version_constraint(&mut repo)?;
-        self.version = self.detect_checked_out_version(&repo)?;
-        drop(repo);
-        std::thread::sleep(std::time::Duration::from_millis(200));
-
-        let versioned_path = deps_dir.join(format!("{}@{}", self.name, self.version));
-        self.move_to_final_path(&temp_path, &versioned_path)?;
-
-        Ok(())
+        let (repo_name, full_name) = repository_names(url)
+            .ok_or_else(|| DepoError::other(format!("Could not derive a dependency name from '{}'", url)))?;
+        let name = name.unwrap_or(&repo_name);
+        validate_name(name)?;
+        Ok(Dependency::new(name, &full_name, url, None, ""))
     }
 
-    pub fn find_latest_matching_version(&self) -> anyhow::Result<String> {
-        let temp_dir = TempDir::new()
-            .context("Failed to create temporary directory")?;
-        let temp_path = temp_dir.path();
-
-        let repo = Repository::clone(&self.url, temp_path)
-            .context("Failed to clone repository")?;
-
-        let mut versions: Vec<Version> = vec![];
+    /// Check whether the dependency is pinned to an exact `rev` or `tag`
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the dependency must never move to another commit, `false` otherwise.
+    pub fn is_pinned(&self) -> bool {
+        self.rev.is_some() || self.tag.is_some()
+    }
 
-        for tag_name in repo.tag_names(None)?.iter().flatten() {
-            let version_str = tag_name.trim_start_matches('v');
-            if let Ok(v) = Version::parse(version_str) {
-                versions.push(v);
-            }
+    /// Check that the dependency does not request conflicting versions
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating whether the dependency is consistent.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The name cannot be used as a directory name in `deps/` (see `validate_name`)
+    /// - More than one of `version_constraint`, `rev` and `tag` is set
+    /// - A local path dependency sets `rev` or `tag`
+    /// - An archive dependency has no `sha256`, or also sets a URL, path, constraint or pin
+    /// - A local path or archive dependency is assigned to a registry
+    pub fn validate_source(&self) -> Result<()> {
+        validate_name(&self.name)?;
+        let requested = [
+            self.version_constraint.is_some(),
+            self.rev.is_some(),
+            self.tag.is_some(),
+        ];
+        if requested.iter().filter(|set| **set).count() > 1 {
+            return Err(self.invalid(format!(
+                "'{}' can only set one of version_constraint, rev and tag",
+                self.name
+            )));
         }
-
-        if versions.is_empty() {
-            anyhow::bail!("No semantic version tags found for '{}'", self.name);
+        if self.path.is_some() && self.is_pinned() {
+            return Err(self.invalid(format!(
+                "Local dependency '{}' cannot be pinned to a rev or tag",
+                self.name
+            )));
         }
-
-        versions.sort_by(|a, b| b.cmp(a));
-
-        if let Some(constraint) = &self.version_constraint {
-            let req = VersionReq::parse(constraint)?;
-            for v in &versions {
-                if req.matches(v) {
-                    return Ok(v.to_string());
-                }
+        if self.archive.is_some() {
+            if self.sha256.is_none() {
+                return Err(self.invalid(format!(
+                    "Archive dependency '{}' requires a sha256 checksum",
+                    self.name
+                )));
             }
-            anyhow::bail!(
-            "No versions of '{}' match constraint '{}'",
-            self.name,
-            constraint
-        );
-        }
-
-        Ok(versions.first().unwrap().to_string())
-    }
-
-    fn detect_checked_out_version(&self, repo: &Repository) -> anyhow::Result<String> {
-        let head = repo.head()?.peel_to_commit()?;
-        let head_oid = head.id();
-
-        let tag_names = repo.tag_names(None)?;
-        for tag_name in tag_names.iter().flatten() {
-            if let Ok(tag_ref) = repo.revparse_single(tag_name) {
-                if let Ok(tag_commit) = tag_ref.peel_to_commit() {
-                    if tag_commit.id() == head_oid {
-                        return Ok(tag_name.to_string());
-                    }
-                }
+            if self.path.is_some() || !self.url.is_empty() {
+                return Err(self.invalid(format!(
+                    "'{}' can only set one of url, path and archive",
+                    self.name
+                )));
             }
+            if requested.contains(&true) {
+                return Err(self.invalid(format!(
+                    "Archive dependency '{}' cannot set a version_constraint, rev or tag",
+                    self.name
+                )));
+            }
+        } else if self.sha256.is_some() {
+            return Err(self.invalid(format!(
+                "'{}' sets sha256 but is not an archive dependency",
+                self.name
+            )));
         }
-        Ok(format!("{}", &head_oid.to_string()[..7]))
-    }
-
-    fn get_final_path(&self, deps_dir: &Path) -> PathBuf {
-        if self.version.is_empty() {
-            deps_dir.join(format!("{}@temp", self.name))
-        } else {
-            deps_dir.join(format!("{}@{}", self.name, self.version))
-        }
-    }
-
-    fn cleanup_path(&self, path: &Path) -> anyhow::Result<()> {
-        if path.exists() {
-            fs::remove_dir_all(path)
-                .map_err(|e| anyhow::anyhow!("Failed to remove old temp dir '{}': {}", path.display(), e))?;
+        if self.registry.is_some() && (self.path.is_some() || self.archive.is_some()) {
+            return Err(self.invalid(format!(
+                "Only Git dependencies can be assigned to a registry, not '{}'",
+                self.name
+            )));
         }
         Ok(())
     }
 
-    fn clone_repo(&self, dest: &Path) -> anyhow::Result<Repository> {
-        Repository::clone(&self.url, dest)
-            .map_err(|e| anyhow::anyhow!("Failed to clone '{}' into '{}': {}", self.url, dest.display(), e))
-    }
-    fn apply_version_constraint(&self, repo: &mut Repository) -> anyhow::Result<()> {
-        if let Some(ref constraint) = self.version_constraint {
-            self.resolve_and_checkout(repo, constraint)?;
+    /// The error for a dependency that cannot be used as it is declared
+    fn invalid(&self, message: String) -> DepoError {
+        DepoError::DependencyInvalid {
+            name: self.name.clone(),
+            message,
         }
-        Ok(())
     }
 
-    fn move_to_final_path(&self, src: &Path, dst: &Path) -> anyhow::Result<()> {
-        if !src.exists() {
-            anyhow::bail!("Temp path '{}' not found", src.display());
-        }
-
-        if let Err(e) = fs::rename(src, dst) {
-            eprintln!("Rename failed: {} → {}: {}", src.display(), dst.display(), e);
-            eprintln!("Falling back to recursive copy...");
-
-            copy_dir_all(src, dst)?;
-            fs::remove_dir_all(src)?;
-        }
-        Ok(())
+    /// Get the directory this dependency is installed to
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns the `deps/name@version` path for the currently recorded version.
+    pub fn install_path(&self, working_dir: &str) -> PathBuf {
+        Path::new(working_dir)
+            .join("deps")
+            .join(format!("{}@{}", self.name, self.version))
     }
 
-    fn resolve_and_checkout(&self, repo: &mut Repository, constraint: &str) -> anyhow::Result<()> {
-        if let Ok(version_req) = VersionReq::parse(constraint) {
-            let tags = self.get_matching_tags(repo, &version_req)?;
-            if let Some(tag) = tags.first() {
-                let commit = repo.revparse_single(tag)?.id();
-                repo.checkout_tree(&repo.find_object(commit, None)?, None)?;
-                repo.set_head_detached(commit)?;
-                return Ok(());
-            } else {
-                anyhow::bail!("No version matching constraint '{}' found", constraint);
-            }
+    /// Get the directory this dependency's sources are used from
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns the local directory of a path dependency, the vendored copy if one is in use,
+    /// and otherwise the `deps/name@version` path.
+    pub fn source_path(&self, working_dir: &str) -> PathBuf {
+        if let Some(path) = &self.path {
+            return Path::new(working_dir).join(path);
         }
+        self.source_dir
+            .clone()
+            .unwrap_or_else(|| self.install_path(working_dir))
+    }
 
-        if let Ok(branch_ref) = repo.find_branch(constraint, git2::BranchType::Local) {
-            let commit = branch_ref.get().peel_to_commit()?.id();
-            repo.checkout_tree(&repo.find_object(commit, None)?, None)?;
-            repo.set_head_detached(commit)?;
-            return Ok(());
+    /// Get the directory this dependency is built in
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns `build/` inside a local path dependency, which is built in place, and
+    /// otherwise `build/` inside the `deps/name@version` directory.
+    pub fn build_path(&self, working_dir: &str) -> PathBuf {
+        match self.path {
+            Some(_) => self.source_path(working_dir).join("build"),
+            None => self.install_path(working_dir).join("build"),
         }
+    }
 
-        if let Ok(branch_ref) =
-            repo.find_branch(&format!("origin/{}", constraint), git2::BranchType::Remote)
-        {
-            let commit = branch_ref.get().peel_to_commit()?.id();
-            repo.checkout_tree(&repo.find_object(commit, None)?, None)?;
-            repo.set_head_detached(commit)?;
-            return Ok(());
+    /// Use a vendored copy of the dependency instead of installing it
+    ///
+    /// # Arguments
+    ///
+    /// * `vendor_dir` - The vendor directory
+    /// * `vendored` - The vendor manifest entry for this dependency
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<LockedDependency>` describing the vendored commit.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the vendored directory is missing.
+    pub fn use_vendored(
+        &mut self,
+        vendor_dir: &Path,
+        vendored: &LockedDependency,
+    ) -> Result<LockedDependency> {
+        let path = vendor::entry_path(vendor_dir, vendored);
+        if !path.exists() {
+            return Err(DepoError::other(format!(
+                "Vendored copy of '{}' not found at '{}'; run `vendor` again",
+                self.name,
+                path.display()
+            )));
         }
 
-        anyhow::bail!(
-            "Could not resolve constraint '{}'. No matching version, branch, or tag found.",
-            constraint
-        );
+        self.version = vendored.version.clone();
+        self.source_dir = Some(path);
+        Ok(vendored.clone())
     }
 
-    fn get_matching_tags(
-        &self,
-        repo: &Repository,
-        version_req: &VersionReq,
-    ) -> anyhow::Result<Vec<String>>
-    {
-        let mut tags = Vec::new();
-        let tag_names = repo.tag_names(None)?;
+    /// Read the dependencies declared by this dependency's own `package.yaml`
+    ///
+    /// Libraries that are not Depo projects themselves have no transitive dependencies.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Dependency>>` containing the nested dependencies.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The nested `package.yaml` exists but cannot be loaded
+    /// - A nested local path dependency is absolute or points outside of this dependency
+    pub fn nested_dependencies(&self, working_dir: &str) -> Result<Vec<Dependency>> {
+        let path = self.source_path(working_dir);
+        let path = path.to_string_lossy();
+        if !serialization::package_exists(&path) {
+            return Ok(Vec::new());
+        }
 
-        for tag_name in tag_names.iter().flatten() {
-            let version_str = if tag_name.starts_with('v') {
-                &tag_name[1..]
-            } else {
-                tag_name
-            };
+        let mut nested = serialization::load_package(&path)?;
 
-            if let Ok(version) = Version::parse(version_str) {
-                if version_req.matches(&version) {
-                    tags.push(tag_name.to_string());
+        // Local paths in a nested package are relative to that package and must stay inside it
+        for dep in nested.dependencies.iter_mut() {
+            if let Some(local) = &dep.path {
+                if !is_contained(Path::new(local)) {
+                    return Err(self.invalid(format!(
+                        "'{}' declares the local dependency '{}' at '{}', outside of its own directory",
+                        self.name, dep.name, local
+                    )));
                 }
+                dep.path = Some(Path::new(&*path).join(local).to_string_lossy().into_owned());
             }
         }
-
-        tags.sort_by(|a, b| {
-            let version_a = Version::parse(if a.starts_with('v') { &a[1..] } else { a })
-                .unwrap_or_else(|_| Version::new(0, 0, 0));
-            let version_b = Version::parse(if b.starts_with('v') { &b[1..] } else { b })
-                .unwrap_or_else(|_| Version::new(0, 0, 0));
-            version_b.cmp(&version_a)
-        });
-
-        Ok(tags)
+        Ok(nested.dependencies)
     }
-    pub fn validate_version_constraint(&self, constraint: &str) -> anyhow::Result<()> {
-        if !VersionReq::parse(constraint).is_ok() {
-            anyhow::bail!("Invalid version constraint: {}", constraint);
+
+    /// Check that a version constraint is a valid semantic version requirement
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint` - The constraint to check, such as `^1.2` or `>=1.0, <2.0`
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating whether the constraint is valid.
+    ///
+    /// # Errors
+    ///
+    /// This method will return `DepoError::ConstraintInvalid` if the constraint cannot be parsed.
+    pub fn validate_version_constraint(&self, constraint: &str) -> Result<()> {
+        if VersionReq::parse(constraint).is_err() {
+            return Err(DepoError::ConstraintInvalid {
+                constraint: constraint.to_string(),
+            });
         }
 
         Ok(())
     }
 }
 
-
-fn copy_dir_all(src: &Path, dst: &Path) -> anyhow::Result<()> {
-    if !dst.exists() {
-        fs::create_dir_all(dst)?;
+/// Check that a dependency name can be used as a directory name in `deps/`
+///
+/// # Arguments
+///
+/// * `name` - The name to check
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating whether the name is valid.
+///
+/// # Errors
+///
+/// This function will return `DepoError::DependencyInvalid` if the name is empty, starts
+/// with a dot or contains a path separator.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(DepoError::DependencyInvalid {
+            name: name.to_string(),
+            message: format!(
+                "Invalid dependency name '{}': names cannot start with '.' or contain '/' or '\\'",
+                name
+            ),
+        });
     }
+    Ok(())
+}
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        let target = dst.join(entry.file_name());
-        if ty.is_dir() {
-            copy_dir_all(&entry.path(), &target)?;
-        } else if ty.is_file() {
-            fs::copy(entry.path(), &target)?;
+/// Whether a relative path stays inside the directory it is relative to
+fn is_contained(path: &Path) -> bool {
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
         }
     }
+    true
+}
 
-    Ok(())
-}
\ No newline at end of file
+/// Check whether a string is a Git URL rather than a search term
+///
+/// # Arguments
+///
+/// * `value` - The string to check
+///
+/// # Returns
+///
+/// Returns `true` for URLs with a scheme (e.g. `https://`, `ssh://`, `file://`) and for
+/// SCP-like SSH remotes (e.g. `git@example.com:owner/repo.git`), `false` otherwise.
+pub fn is_git_url(value: &str) -> bool {
+    if value.contains("://") {
+        return true;
+    }
+    match value.split_once(':') {
+        Some((host, path)) => host.contains('@') && !host.contains('/') && !path.is_empty(),
+        None => false,
+    }
+}
+
+/// Derive the repository name and full `owner/repository` name from a Git URL
+///
+/// # Arguments
+///
+/// * `url` - The Git URL of the repository
+///
+/// # Returns
+///
+/// Returns `Some((name, full_name))`, or `None` if the URL has no path.
+pub fn repository_names(url: &str) -> Option<(String, String)> {
+    let trimmed = url.trim_end_matches('/').trim_end_matches(".git");
+    let trimmed = trimmed.split_once("://").map_or(trimmed, |(_, rest)| rest);
+    let mut segments = trimmed.rsplit(['/', ':']);
+    let name = segments.next().filter(|n| !n.is_empty())?;
+    let full_name = match segments.next().filter(|o| !o.is_empty() && !o.contains('@')) {
+        Some(owner) => format!("{}/{}", owner, name),
+        None => name.to_string(),
+    };
+    Some((name.to_string(), full_name))
+}
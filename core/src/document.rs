@@ -0,0 +1,550 @@
+//! # Format-Preserving Package Documents
+//!
+//! This module updates the text of a package file in place instead of re-serialising the
+//! whole package, so that hand-written comments, key order, blank lines and keys Depo does
+//! not know about survive edits such as `add`, `remove`, `update` and `constraint`.
+//!
+//! A `package.yaml` is split into its top-level entries, each owning the comment and blank
+//! lines directly above it. When a package is saved, every entry whose value did not change
+//! is kept verbatim. Changed mappings are merged key by key, and `dependencies` is merged
+//! item by item, matching dependencies by name. Only the entries that actually changed are
+//! rendered again with `serde_yaml`. A `depo.toml` is merged the same way on top of
+//! `toml_edit`, which keeps the formatting of everything it is not asked to change.
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+/// A parsed package document whose text can be updated in place
+pub struct Document {
+    lines: Vec<String>,
+}
+
+/// A mapping entry or sequence item together with the comments and blank lines above it
+struct Entry {
+    lines: Vec<String>,
+    leading: usize,
+}
+
+impl Entry {
+    /// The lines of the entry without its leading comments
+    fn content(&self) -> &[String] {
+        &self.lines[self.leading..]
+    }
+}
+
+impl Document {
+    /// Parse the text of a package file
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the package file
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Document` holding the text line by line.
+    pub fn parse(text: &str) -> Document {
+        Document {
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    /// Update the document to hold a new value, keeping the text of everything unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `known` - The document's current value as understood by the schema, i.e. the
+    ///   package it holds serialised again; keys missing from it are unknown to Depo and
+    ///   always kept
+    /// * `new` - The value the document should hold afterwards
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the update.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either value is not a mapping or the document
+    /// uses a layout that cannot be updated in place (such as a flow-style top level).
+    pub fn update(&mut self, known: &Value, new: &Value) -> anyhow::Result<()> {
+        let (Some(known), Some(new)) = (known.as_mapping(), new.as_mapping()) else {
+            bail!("A package document must be a mapping");
+        };
+        self.lines = merge_mapping(&self.lines, 0, known, new)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a line is blank, a comment or a document marker
+fn is_trivia(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.is_empty()
+        || trimmed.starts_with('#')
+        || line.starts_with("---")
+        || line.starts_with("...")
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_item_start(line: &str, indent: usize) -> bool {
+    let rest = &line[indent..];
+    rest == "-" || rest.starts_with("- ")
+}
+
+/// Split the lines of a block mapping (or sequence) at `indent` into its entries
+///
+/// Returns the entries and the comment lines after the last one.
+fn split_entries(lines: &[String], indent: usize, sequence: bool) -> (Vec<Entry>, Vec<String>) {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut pending = Vec::new();
+    for line in lines {
+        if is_trivia(line) {
+            pending.push(line.clone());
+            continue;
+        }
+
+        let line_indent = indent_of(line);
+        let starts_entry = line_indent == indent
+            && if sequence {
+                is_item_start(line, indent)
+            } else {
+                !is_item_start(line, indent)
+            };
+        match entries.last_mut() {
+            Some(entry) if !starts_entry => {
+                entry.lines.append(&mut pending);
+                entry.lines.push(line.clone());
+            }
+            _ => {
+                let leading = pending.len();
+                pending.push(line.clone());
+                entries.push(Entry {
+                    lines: std::mem::take(&mut pending),
+                    leading,
+                });
+            }
+        }
+    }
+    (entries, pending)
+}
+
+/// Remove `indent` leading spaces from every line that has them
+fn dedent(lines: &[String], indent: usize) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            if indent_of(line) >= indent {
+                &line[indent..]
+            } else {
+                line.trim_start()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Prefix every line of serialised YAML with `indent` spaces
+fn indented(yaml: &str, indent: usize) -> Vec<String> {
+    let prefix = " ".repeat(indent);
+    yaml.lines()
+        .map(|line| format!("{}{}", prefix, line))
+        .collect()
+}
+
+fn render_entry(key: &Value, value: &Value, indent: usize) -> anyhow::Result<Vec<String>> {
+    let mut mapping = Mapping::new();
+    mapping.insert(key.clone(), without_nulls(value));
+    Ok(indented(&serde_yaml::to_string(&mapping)?, indent))
+}
+
+fn render_item(value: &Value, indent: usize) -> anyhow::Result<Vec<String>> {
+    Ok(indented(
+        &serde_yaml::to_string(&vec![without_nulls(value)])?,
+        indent,
+    ))
+}
+
+/// Parse the key and value of a mapping entry
+fn parse_entry(entry: &Entry, indent: usize) -> anyhow::Result<(Value, Value)> {
+    let mapping: Mapping = serde_yaml::from_str(&dedent(entry.content(), indent))
+        .context("Failed to parse package document entry")?;
+    let mut entries = mapping.into_iter();
+    match (entries.next(), entries.next()) {
+        (Some(entry), None) => Ok(entry),
+        _ => bail!("Expected a single key per package document entry"),
+    }
+}
+
+/// Parse the value of a sequence item
+fn parse_item(entry: &Entry, indent: usize) -> anyhow::Result<Value> {
+    let items: Vec<Value> = serde_yaml::from_str(&dedent(entry.content(), indent))
+        .context("Failed to parse package document item")?;
+    match <[Value; 1]>::try_from(items) {
+        Ok([item]) => Ok(item),
+        Err(_) => bail!("Expected a single value per package document item"),
+    }
+}
+
+/// The indentation of the first non-comment line
+fn body_indent(lines: &[String]) -> Option<usize> {
+    lines
+        .iter()
+        .find(|line| !is_trivia(line))
+        .map(|line| indent_of(line))
+}
+
+/// Merge a block mapping at `indent` with a new value
+fn merge_mapping(
+    lines: &[String],
+    indent: usize,
+    known: &Mapping,
+    new: &Mapping,
+) -> anyhow::Result<Vec<String>> {
+    let (entries, trailer) = split_entries(lines, indent, false);
+    let parsed = entries
+        .iter()
+        .map(|entry| parse_entry(entry, indent))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let old_keys: HashSet<&Value> = parsed.iter().map(|(key, _)| key).collect();
+
+    // New keys are inserted after the closest key preceding them in the new value
+    let mut anchored: Vec<(Option<&Value>, &Value, &Value)> = Vec::new();
+    let mut anchor = None;
+    for (key, value) in new {
+        if old_keys.contains(key) {
+            anchor = Some(key);
+        } else if !value.is_null() {
+            anchored.push((anchor, key, value));
+        }
+    }
+
+    let mut out = Vec::new();
+    let insert_new = |out: &mut Vec<String>, after: Option<&Value>| -> anyhow::Result<()> {
+        for (_, key, value) in anchored.iter().filter(|(a, _, _)| *a == after) {
+            out.extend(render_entry(key, value, indent)?);
+        }
+        Ok(())
+    };
+
+    if entries.is_empty() {
+        insert_new(&mut out, None)?;
+    }
+    for (index, (entry, (key, old))) in entries.iter().zip(&parsed).enumerate() {
+        let merged = match new.get(key).filter(|value| !value.is_null()) {
+            Some(value) => merge_entry(entry, indent, key, old, known.get(key), value)?,
+            // Keys the schema does not know are kept, known keys that are gone or null were
+            // removed
+            None if !known.contains_key(key) => entry.lines.clone(),
+            None => entry.lines[..entry.leading].to_vec(),
+        };
+
+        // Keys new at the start go below the comments heading the document
+        let (leading, rest) = merged.split_at(entry.leading);
+        out.extend_from_slice(leading);
+        if index == 0 {
+            insert_new(&mut out, None)?;
+        }
+        out.extend_from_slice(rest);
+        if new.contains_key(key) {
+            insert_new(&mut out, Some(key))?;
+        }
+    }
+    out.extend(trailer);
+    Ok(out)
+}
+
+/// Merge a single mapping entry with its new value
+fn merge_entry(
+    entry: &Entry,
+    indent: usize,
+    key: &Value,
+    old: &Value,
+    known: Option<&Value>,
+    new: &Value,
+) -> anyhow::Result<Vec<String>> {
+    // Absent optional fields are serialised as nulls, which a hand-written entry leaves out
+    if without_nulls(old) == without_nulls(new) {
+        return Ok(entry.lines.clone());
+    }
+
+    let content = entry.content();
+    let body = &content[1..];
+    let mut out = entry.lines[..entry.leading + 1].to_vec();
+    match (body_indent(body), old, new) {
+        (Some(body_indent), Value::Mapping(_), Value::Mapping(new)) if body_indent > indent => {
+            let known = known.and_then(Value::as_mapping).cloned().unwrap_or_default();
+            out.extend(merge_mapping(body, body_indent, &known, new)?);
+        }
+        (Some(body_indent), Value::Sequence(_), Value::Sequence(new))
+            if named_items(new).is_some() =>
+        {
+            let known = known.and_then(Value::as_sequence).cloned().unwrap_or_default();
+            out.extend(merge_sequence(body, body_indent, &known, new)?);
+        }
+        _ => {
+            out.truncate(entry.leading);
+            let mut rendered = render_entry(key, new, indent)?;
+            if let ([line], [rendered_line]) = (content, rendered.as_mut_slice())
+                && let Some(comment) = trailing_comment(line, indent, old)
+            {
+                rendered_line.push_str(comment);
+            }
+            out.extend(rendered);
+        }
+    }
+    Ok(out)
+}
+
+/// The comment at the end of a single-line entry holding `value`, with the spaces before it
+fn trailing_comment<'a>(line: &'a str, indent: usize, value: &Value) -> Option<&'a str> {
+    line.match_indices(" #").find_map(|(index, _)| {
+        let entry = line[..index].trim_end();
+        let mapping: Mapping = serde_yaml::from_str(&entry[indent..]).ok()?;
+        (mapping.len() == 1 && mapping.values().next() == Some(value))
+            .then(|| &line[entry.len()..])
+    })
+}
+
+/// The names of a sequence of mappings, if every item has a unique `name`
+fn named_items(items: &[Value]) -> Option<Vec<&Value>> {
+    let names: Vec<&Value> = items
+        .iter()
+        .map(|item| item.get("name").filter(|name| name.is_string()))
+        .collect::<Option<_>>()?;
+    let unique: HashSet<&Value> = names.iter().copied().collect();
+    (unique.len() == names.len()).then_some(names)
+}
+
+/// Merge a block sequence of named mappings at `indent`, matching items by name
+fn merge_sequence(
+    lines: &[String],
+    indent: usize,
+    known: &[Value],
+    new: &[Value],
+) -> anyhow::Result<Vec<String>> {
+    let (entries, trailer) = split_entries(lines, indent, true);
+    let parsed = entries
+        .iter()
+        .map(|entry| parse_item(entry, indent))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let Some(old_names) = named_items(&parsed) else {
+        bail!("Sequence items must have unique names to be updated in place");
+    };
+
+    let mut out = Vec::new();
+    for item in new {
+        let name = item.get("name");
+        let Some(index) = old_names.iter().position(|old| Some(*old) == name) else {
+            out.extend(render_item(item, indent)?);
+            continue;
+        };
+
+        let entry = &entries[index];
+        if &parsed[index] == item {
+            out.extend(entry.lines.iter().cloned());
+            continue;
+        }
+
+        let (Some(new_item), Some(first)) = (item.as_mapping(), entry.content().first()) else {
+            out.extend(render_item(item, indent)?);
+            continue;
+        };
+        let known_item = known
+            .iter()
+            .find(|known| known.get("name") == name)
+            .and_then(Value::as_mapping)
+            .cloned()
+            .unwrap_or_default();
+
+        // Turn the item into a plain mapping by blanking its dash, merge it, and put the
+        // dash back in front of whichever key comes first afterwards
+        let mut item_lines = entry.content().to_vec();
+        item_lines[0] = format!("{} {}", &first[..indent], &first[indent + 1..]);
+        let item_indent = indent_of(&item_lines[0]);
+        let mut merged = merge_mapping(&item_lines, item_indent, &known_item, new_item)?;
+        let Some(first) = merged.iter_mut().find(|line| !is_trivia(line)) else {
+            out.extend(render_item(item, indent)?);
+            continue;
+        };
+        first.replace_range(indent..indent + 1, "-");
+        out.extend(entry.lines[..entry.leading].iter().cloned());
+        out.extend(merged);
+    }
+    out.extend(trailer);
+    Ok(out)
+}
+
+/// A parsed `depo.toml` whose text can be updated in place
+pub struct TomlDocument {
+    document: DocumentMut,
+}
+
+impl TomlDocument {
+    /// Parse the text of a package file
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the package file
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<TomlDocument>` with the parsed document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the text is not valid TOML.
+    pub fn parse(text: &str) -> anyhow::Result<TomlDocument> {
+        Ok(TomlDocument {
+            document: text.parse()?,
+        })
+    }
+
+    /// Update the document to hold a new value, keeping the text of everything unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `known` - The document's current value as understood by the schema (see
+    ///   `Document::update`)
+    /// * `new` - The value the document should hold afterwards
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the update.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if either value is not a mapping or the new value
+    /// cannot be represented in TOML.
+    pub fn update(&mut self, known: &Value, new: &Value) -> anyhow::Result<()> {
+        let (Some(known), Some(new)) = (known.as_mapping(), new.as_mapping()) else {
+            bail!("A package document must be a mapping");
+        };
+        let fresh: DocumentMut = toml::to_string(&without_nulls(&Value::Mapping(new.clone())))?
+            .parse()?;
+        merge_table(self.document.as_table_mut(), known, fresh.as_table())
+    }
+}
+
+impl std::fmt::Display for TomlDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/// Remove every null from a value, as TOML has no way of writing one
+///
+/// Nulls only ever stand for absent optional fields, so dropping them loses nothing.
+pub fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), without_nulls(value)))
+                .collect(),
+        ),
+        Value::Sequence(items) => Value::Sequence(items.iter().map(without_nulls).collect()),
+        value => value.clone(),
+    }
+}
+
+/// The value of a TOML item in the common document model
+fn item_value(item: &Item) -> Option<Value> {
+    let value = match item {
+        Item::None => return None,
+        Item::Value(value) => value.clone(),
+        Item::Table(table) => toml_edit::Value::InlineTable(table.clone().into_inline_table()),
+        Item::ArrayOfTables(tables) => toml_edit::Value::Array(tables.clone().into_array()),
+    };
+    let value = toml::Value::deserialize(value.into_deserializer()).ok()?;
+    serde_yaml::to_value(value).ok()
+}
+
+/// Merge a TOML table with the freshly serialised new value of the same table
+fn merge_table(table: &mut Table, known: &Mapping, fresh: &Table) -> anyhow::Result<()> {
+    // Keys the schema does not know are kept, known keys that are gone were removed
+    let removed: Vec<String> = table
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !fresh.contains_key(key) && known.contains_key(key.as_str()))
+        .collect();
+    for key in removed {
+        table.remove(&key);
+    }
+
+    for (key, new) in fresh.iter() {
+        let Some(old) = table.get_mut(key) else {
+            table.insert(key, new.clone());
+            continue;
+        };
+        if item_value(old) == item_value(new) {
+            continue;
+        }
+
+        let known = known.get(key);
+        match (&mut *old, new) {
+            (Item::Table(old), Item::Table(new)) => {
+                let known = known.and_then(Value::as_mapping).cloned().unwrap_or_default();
+                merge_table(old, &known, new)?;
+            }
+            (Item::ArrayOfTables(old), Item::ArrayOfTables(new)) => {
+                let known = known.and_then(Value::as_sequence).cloned().unwrap_or_default();
+                merge_array_of_tables(old, &known, new)?;
+            }
+            (Item::Value(old), Item::Value(new)) => {
+                let decor = old.decor().clone();
+                *old = new.clone();
+                *old.decor_mut() = decor;
+            }
+            (old, new) => *old = new.clone(),
+        }
+    }
+    Ok(())
+}
+
+/// Merge an array of tables, such as `[[dependencies]]`, matching tables by name
+fn merge_array_of_tables(
+    tables: &mut ArrayOfTables,
+    known: &[Value],
+    fresh: &ArrayOfTables,
+) -> anyhow::Result<()> {
+    let name = |table: &Table| table.get("name").and_then(Item::as_str).map(str::to_string);
+    let names: Vec<Option<String>> = tables.iter().map(name).collect();
+    let unique: HashSet<&String> = names.iter().flatten().collect();
+    if unique.len() != names.len() {
+        *tables = fresh.clone();
+        return Ok(());
+    }
+
+    let mut old: Vec<Option<Table>> = std::mem::take(tables).into_iter().map(Some).collect();
+    for new in fresh.iter() {
+        let index = name(new).and_then(|new| names.iter().position(|old| old.as_ref() == Some(&new)));
+        let Some(mut table) = index.and_then(|index| old[index].take()) else {
+            tables.push(new.clone());
+            continue;
+        };
+        let known = known
+            .iter()
+            .find(|known| known.get("name").and_then(Value::as_str) == name(new).as_deref())
+            .and_then(Value::as_mapping)
+            .cloned()
+            .unwrap_or_default();
+        merge_table(&mut table, &known, new)?;
+        tables.push(table);
+    }
+    Ok(())
+}
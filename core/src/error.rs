@@ -0,0 +1,243 @@
+//! # Errors
+//!
+//! This module defines `DepoError`, the error returned by the public API of `Package`,
+//! `Dependency`, `CMake` and the `serialization` module. Each variant describes one kind of
+//! failure, so front ends can react to specific cases, such as offering to create a package
+//! when none is found, instead of matching on error messages.
+//!
+//! `DepoError` is serialisable: it is written as an object whose `kind` is the name of the
+//! variant, next to the variant's fields. The GUI passes it to the frontend as is.
+//!
+//! The internals of the crate still use `anyhow`. Errors that are raised as a `DepoError`
+//! deep inside an operation keep their variant when they reach the public API, even if
+//! context was added along the way; everything else becomes a `GitError`, `Io` or `Other`.
+
+use serde::Serialize;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// A `Result` whose error is a `DepoError`
+pub type Result<T, E = DepoError> = std::result::Result<T, E>;
+
+/// The step of a build that failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildStage {
+    /// Generating the build files with `cmake <source>`
+    Configure,
+    /// Compiling with `cmake --build`
+    Build,
+}
+
+impl Display for BuildStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BuildStage::Configure => "configure",
+            BuildStage::Build => "build",
+        })
+    }
+}
+
+/// An error returned by Depo
+#[derive(Debug, Clone, Error, Serialize)]
+#[serde(tag = "kind")]
+pub enum DepoError {
+    /// The directory has no `package.yaml` or `depo.toml`
+    #[error("Package file not found at path: {path}")]
+    ManifestNotFound {
+        /// The directory that was searched
+        path: String,
+    },
+
+    /// A package is initialised in a directory that already has a package file
+    #[error("A package already exists in {path}")]
+    ManifestExists {
+        /// The directory of the existing package
+        path: String,
+    },
+
+    /// The directory has both a `package.yaml` and a `depo.toml`
+    #[error("Both package.yaml and depo.toml exist in {path}; remove one of them")]
+    ManifestConflict {
+        /// The directory with both package files
+        path: String,
+    },
+
+    /// A package file cannot be read as a package
+    #[error("Invalid package file '{path}': {message}")]
+    ManifestInvalid {
+        /// The package file
+        path: String,
+        /// Why the package file is invalid
+        message: String,
+    },
+
+    /// The project metadata of a package is invalid
+    #[error("{message}")]
+    PackageInvalid {
+        /// Which field is invalid and why
+        message: String,
+    },
+
+    /// A dependency is added that the package already has
+    #[error("Dependency '{name}' already exists")]
+    DependencyExists {
+        /// The name of the dependency
+        name: String,
+    },
+
+    /// A dependency the package does not have is referred to
+    #[error("Dependency '{name}' not found")]
+    DependencyNotFound {
+        /// The name of the dependency
+        name: String,
+    },
+
+    /// A dependency requests a combination of sources or versions that cannot be used
+    #[error("{message}")]
+    DependencyInvalid {
+        /// The name of the dependency
+        name: String,
+        /// What is wrong with the dependency
+        message: String,
+    },
+
+    /// A version constraint is not a valid semantic version requirement
+    #[error("Invalid version constraint: {constraint}")]
+    ConstraintInvalid {
+        /// The constraint as given
+        constraint: String,
+    },
+
+    /// No version of a dependency satisfies its requirements
+    #[error("{message}")]
+    NoMatchingVersion {
+        /// The name of the dependency
+        name: String,
+        /// The requirements that could not be met and the versions available
+        message: String,
+    },
+
+    /// A hosting service refused a request because its rate limit was exceeded
+    #[error("{service} API rate limit exceeded. {hint}")]
+    RateLimited {
+        /// The service that refused the request
+        service: String,
+        /// When the limit resets, in seconds since the Unix epoch, if the service said so
+        reset: Option<u64>,
+        /// What the user can do about it, such as configuring a token for the service
+        hint: String,
+    },
+
+    /// Building a dependency failed
+    #[error("CMake {stage} failed for {name}")]
+    BuildFailed {
+        /// The name of the dependency
+        name: String,
+        /// The step that failed
+        stage: BuildStage,
+        /// The output of the failed step
+        log: String,
+    },
+
+    /// Another depo process held the project lock for longer than the lock timeout
+    #[error(
+        "Another depo process ({}) holds the lock on '{path}'; gave up after {timeout}s (set DEPO_LOCK_TIMEOUT to wait longer)",
+        pid.map_or_else(|| "unknown pid".to_string(), |pid| format!("pid {}", pid))
+    )]
+    ProjectLocked {
+        /// The directory of the project
+        path: String,
+        /// The process ID of the holder, if it is known
+        pid: Option<u32>,
+        /// How many seconds were waited
+        timeout: u64,
+    },
+
+    /// A Git operation failed
+    #[error("{message}")]
+    GitError {
+        /// The error reported by Git
+        message: String,
+    },
+
+    /// Reading or writing a file failed
+    #[error("{message}")]
+    Io {
+        /// The error reported by the operating system
+        message: String,
+    },
+
+    /// Any other failure
+    #[error("{message}")]
+    Other {
+        /// A description of the failure
+        message: String,
+    },
+}
+
+impl DepoError {
+    /// Create an error that has no variant of its own
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - A description of the failure
+    pub fn other(message: impl Display) -> DepoError {
+        DepoError::Other {
+            message: message.to_string(),
+        }
+    }
+
+    /// Whether the variant is one of the catch-all variants describing the failure only by
+    /// its message
+    fn is_generic(&self) -> bool {
+        matches!(
+            self,
+            DepoError::GitError { .. } | DepoError::Io { .. } | DepoError::Other { .. }
+        )
+    }
+}
+
+impl From<anyhow::Error> for DepoError {
+    fn from(e: anyhow::Error) -> DepoError {
+        let found = e.chain().find_map(|cause| cause.downcast_ref::<DepoError>());
+        if let Some(error) = found
+            && !error.is_generic()
+        {
+            return error.clone();
+        }
+
+        // Generic errors keep the context added around them
+        let message = format!("{:#}", e);
+        match found {
+            Some(DepoError::GitError { .. }) => DepoError::GitError { message },
+            Some(DepoError::Io { .. }) => DepoError::Io { message },
+            Some(_) => DepoError::Other { message },
+            None if e.chain().any(|cause| cause.is::<git2::Error>()) => DepoError::GitError { message },
+            None if e.chain().any(|cause| cause.is::<std::io::Error>()) => DepoError::Io { message },
+            None => DepoError::Other { message },
+        }
+    }
+}
+
+impl From<std::io::Error> for DepoError {
+    fn from(e: std::io::Error) -> DepoError {
+        DepoError::Io {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<git2::Error> for DepoError {
+    fn from(e: git2::Error) -> DepoError {
+        DepoError::GitError {
+            message: e.message().to_string(),
+        }
+    }
+}
+
+impl From<serde_yaml::Error> for DepoError {
+    fn from(e: serde_yaml::Error) -> DepoError {
+        DepoError::other(e)
+    }
+}
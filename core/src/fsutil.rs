@@ -0,0 +1,184 @@
+//! # Filesystem Utilities
+//!
+//! This module contains the directory helpers shared by dependency installation,
+//! vendoring and the download cache, and the atomic file writes used for package files,
+//! lockfiles and other manifests.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Write a file atomically
+///
+/// The contents are written to a temporary file next to `path`, flushed to disk and then
+/// renamed over `path`, so readers and a crash at any point see either the old or the new
+/// contents, never a partially written file. The permissions of an existing file are kept.
+///
+/// # Arguments
+///
+/// * `path` - The file to write
+/// * `contents` - The new contents of the file
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the write.
+///
+/// # Errors
+///
+/// This function will return an error if the temporary file cannot be written or renamed.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Cannot write to '{}'", path.display()))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result.map_err(|e| anyhow::anyhow!("Failed to write '{}': {}", path.display(), e))
+}
+
+/// Recursively copy a directory
+///
+/// # Arguments
+///
+/// * `src` - The directory to copy
+/// * `dst` - The destination directory, created if missing
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the copy.
+pub fn copy_dir_all(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    if !dst.exists() {
+        fs::create_dir_all(dst)?;
+    }
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+        if ty.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else if ty.is_file() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively copy a directory, skipping entries rejected by a filter
+///
+/// # Arguments
+///
+/// * `src` - The directory to copy
+/// * `dst` - The destination directory, created if missing
+/// * `keep` - Called with each entry's path relative to `src`; entries for which it
+///   returns `false` are skipped along with their contents
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the copy.
+pub fn copy_dir_filtered(
+    src: &Path,
+    dst: &Path,
+    keep: &dyn Fn(&Path) -> bool,
+) -> anyhow::Result<()> {
+    copy_filtered(src, dst, Path::new(""), keep)
+}
+
+fn copy_filtered(
+    src: &Path,
+    dst: &Path,
+    relative: &Path,
+    keep: &dyn Fn(&Path) -> bool,
+) -> anyhow::Result<()> {
+    if !dst.exists() {
+        fs::create_dir_all(dst)?;
+    }
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        if !keep(&relative) {
+            continue;
+        }
+        let ty = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+        if ty.is_dir() {
+            copy_filtered(&entry.path(), &target, &relative, keep)?;
+        } else if ty.is_file() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively recreate a directory using hard links for its files
+///
+/// Files that cannot be hard linked, for example because the destination is on
+/// another filesystem, are copied instead.
+///
+/// # Arguments
+///
+/// * `src` - The directory to link
+/// * `dst` - The destination directory, created if missing
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the operation.
+pub fn link_dir_all(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    if !dst.exists() {
+        fs::create_dir_all(dst)?;
+    }
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+        if ty.is_dir() {
+            link_dir_all(&entry.path(), &target)?;
+        } else if ty.is_file() && fs::hard_link(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compute the total size of the files in a directory
+///
+/// # Arguments
+///
+/// * `path` - The directory to measure
+///
+/// # Returns
+///
+/// Returns a `Result<u64>` with the size in bytes.
+pub fn dir_size(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.path().symlink_metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
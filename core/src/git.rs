@@ -0,0 +1,288 @@
+//! # Git Transport
+//!
+//! This module wraps the git operations used to discover and download dependencies.
+//! Versions are discovered by listing the references of a remote (the equivalent of
+//! `git ls-remote`) instead of cloning it, and a chosen reference is downloaded with
+//! a shallow fetch of depth 1.
+
+use anyhow::Context;
+use git2::{Direction, FetchOptions, Oid, Remote, Repository};
+use std::fmt;
+use std::path::Path;
+
+/// A tag advertised by a remote repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteTag {
+    /// The tag name without the `refs/tags/` prefix
+    pub name: String,
+    /// The commit the tag points at, with annotated tags peeled
+    pub commit: Oid,
+}
+
+/// The references advertised by a remote repository
+#[derive(Debug, Clone, Default)]
+pub struct RemoteRefs {
+    /// The commit the remote `HEAD` points at, if advertised
+    pub head: Option<Oid>,
+    /// All tags of the remote
+    pub tags: Vec<RemoteTag>,
+    /// All branches of the remote, by name and commit
+    pub branches: Vec<(String, Oid)>,
+}
+
+impl RemoteRefs {
+    /// Find the commit a tag points at
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The tag name
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Oid)` if the remote advertises the tag, `None` otherwise.
+    pub fn tag(&self, name: &str) -> Option<Oid> {
+        self.tags.iter().find(|t| t.name == name).map(|t| t.commit)
+    }
+
+    /// Find the commit a branch points at
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The branch name
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Oid)` if the remote advertises the branch, `None` otherwise.
+    pub fn branch(&self, name: &str) -> Option<Oid> {
+        self.branches
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, oid)| *oid)
+    }
+
+    /// Find the commit a fetch target currently points at
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The reference to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(Oid)` if the remote advertises the reference, `None` otherwise.
+    pub fn resolve(&self, target: &FetchTarget) -> Option<Oid> {
+        match target {
+            FetchTarget::Head => self.head,
+            FetchTarget::Branch(name) => self.branch(name),
+            FetchTarget::Tag(name) => self.tag(name),
+            FetchTarget::Commit(oid) => Some(*oid),
+        }
+    }
+
+    /// Find the tags pointing at a commit
+    ///
+    /// # Arguments
+    ///
+    /// * `commit` - The commit to look for
+    ///
+    /// # Returns
+    ///
+    /// Returns the names of every tag pointing at the commit.
+    pub fn tags_at(&self, commit: Oid) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|t| t.commit == commit)
+            .map(|t| t.name.as_str())
+            .collect()
+    }
+}
+
+/// A reference to download from a remote
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchTarget {
+    /// The remote's default branch
+    Head,
+    /// A branch by name
+    Branch(String),
+    /// A tag by name
+    Tag(String),
+    /// A commit by OID
+    Commit(Oid),
+}
+
+impl fmt::Display for FetchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchTarget::Head => write!(f, "the default branch"),
+            FetchTarget::Branch(name) => write!(f, "branch '{}'", name),
+            FetchTarget::Tag(name) => write!(f, "tag '{}'", name),
+            FetchTarget::Commit(oid) => write!(f, "commit {}", oid),
+        }
+    }
+}
+
+impl FetchTarget {
+    fn refspec(&self) -> String {
+        match self {
+            FetchTarget::Head => "HEAD".to_string(),
+            FetchTarget::Branch(name) => format!("+refs/heads/{0}:refs/remotes/origin/{0}", name),
+            FetchTarget::Tag(name) => format!("+refs/tags/{0}:refs/tags/{0}", name),
+            FetchTarget::Commit(oid) => oid.to_string(),
+        }
+    }
+}
+
+/// List the references of a remote repository without cloning it
+///
+/// # Arguments
+///
+/// * `url` - The Git URL of the remote repository
+///
+/// # Returns
+///
+/// Returns a `Result<RemoteRefs>` with the remote's `HEAD`, tags and branches.
+///
+/// # Errors
+///
+/// This function will return an error if the remote cannot be contacted.
+pub fn list_remote(url: &str) -> anyhow::Result<RemoteRefs> {
+    let mut remote = Remote::create_detached(url)?;
+    let connection = remote
+        .connect_auth(Direction::Fetch, None, None)
+        .with_context(|| format!("Failed to list references of '{}'", url))?;
+
+    let mut refs = RemoteRefs::default();
+    let mut peeled = Vec::new();
+
+    for head in connection.list()? {
+        let name = head.name();
+        if name == "HEAD" {
+            refs.head = Some(head.oid());
+        } else if let Some(tag) = name.strip_prefix("refs/tags/") {
+            match tag.strip_suffix("^{}") {
+                Some(tag) => peeled.push((tag.to_string(), head.oid())),
+                None => refs.tags.push(RemoteTag {
+                    name: tag.to_string(),
+                    commit: head.oid(),
+                }),
+            }
+        } else if let Some(branch) = name.strip_prefix("refs/heads/") {
+            refs.branches.push((branch.to_string(), head.oid()));
+        }
+    }
+
+    for (name, commit) in peeled {
+        if let Some(tag) = refs.tags.iter_mut().find(|t| t.name == name) {
+            tag.commit = commit;
+        }
+    }
+
+    Ok(refs)
+}
+
+/// Fetch a single reference of a remote into an existing repository
+///
+/// # Arguments
+///
+/// * `repo` - The repository to fetch into
+/// * `url` - The Git URL of the remote repository
+/// * `target` - The reference to download
+/// * `depth` - Limit the fetched history to this many commits, or fetch it all with `None`
+///
+/// # Returns
+///
+/// Returns a `Result<Oid>` with the commit that was fetched.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The remote cannot be contacted or does not have the reference
+/// - The remote does not support shallow fetches when a depth is given
+/// - The fetched object is not the requested commit
+pub fn fetch(
+    repo: &Repository,
+    url: &str,
+    target: &FetchTarget,
+    depth: Option<i32>,
+) -> anyhow::Result<Oid> {
+    let mut remote = repo.remote_anonymous(url)?;
+    let mut options = FetchOptions::new();
+    if let Some(depth) = depth {
+        options.depth(depth);
+    }
+    remote
+        .fetch(&[target.refspec()], Some(&mut options), None)
+        .with_context(|| format!("Failed to fetch {} from '{}'", target, url))?;
+
+    let commit = repo
+        .find_reference("FETCH_HEAD")?
+        .peel_to_commit()
+        .context("FETCH_HEAD does not point at a commit")?
+        .id();
+
+    if let FetchTarget::Commit(expected) = target
+        && *expected != commit
+    {
+        anyhow::bail!("Fetched commit {} but {} was requested", commit, expected);
+    }
+
+    Ok(commit)
+}
+
+/// Download a single reference of a remote into a new repository
+///
+/// The fetched commit is checked out as a detached `HEAD`. Tags advertised for that
+/// commit are created locally so the checkout can be identified by its tag.
+///
+/// # Arguments
+///
+/// * `url` - The Git URL of the remote repository
+/// * `target` - The reference to download
+/// * `refs` - The references advertised by the remote, used to name the checkout
+/// * `dest` - The directory to create the repository in
+/// * `depth` - Limit the fetched history to this many commits, or fetch it all with `None`
+///
+/// # Returns
+///
+/// Returns a `Result<Repository>` with the fetched commit checked out.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The repository cannot be created
+/// - The reference cannot be fetched, or not shallowly when a depth is given
+/// - The fetched commit cannot be checked out
+pub fn fetch_ref(
+    url: &str,
+    target: &FetchTarget,
+    refs: &RemoteRefs,
+    dest: &Path,
+    depth: Option<i32>,
+) -> anyhow::Result<Repository> {
+    let repo = Repository::init(dest)?;
+    repo.remote("origin", url)?;
+    let commit = fetch(&repo, url, target, depth)?;
+    checkout(&repo, commit)?;
+
+    for tag in refs.tags_at(commit) {
+        let object = repo.find_object(commit, None)?;
+        repo.tag_lightweight(tag, &object, true)?;
+    }
+
+    Ok(repo)
+}
+
+/// Check out a commit as a detached `HEAD`
+///
+/// # Arguments
+///
+/// * `repo` - The repository to check out in
+/// * `commit` - The commit to check out
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the checkout.
+pub fn checkout(repo: &Repository, commit: Oid) -> anyhow::Result<()> {
+    repo.checkout_tree(&repo.find_object(commit, None)?, None)?;
+    repo.set_head_detached(commit)?;
+    Ok(())
+}
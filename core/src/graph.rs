@@ -0,0 +1,120 @@
+//! # Dependency Graph
+//!
+//! This module models the full dependency graph of a package, including the
+//! transitive dependencies declared in the `package.yaml` of each dependency,
+//! and orders it so that every dependency comes before the ones that need it.
+
+use crate::dependency::Dependency;
+use std::collections::{HashMap, HashSet};
+
+/// A graph of dependencies keyed by dependency name
+///
+/// Each node is a dependency together with the names of the dependencies it requires.
+/// Nodes keep the order in which they were added, which makes the resulting build
+/// order deterministic.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    nodes: Vec<Dependency>,
+    edges: HashMap<String, Vec<String>>,
+}
+
+impl DependencyGraph {
+    /// Create a new empty graph
+    pub fn new() -> DependencyGraph {
+        DependencyGraph {
+            nodes: Vec::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    /// Check if a dependency with the given name is part of the graph
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to check for
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the dependency has already been added, `false` otherwise.
+    pub fn contains(&self, name: &str) -> bool {
+        self.edges.contains_key(name)
+    }
+
+    /// Get the dependency with the given name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&Dependency)` if the dependency is part of the graph, `None` otherwise.
+    pub fn get(&self, name: &str) -> Option<&Dependency> {
+        self.nodes.iter().find(|d| d.name == name)
+    }
+
+    /// Add a dependency and the names of the dependencies it requires
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to add
+    /// * `requires` - Names of the dependencies declared in its own `package.yaml`
+    pub fn add(&mut self, dep: Dependency, requires: Vec<String>) {
+        self.edges.insert(dep.name.clone(), requires);
+        self.nodes.push(dep);
+    }
+
+    /// Order the graph so that every dependency precedes its dependents
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Dependency>>` containing every node in topological order.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the graph contains a cycle.
+    pub fn topological_order(&self) -> anyhow::Result<Vec<Dependency>> {
+        let mut visited = HashSet::new();
+        let mut path = Vec::new();
+        let mut order = Vec::new();
+
+        for dep in &self.nodes {
+            self.visit(&dep.name, &mut visited, &mut path, &mut order)?;
+        }
+
+        Ok(order
+            .iter()
+            .filter_map(|name| self.get(name).cloned())
+            .collect())
+    }
+
+    fn visit(
+        &self,
+        name: &str,
+        visited: &mut HashSet<String>,
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        if visited.contains(name) {
+            return Ok(());
+        }
+
+        if let Some(start) = path.iter().position(|n| n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.to_string());
+            anyhow::bail!("Dependency cycle detected: {}", cycle.join(" -> "));
+        }
+
+        path.push(name.to_string());
+        for child in self.edges.get(name).into_iter().flatten() {
+            if self.contains(child) {
+                self.visit(child, visited, path, order)?;
+            }
+        }
+        path.pop();
+
+        visited.insert(name.to_string());
+        order.push(name.to_string());
+        Ok(())
+    }
+}
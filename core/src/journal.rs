@@ -0,0 +1,206 @@
+//! # Install Journal
+//!
+//! This module keeps a journal of the dependency installs in progress in
+//! `deps/.depo-journal.yaml`, so that an install interrupted by a crash or Ctrl-C can be
+//! cleaned up on the next run instead of leaving half-written directories behind.
+//!
+//! A source records an install with `begin` before it writes anything to `deps/`, marks
+//! the point from which the final directory is being written with `set_stage`, and removes
+//! the record with `finish` once the directory is complete. `recover` removes whatever an
+//! unfinished install left behind, as well as `@temp` and `.unpack` directories written by
+//! versions of Depo without the journal.
+
+use crate::fsutil;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const JOURNAL_FILE: &str = ".depo-journal.yaml";
+
+/// How far an install has progressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// The dependency is being downloaded into its temporary directory
+    Fetching,
+    /// The final directory is being written and is incomplete until the install finishes
+    Installing,
+}
+
+/// An install in progress
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The name of the dependency being installed
+    pub name: String,
+    /// The temporary directory the dependency is downloaded into
+    pub temp: PathBuf,
+    /// The directory the dependency is installed to
+    pub target: PathBuf,
+    /// How far the install has progressed
+    pub stage: Stage,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Journal {
+    installs: Vec<JournalEntry>,
+}
+
+fn journal_path(working_dir: &str) -> PathBuf {
+    Path::new(working_dir).join("deps").join(JOURNAL_FILE)
+}
+
+fn load(working_dir: &str) -> anyhow::Result<Journal> {
+    let path = journal_path(working_dir);
+    if !path.exists() {
+        return Ok(Journal::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    // A journal that cannot be read is treated as empty; the directory scan in `recover`
+    // still catches the leftovers of interrupted installs
+    Ok(serde_yaml::from_str(&content).unwrap_or_default())
+}
+
+fn save(working_dir: &str, journal: &Journal) -> anyhow::Result<()> {
+    let path = journal_path(working_dir);
+    if journal.installs.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    fsutil::write_atomic(&path, serde_yaml::to_string(journal)?)
+}
+
+/// Record the start of an install
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+/// * `name` - The name of the dependency being installed
+/// * `temp` - The temporary directory the dependency is downloaded into
+/// * `target` - The directory the dependency is installed to
+/// * `stage` - The stage the install starts in
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of writing the journal.
+pub fn begin(
+    working_dir: &str,
+    name: &str,
+    temp: &Path,
+    target: &Path,
+    stage: Stage,
+) -> anyhow::Result<()> {
+    let mut journal = load(working_dir)?;
+    journal.installs.retain(|entry| entry.name != name);
+    journal.installs.push(JournalEntry {
+        name: name.to_string(),
+        temp: temp.to_path_buf(),
+        target: target.to_path_buf(),
+        stage,
+    });
+    save(working_dir, &journal)
+}
+
+/// Record that an install has progressed to another stage
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+/// * `name` - The name of the dependency being installed
+/// * `target` - The directory the dependency is installed to, which may have changed
+///   since `begin` once the installed version is known
+/// * `stage` - The stage the install is in now
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of writing the journal.
+pub fn set_stage(working_dir: &str, name: &str, target: &Path, stage: Stage) -> anyhow::Result<()> {
+    let mut journal = load(working_dir)?;
+    for entry in journal.installs.iter_mut().filter(|entry| entry.name == name) {
+        entry.target = target.to_path_buf();
+        entry.stage = stage;
+    }
+    save(working_dir, &journal)
+}
+
+/// Record that an install has finished
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+/// * `name` - The name of the dependency that was installed
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of writing the journal.
+pub fn finish(working_dir: &str, name: &str) -> anyhow::Result<()> {
+    let mut journal = load(working_dir)?;
+    journal.installs.retain(|entry| entry.name != name);
+    save(working_dir, &journal)
+}
+
+/// Forget every install in the journal without cleaning up after them
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of removing the journal.
+pub fn clear(working_dir: &str) -> anyhow::Result<()> {
+    save(working_dir, &Journal::default())
+}
+
+/// Clean up after installs that were interrupted
+///
+/// For every unfinished install in the journal, the temporary directory is removed, and so
+/// is the final directory if the install had started writing it. Any other `name@temp` or
+/// `.unpack` directory in `deps/` is removed as well.
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+///
+/// # Returns
+///
+/// Returns a `Result<Vec<PathBuf>>` with the directories that were removed.
+///
+/// # Errors
+///
+/// This function will return an error if a leftover directory cannot be removed.
+pub fn recover(working_dir: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let deps_dir = Path::new(working_dir).join("deps");
+    if !deps_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut stale = Vec::new();
+    for entry in load(working_dir)?.installs {
+        stale.push(entry.temp);
+        if entry.stage == Stage::Installing {
+            stale.push(entry.target);
+        }
+    }
+    for entry in fs::read_dir(&deps_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() && (name.ends_with("@temp") || name.ends_with(".unpack")) {
+            stale.push(entry.path());
+        }
+    }
+
+    let mut removed = Vec::new();
+    for path in stale {
+        if path.exists() && !removed.contains(&path) {
+            fs::remove_dir_all(&path).map_err(|e| {
+                anyhow::anyhow!("Failed to remove interrupted install '{}': {}", path.display(), e)
+            })?;
+            removed.push(path);
+        }
+    }
+    clear(working_dir)?;
+    Ok(removed)
+}
@@ -2,10 +2,24 @@
 //!
 //! This library provides the core functionality for managing C++ package dependencies.
 //! It includes modules for:
-//! - Package management and dependency resolution
+//! - Package management and transitive dependency resolution
+//! - Version solving across shared dependencies
 //! - Configuration handling
+//! - A download cache shared across projects
 //! - Build system integration (CMake)
 //! - Serialization and persistence
+//! - Lockfiles pinning dependencies to exact commits
+//! - Vendoring dependency sources into the project
+//! - Release archives as checksum-verified dependency sources
+//! - Pluggable dependency sources (Git, local paths and archives)
+//! - Package search across GitHub, GitLab, Gitea and static index files
+//! - Private registries indexing published package versions
+//! - Schema versioning and migration of package files
+//! - Format-preserving edits of package files
+//! - Crash-safe writes and recovery of interrupted installs
+//! - Transactional dependency operations with rollback
+//! - A project lock preventing concurrent Depo runs
+//! - Typed, serialisable errors for front ends
 //!
 //! ## Overview
 //!
@@ -18,17 +32,68 @@
 //!
 
 
+/// Archive sources module
+pub mod archive;
+
 /// Build system integration module
 pub mod build;
 
+/// Download cache module
+pub mod cache;
+
 /// Configuration management module
 pub mod config;
 
 /// Dependency management module
 pub mod dependency;
 
+/// Format-preserving package document module
+pub mod document;
+
+/// Error types module
+pub mod error;
+
+/// Filesystem utilities module
+pub mod fsutil;
+
+/// Git transport module
+pub mod git;
+
+/// Dependency graph module
+pub mod graph;
+
+/// Install journal module
+pub mod journal;
+
+/// Project lock module
+pub mod lock;
+
+/// Lockfile module
+pub mod lockfile;
+
+/// Manifest migrations module
+pub mod migration;
+
 /// Package management module
 pub mod package;
 
+/// Registry index module
+pub mod registry;
+
+/// Package search module
+pub mod search;
+
 /// Serialization and persistence module
 pub mod serialization;
+
+/// Version solving module
+pub mod solver;
+
+/// Dependency sources module
+pub mod source;
+
+/// Transactions module
+pub mod transaction;
+
+/// Vendoring module
+pub mod vendor;
@@ -0,0 +1,182 @@
+//! # Project Lock
+//!
+//! This module keeps several Depo processes, such as the CLI and the GUI, from changing
+//! the same project at the same time. Every operation that changes the dependencies of a
+//! project holds an advisory lock on `deps/.depo-lock` while it runs, and waits for up to
+//! `DEPO_LOCK_TIMEOUT` seconds if another process holds it. Creating, migrating or
+//! converting a package file on its own does not take the lock, so projects without
+//! dependencies never get a `deps/` directory.
+//!
+//! The lock is taken with the operating system's file locking, so it is released when
+//! its holder exits, even if it crashes. The lock file records the holder's process ID
+//! for error messages and is emptied on release; a lock file that still names a process
+//! but is not locked was left behind by a crashed process and is taken over.
+//!
+//! A thread that already holds the lock of a project can acquire it again, so core
+//! operations can take the lock themselves while a front end holds it across loading,
+//! changing and saving a package.
+
+use crate::config::Config;
+use crate::error::DepoError;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+const LOCK_FILE: &str = ".depo-lock";
+
+/// How often a waiting process checks whether the lock was released
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A project lock held by a thread of this process
+struct Held {
+    path: PathBuf,
+    thread: ThreadId,
+    count: usize,
+    file: File,
+}
+
+static HELD: Mutex<Vec<Held>> = Mutex::new(Vec::new());
+
+/// A held lock on a project, released when dropped
+pub struct ProjectLock {
+    path: PathBuf,
+}
+
+impl ProjectLock {
+    /// Acquire the lock of a project, waiting for up to the configured timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<ProjectLock>` holding the lock until it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if another process holds the lock for longer
+    /// than `DEPO_LOCK_TIMEOUT` seconds, or the lock file cannot be created or locked.
+    pub fn acquire(working_dir: &str) -> anyhow::Result<ProjectLock> {
+        let timeout = Duration::from_secs(Config::load()?.lock_timeout);
+        Self::acquire_with_timeout(working_dir, timeout)
+    }
+
+    /// Acquire the lock of a project, waiting for up to `timeout`
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    /// * `timeout` - How long to wait for another process to release the lock
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<ProjectLock>` holding the lock until it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if another process holds the lock for longer
+    /// than `timeout`, or the lock file cannot be created or locked.
+    pub fn acquire_with_timeout(working_dir: &str, timeout: Duration) -> anyhow::Result<ProjectLock> {
+        let deps_dir = Path::new(working_dir).join("deps");
+        fs::create_dir_all(&deps_dir)?;
+        let path = deps_dir.canonicalize()?.join(LOCK_FILE);
+
+        let current = thread::current().id();
+        if let Some(held) = HELD
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|held| held.path == path && held.thread == current)
+        {
+            held.count += 1;
+            return Ok(ProjectLock { path });
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {
+                    let pid = holder_pid(&mut file);
+                    if started.elapsed() >= timeout {
+                        return Err(DepoError::ProjectLocked {
+                            path: working_dir.to_string(),
+                            pid,
+                            timeout: timeout.as_secs(),
+                        }
+                        .into());
+                    }
+                    if !waiting {
+                        eprintln!(
+                            "Another depo process ({}) holds the lock on '{}'; waiting for it to finish...",
+                            pid.map_or_else(|| "unknown pid".to_string(), |pid| format!("pid {}", pid)),
+                            working_dir
+                        );
+                        waiting = true;
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+                Err(TryLockError::Error(e)) => {
+                    anyhow::bail!("Failed to lock '{}': {}", path.display(), e);
+                }
+            }
+        }
+
+        let stale = holder_pid(&mut file);
+        if let Some(pid) = stale {
+            eprintln!("Took over a stale lock left by depo process (pid {})", pid);
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+
+        HELD.lock().unwrap().push(Held {
+            path: path.clone(),
+            thread: current,
+            count: 1,
+            file,
+        });
+        Ok(ProjectLock { path })
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        let current = thread::current().id();
+        let mut held = HELD.lock().unwrap();
+        let Some(index) = held
+            .iter()
+            .position(|held| held.path == self.path && held.thread == current)
+        else {
+            return;
+        };
+
+        held[index].count -= 1;
+        if held[index].count == 0 {
+            // Emptying the file marks the release as clean; the lock itself is released
+            // when the file is closed
+            let released = held.swap_remove(index);
+            let _ = released.file.set_len(0);
+        }
+    }
+}
+
+/// The process ID recorded in a lock file, if any
+fn holder_pid(file: &mut File) -> Option<u32> {
+    let mut content = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
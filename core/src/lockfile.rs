@@ -0,0 +1,249 @@
+//! # Lockfile
+//!
+//! This module defines the lockfile (`depo.lock`) written next to `package.yaml`.
+//! The lockfile records the exact commit each dependency was resolved to, so that
+//! every `install` reproduces the same dependency tree on every machine.
+
+use crate::dependency::Dependency;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// The resolved state of all dependencies of a package
+///
+/// A Lockfile is produced whenever dependencies are added, updated or installed,
+/// and is consulted by `Package::install_graph`, which passes each entry to `Source::fetch`
+/// to check out exactly the locked commit.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Lockfile {
+    /// Locked entries, one per dependency
+    pub dependencies: Vec<LockedDependency>,
+}
+
+/// A single dependency pinned to an exact commit
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedDependency {
+    /// The short name of the dependency
+    pub name: String,
+    /// The Git clone URL the commit was resolved from
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+    /// The installed version, as used in the `deps/name@version` directory
+    pub version: String,
+    /// The full commit OID that was checked out, empty for local path and archive dependencies
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub commit: String,
+    /// The tag pointing at the locked commit, if any
+    pub tag: Option<String>,
+    /// The version constraint from `package.yaml` the commit was resolved against
+    #[serde(default)]
+    pub version_constraint: Option<String>,
+    /// The rev from `package.yaml` the dependency is pinned to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// The local directory of a path dependency, as declared in `package.yaml`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The URL of an archive dependency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    /// The SHA-256 checksum of an archive dependency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Names of the dependencies declared in this dependency's own `package.yaml`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+}
+
+impl LockedDependency {
+    /// Check whether this entry still describes the given dependency
+    ///
+    /// An entry is stale once the dependency's URL, local path, archive, version constraint or pin
+    /// in `package.yaml` no longer matches the values it was resolved from.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency as declared in the package
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the entry can be used to install the dependency, `false` otherwise.
+    pub fn matches(&self, dep: &Dependency) -> bool {
+        self.name == dep.name
+            && self.url == dep.url
+            && self.path == dep.path
+            && self.archive == dep.archive
+            && self.sha256 == dep.sha256
+            && self.version_constraint == dep.version_constraint
+            && self.rev == dep.rev
+            && dep.tag.as_ref().is_none_or(|tag| self.tag.as_ref() == Some(tag))
+    }
+    /// Get the directory the locked version is installed to
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns the same `deps/name@version` path as `Dependency::install_path`.
+    pub fn install_path(&self, working_dir: &str) -> PathBuf {
+        Dependency::new(&self.name, &self.name, &self.url, None, &self.version).install_path(working_dir)
+    }
+}
+
+impl Lockfile {
+    /// Create a new empty lockfile
+    pub fn new() -> Lockfile {
+        Lockfile {
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Find the locked entry for a dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&LockedDependency)` if the dependency is locked, `None` otherwise.
+    pub fn find(&self, name: &str) -> Option<&LockedDependency> {
+        self.dependencies.iter().find(|d| d.name == name)
+    }
+
+    /// Insert a locked entry, replacing any existing entry with the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The locked dependency to record
+    pub fn upsert(&mut self, entry: LockedDependency) {
+        match self.dependencies.iter_mut().find(|d| d.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => self.dependencies.push(entry),
+        }
+    }
+
+    /// Verify that the lockfile agrees with the dependencies declared in the package
+    ///
+    /// # Arguments
+    ///
+    /// * `dependencies` - The dependencies declared in `package.yaml`
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating whether the lockfile is up to date.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error listing every disagreement if:
+    /// - A dependency has been added to the package but is not locked
+    /// - A dependency's URL, local path, archive, version constraint or pin has changed since
+    ///   it was locked
+    /// - The lockfile contains a dependency that is no longer required by the package
+    pub fn verify(&self, dependencies: &[Dependency]) -> anyhow::Result<()> {
+        let mut problems = Vec::new();
+
+        for dep in dependencies {
+            match self.find(&dep.name) {
+                None => problems.push(format!("'{}' is not in depo.lock", dep.name)),
+                Some(lock) if lock.url != dep.url => problems.push(format!(
+                    "URL of '{}' changed from '{}' to '{}'",
+                    dep.name, lock.url, dep.url
+                )),
+                Some(lock) if lock.path != dep.path => problems.push(format!(
+                    "path of '{}' changed from '{}' to '{}'",
+                    dep.name,
+                    lock.path.as_deref().unwrap_or("none"),
+                    dep.path.as_deref().unwrap_or("none")
+                )),
+                Some(lock) if lock.version_constraint != dep.version_constraint => {
+                    problems.push(format!(
+                        "constraint of '{}' changed from '{}' to '{}'",
+                        dep.name,
+                        lock.version_constraint.as_deref().unwrap_or("none"),
+                        dep.version_constraint.as_deref().unwrap_or("none")
+                    ))
+                }
+                Some(lock) if lock.archive != dep.archive || lock.sha256 != dep.sha256 => {
+                    problems.push(format!("archive of '{}' changed", dep.name))
+                }
+                Some(lock) if lock.rev != dep.rev => problems.push(format!(
+                    "rev of '{}' changed from '{}' to '{}'",
+                    dep.name,
+                    lock.rev.as_deref().unwrap_or("none"),
+                    dep.rev.as_deref().unwrap_or("none")
+                )),
+                Some(lock) if !lock.matches(dep) => problems.push(format!(
+                    "'{}' is locked at tag '{}' but pinned to tag '{}'",
+                    dep.name,
+                    lock.tag.as_deref().unwrap_or("none"),
+                    dep.tag.as_deref().unwrap_or("none")
+                )),
+                Some(_) => {}
+            }
+        }
+
+        let reachable = self.reachable(dependencies);
+        for lock in &self.dependencies {
+            if !reachable.contains(&lock.name) {
+                problems.push(format!("'{}' is locked but not required by package.yaml", lock.name));
+            }
+        }
+
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "depo.lock is out of date with package.yaml:\n  {}",
+                problems.join("\n  ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Remove every entry that is no longer reachable from the given direct dependencies
+    ///
+    /// Entries are reachable if they are one of `roots` or are required, directly or
+    /// transitively, by a reachable entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - The direct dependencies declared in `package.yaml`
+    ///
+    /// # Returns
+    ///
+    /// Returns the entries that were removed.
+    pub fn prune(&mut self, roots: &[Dependency]) -> Vec<LockedDependency> {
+        let reachable = self.reachable(roots);
+        let (kept, removed) = self
+            .dependencies
+            .drain(..)
+            .partition(|d| reachable.contains(&d.name));
+        self.dependencies = kept;
+        removed
+    }
+
+    fn reachable(&self, roots: &[Dependency]) -> HashSet<String> {
+        let mut reachable = HashSet::new();
+        let mut stack: Vec<String> = roots.iter().map(|d| d.name.clone()).collect();
+
+        while let Some(name) = stack.pop() {
+            if !reachable.insert(name.clone()) {
+                continue;
+            }
+            if let Some(lock) = self.find(&name) {
+                stack.extend(lock.requires.iter().cloned());
+            }
+        }
+        reachable
+    }
+
+    /// Remove the locked entry for a dependency, if present
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to remove
+    pub fn remove(&mut self, name: &str) {
+        self.dependencies.retain(|d| d.name != name);
+    }
+}
@@ -0,0 +1,110 @@
+//! # Manifest Migrations
+//!
+//! This module upgrades package files written by older versions of Depo. Every
+//! package file records the `schema_version` it was written with; files without one
+//! predate versioning and are schema version 0. Before a package file is deserialised,
+//! the migrations between its schema version and `SCHEMA_VERSION` are applied to the
+//! raw YAML document one after another.
+//!
+//! To change the manifest format, bump `SCHEMA_VERSION` and append a migration from
+//! the previous version to `MIGRATIONS`.
+
+use serde_yaml::{Mapping, Value};
+
+/// The schema version of package files written by this version of Depo
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A migration upgrading a package document by one schema version
+type Migration = fn(&mut Mapping) -> anyhow::Result<()>;
+
+/// Migrations indexed by the schema version they upgrade from
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [migrate_v0_to_v1];
+
+/// Get the schema version of a package document
+///
+/// # Arguments
+///
+/// * `doc` - The parsed package file
+///
+/// # Returns
+///
+/// Returns a `Result<u32>` with the document's schema version, 0 if it has none.
+///
+/// # Errors
+///
+/// This function will return an error if `schema_version` is not a non-negative integer.
+pub fn schema_version(doc: &Value) -> anyhow::Result<u32> {
+    match doc.get("schema_version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow::anyhow!("schema_version must be a non-negative integer")),
+    }
+}
+
+/// Upgrade a package document to the current schema version
+///
+/// # Arguments
+///
+/// * `doc` - The parsed package file, upgraded in place
+///
+/// # Returns
+///
+/// Returns a `Result<u32>` with the schema version the document had before migrating.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The document is not a YAML mapping
+/// - The document was written by a newer version of Depo
+/// - A migration fails
+pub fn migrate(doc: &mut Value) -> anyhow::Result<u32> {
+    let original = schema_version(doc)?;
+    if original > SCHEMA_VERSION {
+        anyhow::bail!(
+            "The package file uses schema version {}, but this version of Depo only supports up to {}; upgrade Depo to use it",
+            original,
+            SCHEMA_VERSION
+        );
+    }
+
+    let mapping = doc
+        .as_mapping_mut()
+        .ok_or_else(|| anyhow::anyhow!("The package file must be a mapping"))?;
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(original as usize) {
+        migration(mapping).map_err(|e| {
+            anyhow::anyhow!("Failed to migrate the package file from schema version {}: {}", from, e)
+        })?;
+    }
+    // Like in a saved package, the schema version comes first
+    let rest = std::mem::take(mapping);
+    mapping.insert("schema_version".into(), SCHEMA_VERSION.into());
+    mapping.extend(rest.into_iter().filter(|(key, _)| key != "schema_version"));
+    Ok(original)
+}
+
+/// Normalise a manifest without a `schema_version` for the version 1 model, which
+/// requires a `dependencies` list and a `version` on every dependency. A missing or
+/// null `dependencies` becomes an empty list, and dependencies without a `version` get
+/// an empty one, meaning not yet installed. Nothing else is changed.
+fn migrate_v0_to_v1(doc: &mut Mapping) -> anyhow::Result<()> {
+    let dependencies = doc
+        .entry("dependencies".into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    if dependencies.is_null() {
+        *dependencies = Value::Sequence(Vec::new());
+    }
+
+    let dependencies = dependencies
+        .as_sequence_mut()
+        .ok_or_else(|| anyhow::anyhow!("dependencies must be a list"))?;
+    for dep in dependencies {
+        let dep = dep
+            .as_mapping_mut()
+            .ok_or_else(|| anyhow::anyhow!("every dependency must be a mapping"))?;
+        dep.entry("version".into())
+            .or_insert_with(|| Value::String(String::new()));
+    }
+    Ok(())
+}
@@ -4,22 +4,99 @@
 //! It handles dependency resolution, package initialization, and dependency operations.
 
 use crate::build::{BuildSystem, CMake};
+use crate::cache::Cache;
 use crate::config::Config;
-use crate::dependency::Dependency;
+use crate::dependency::{self, Dependency};
+use crate::error::{DepoError, Result};
+use crate::graph::DependencyGraph;
+use crate::journal;
+use crate::lock::ProjectLock;
+use crate::migration::SCHEMA_VERSION;
+use crate::registry::{self, Registry, RegistryConfig, RegistryVersion};
+use crate::search::{self, SearchProvider, SearchProviderConfig};
 use crate::serialization;
+use crate::lockfile::Lockfile;
+use crate::solver::{self, GitVersionProvider, Resolution, Solver};
+use crate::source::{RegistrySource, Sources};
+use crate::transaction::{self, Transaction};
+use crate::vendor::{self, DEFAULT_VENDOR_DIR, VendorManifest};
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// The version `Package::init` starts new packages at
+pub const DEFAULT_VERSION: &str = "0.1.0";
+
+/// The C++ standards accepted in `cpp_standard`
+const CPP_STANDARDS: [&str; 8] = ["98", "03", "11", "14", "17", "20", "23", "26"];
+
+/// How `Package::install` treats the lockfile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InstallMode {
+    /// Use locked commits where available and resolve and lock everything else
+    #[default]
+    Normal,
+    /// Fail if the lockfile does not match `package.yaml` instead of resolving anything new
+    Locked,
+    /// Like `Locked`, and additionally refuse any network access
+    Frozen,
+}
 
 /// Represents a C++ package with its dependencies
 ///
 /// A Package contains a collection of dependencies that are managed together.
 /// It provides methods for adding, removing, updating, and searching dependencies.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Package {
+    /// The schema version of the package file (see the `migration` module)
+    #[serde(default)]
+    pub schema_version: u32,
+    /// The name the package is published under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The semantic version of the package, matching the tag it is published from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// A short description of the package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The license of the package as an SPDX expression (e.g. `MIT OR Apache-2.0`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// The authors of the package (e.g. `Jane Doe <jane@example.com>`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// The URL of the package's repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    /// The C++ standard the package is written against (e.g. `17` or `20`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpp_standard: Option<String>,
+    /// The CMake targets the package exports to its consumers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
     /// List of dependencies managed by this package
     pub dependencies: Vec<Dependency>,
+    /// Directory dependency sources are vendored into, relative to the package
+    /// (defaults to `vendor`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_dir: Option<String>,
+    /// Search providers queried by `find_dependency`, before those configured by the user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub search: Vec<SearchProviderConfig>,
+    /// Named registries dependencies can be assigned to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub registries: Vec<RegistryConfig>,
 }
+
+impl Default for Package {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Package {
     /// Create a new empty package
     ///
@@ -28,14 +105,27 @@ impl Package {
     /// Returns a new `Package` instance with an empty dependencies list.
     pub fn new() -> Package {
         Package {
+            schema_version: SCHEMA_VERSION,
+            name: None,
+            version: None,
+            description: None,
+            license: None,
+            authors: Vec::new(),
+            repository: None,
+            cpp_standard: None,
+            targets: Vec::new(),
             dependencies: Vec::new(),
+            vendor_dir: None,
+            search: Vec::new(),
+            registries: Vec::new(),
         }
     }
 
     /// Initialize a new package in the specified directory
     ///
     /// This method creates a new package configuration file (`package.yaml`) in the given directory.
-    /// If a package already exists in the directory, this method will return an error.
+    /// If a package already exists in the directory, this method will return an error. The
+    /// package is named after the directory and starts at version `0.1.0`.
     ///
     /// # Arguments
     ///
@@ -48,27 +138,122 @@ impl Package {
     /// # Errors
     ///
     /// This method will return an error if:
-    /// - A package already exists in the specified directory
+    /// - A package already exists in the specified directory (`DepoError::ManifestExists`)
     /// - The directory cannot be written to
     /// - File I/O operations fail
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use pkgcore::package::Package;
-    ///
+    /// ```rust,no_run
+    /// use depo_core::package::Package;
+    /// # fn main() -> anyhow::Result<()> {
     /// let package = Package::init("./my_project")?;
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn init(path: &str) -> anyhow::Result<Package> {
-        if serialization::package_exists(&path) {
-            anyhow::bail!("package already exists");
+    pub fn init(path: &str) -> Result<Package> {
+        if serialization::package_exists(path) {
+            Err(DepoError::ManifestExists {
+                path: path.to_string(),
+            })
         } else {
-            let pkg = Package::new();
-            serialization::save_package(&pkg, &path)?;
+            let mut pkg = Package::new();
+            pkg.name = Some(default_name(Path::new(path)));
+            pkg.version = Some(DEFAULT_VERSION.to_string());
+            serialization::save_package(&pkg, path)?;
             Ok(pkg)
         }
     }
 
+    /// Check that the project metadata is well-formed
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating whether the metadata is valid.
+    ///
+    /// # Errors
+    ///
+    /// This method will return `DepoError::PackageInvalid` if:
+    /// - The name is empty or contains characters other than letters, digits, `-`, `_` and `.`
+    /// - The version is not a semantic version such as `1.2.0`
+    /// - The description, license or an author is empty
+    /// - The repository is not a URL
+    /// - The C++ standard is not one of 98, 03, 11, 14, 17, 20, 23 or 26
+    /// - A target name is empty, contains whitespace or is listed twice
+    ///
+    /// It returns `DepoError::DependencyInvalid` if a dependency name cannot be used as a
+    /// directory name in `deps/` or is listed twice.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = &self.name
+            && (name.is_empty()
+                || name.starts_with(['.', '-'])
+                || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)))
+        {
+            return Err(invalid_package(format!(
+                "Invalid package name '{}': use letters, digits, '-', '_' and '.'",
+                name
+            )));
+        }
+        if let Some(version) = &self.version {
+            semver::Version::parse(version).map_err(|_| {
+                invalid_package(format!(
+                    "Invalid package version '{}': expected a semantic version such as 1.2.0",
+                    version
+                ))
+            })?;
+        }
+        for (field, value) in [("description", &self.description), ("license", &self.license)] {
+            if value.as_ref().is_some_and(|v| v.trim().is_empty()) {
+                return Err(invalid_package(format!(
+                    "The package {} must not be empty",
+                    field
+                )));
+            }
+        }
+        if self.authors.iter().any(|author| author.trim().is_empty()) {
+            return Err(invalid_package("Package authors must not be empty"));
+        }
+        if let Some(repository) = &self.repository
+            && !dependency::is_git_url(repository)
+        {
+            return Err(invalid_package(format!(
+                "Invalid repository '{}': expected a URL",
+                repository
+            )));
+        }
+        if let Some(standard) = &self.cpp_standard {
+            let number = standard
+                .strip_prefix("c++")
+                .or_else(|| standard.strip_prefix("C++"))
+                .unwrap_or(standard);
+            if !CPP_STANDARDS.contains(&number) {
+                return Err(invalid_package(format!(
+                    "Unknown C++ standard '{}': expected one of {}",
+                    standard,
+                    CPP_STANDARDS.join(", ")
+                )));
+            }
+        }
+        for (i, target) in self.targets.iter().enumerate() {
+            if target.is_empty() || target.chars().any(char::is_whitespace) {
+                return Err(invalid_package(format!("Invalid target name '{}'", target)));
+            }
+            if self.targets[..i].contains(target) {
+                return Err(invalid_package(format!("Target '{}' is listed twice", target)));
+            }
+        }
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            dependency::validate_name(&dep.name)?;
+            if self.dependencies[..i].iter().any(|d| d.name == dep.name) {
+                return Err(DepoError::DependencyInvalid {
+                    name: dep.name.clone(),
+                    message: format!("Dependency '{}' is listed twice", dep.name),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Check if a dependency with the given name already exists
     ///
     /// # Arguments
@@ -82,14 +267,19 @@ impl Package {
         self.dependencies.iter().any(|d| d.name == name)
     }
 
-    /// Search for dependencies on GitHub
+    /// Search for dependencies
     ///
-    /// This method searches GitHub repositories for C++ libraries matching the given name.
-    /// It uses the GitHub API to find repositories with the specified name and C++ language tag.
+    /// This method queries the search providers configured in the package, followed by
+    /// those configured by the user, and merges their results, dropping repositories
+    /// found more than once. Without any configured provider, GitHub is searched for C++
+    /// repositories. In offline mode the repositories known to the download cache are
+    /// searched instead.
     ///
     /// # Arguments
     ///
-    /// * `name` - The name to search for in GitHub repositories
+    /// * `name` - The name to search for
+    /// * `working_dir` - The working directory of the package, used to resolve index paths
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
     ///
     /// # Returns
     ///
@@ -98,85 +288,295 @@ impl Package {
     /// # Errors
     ///
     /// This method will return an error if:
-    /// - GitHub API rate limit is exceeded (suggests adding a GitHub token)
-    /// - Network requests fail
-    /// - API responses cannot be parsed
+    /// - Every provider fails, e.g. because the GitHub API rate limit is exceeded
+    /// - The user's `search.yaml` cannot be parsed
+    /// - Offline mode is enabled and no cached repository matches the name
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use pkgcore::package::Package;
-    ///
+    /// ```rust,no_run
+    /// use depo_core::config::Config;
+    /// use depo_core::package::Package;
+    /// # async fn example() -> anyhow::Result<()> {
     /// let package = Package::new();
-    /// let dependencies = package.find_dependency("json").await?;
+    /// let dependencies = package.find_dependency("json", ".", &Config::load()?).await?;
     /// for dep in dependencies {
     ///     println!("Found: {}", dep.full_name);
     /// }
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn find_dependency(&self, name: &str) -> anyhow::Result<Vec<Dependency>> {
-        use reqwest::Client;
-        use serde_json::Value;
-
-        let config = Config::load()?;
-        let client = Client::new();
-        let api_url = "https://api.github.com/search/repositories";
-        let query = format!("{} language:C++", name);
-        let params = [
-            ("q", query.as_str()),
-            ("sort", "stars"),
-            ("order", "desc"),
-            ("per_page", "5"),
-        ];
-
-        let mut request = client
-            .get(api_url)
-            .header("User-Agent", "rust-client")
-            .query(&params);
-
-        if let Some(auth_header) = config.get_auth_header() {
-            request = request.header("Authorization", auth_header);
-        }
-
-        let response = request.send().await?;
-
-        if response.status() == 403 {
-            let error_text = response.text().await?;
-            if error_text.contains("rate limit") {
-                anyhow::bail!(
-                    "GitHub API rate limit exceeded. Please add a GitHub token to .pkg.env file"
-                );
+    pub async fn find_dependency(
+        &self,
+        name: &str,
+        working_dir: &str,
+        config: &Config,
+    ) -> Result<Vec<Dependency>> {
+        if config.offline {
+            return Ok(Self::find_cached_dependency(name, config)?);
+        }
+
+        let providers = self.search_providers(working_dir, config)?;
+        Ok(search::search(&providers, name, 5).await?)
+    }
+
+    fn search_providers(
+        &self,
+        working_dir: &str,
+        config: &Config,
+    ) -> anyhow::Result<Vec<Box<dyn SearchProvider>>> {
+        let mut configured: Vec<SearchProviderConfig> = Vec::new();
+        let project = self.search.iter().map(|p| p.clone().resolve(Path::new(working_dir)));
+        for provider in project.chain(search::load_user_providers()?) {
+            if !configured.contains(&provider) {
+                configured.push(provider);
             }
-            anyhow::bail!("GitHub API error: {}", error_text);
+        }
+        if configured.is_empty() {
+            configured.push(SearchProviderConfig::Github { url: None });
         }
 
-        let data: Value = response.json().await?;
-        let repos: Vec<Dependency> = data["items"]
-            .as_array()
-            .unwrap_or(&vec![])
+        Ok(configured
             .iter()
-            .filter_map(|repo| {
-                Some(Dependency::new(
-                    repo["name"].as_str()?,
-                    repo["full_name"].as_str()?,
-                    repo["clone_url"].as_str()?,
-                    None,
-                    "",
-                ))
+            .map(|p| p.provider(config))
+            .collect())
+    }
+
+    fn find_cached_dependency(name: &str, config: &Config) -> anyhow::Result<Vec<Dependency>> {
+        let needle = name.to_lowercase();
+        let repos: Vec<Dependency> = Cache::open(config)?
+            .repositories()?
+            .iter()
+            .filter_map(|url| {
+                let (repo_name, full_name) = dependency::repository_names(url)?;
+                repo_name
+                    .to_lowercase()
+                    .contains(&needle)
+                    .then(|| Dependency::new(&repo_name, &full_name, url, None, ""))
             })
             .collect();
 
+        if repos.is_empty() {
+            anyhow::bail!("Offline mode: no cached repository matches '{}'", name);
+        }
         Ok(repos)
     }
 
+    /// Look up a dependency in the package's registries
+    ///
+    /// Registries are searched in the order they are configured, and the first one
+    /// publishing a package with exactly this name is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The package name
+    /// * `registry` - Only search the registry with this name, if given
+    /// * `working_dir` - The working directory of the package
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Some(Dependency))` assigned to the registry that publishes the package,
+    /// or `Ok(None)` if no registry does.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - `registry` names a registry that is not configured
+    /// - A registry index cannot be opened or parsed
+    pub fn find_in_registries(
+        &self,
+        name: &str,
+        registry: Option<&str>,
+        working_dir: &str,
+        config: &Config,
+    ) -> Result<Option<Dependency>> {
+        if let Some(registry) = registry
+            && !self.registries.iter().any(|r| r.name == registry)
+        {
+            return Err(DepoError::other(format!(
+                "Registry '{}' is not configured in package.yaml",
+                registry
+            )));
+        }
+
+        let cache = Cache::open(config)?;
+        for configured in &self.registries {
+            if registry.is_some_and(|r| r != configured.name) {
+                continue;
+            }
+            let opened = Registry::open(configured, working_dir, true, &cache)?;
+            if let Some(package) = opened.package(name)? {
+                return Ok(Some(opened.dependency(&package)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Open every registry configured in the package
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    /// * `update` - Whether Git indexes may be fetched from their remotes
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Registry>>` with the opened registries.
+    pub fn open_registries(&self, working_dir: &str, update: bool, config: &Config) -> Result<Vec<Registry>> {
+        let cache = Cache::open(config)?;
+        self.registries
+            .iter()
+            .map(|registry| Registry::open(registry, working_dir, update, &cache).map_err(Into::into))
+            .collect()
+    }
+
+    /// Publish the current version of the package to a registry
+    ///
+    /// The package's Git repository must be checked out at a tag matching its version
+    /// (`1.2.0` or `v1.2.0`) without uncommitted changes. The index entry records the tag,
+    /// commit and source checksum together with the declared dependencies.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package, the root of its repository
+    /// * `registry` - The registry to publish to, required when several are configured
+    /// * `url` - The Git URL consumers fetch the package from, defaults to `repository` and
+    ///   then to the `origin` remote
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<RegistryVersion>` with the published entry.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The package has no name or version
+    /// - No registry, or several without one being chosen, are configured
+    /// - The repository has uncommitted changes or `HEAD` is not at the version's tag
+    /// - A dependency is a local path dependency
+    /// - The version is already published
+    pub fn publish(
+        &self,
+        working_dir: &str,
+        registry: Option<&str>,
+        url: Option<&str>,
+    ) -> Result<RegistryVersion> {
+        let (Some(name), Some(version)) = (&self.name, &self.version) else {
+            return Err(DepoError::other("package.yaml needs a name and version to be published"));
+        };
+        let config = match (registry, self.registries.as_slice()) {
+            (Some(wanted), configured) => configured
+                .iter()
+                .find(|r| r.name == wanted)
+                .ok_or_else(|| {
+                    DepoError::other(format!("Registry '{}' is not configured in package.yaml", wanted))
+                })?,
+            (None, [only]) => only,
+            (None, []) => return Err(DepoError::other("No registry is configured in package.yaml")),
+            (None, _) => {
+                return Err(DepoError::other(
+                    "Several registries are configured; choose one to publish to",
+                ));
+            }
+        };
+
+        let repo = git2::Repository::open(working_dir)
+            .with_context(|| format!("'{}' is not the root of a Git repository", working_dir))?;
+        let mut status_options = git2::StatusOptions::new();
+        status_options.include_untracked(false);
+        if !repo.statuses(Some(&mut status_options))?.is_empty() {
+            return Err(DepoError::other(
+                "The working tree has uncommitted changes; commit them before publishing",
+            ));
+        }
+
+        let head = repo.head()?.peel_to_commit()?.id();
+        let tag = [format!("v{}", version), version.clone()]
+            .into_iter()
+            .find(|tag| {
+                repo.revparse_single(&format!("refs/tags/{}", tag))
+                    .and_then(|object| object.peel_to_commit())
+                    .is_ok_and(|commit| commit.id() == head)
+            })
+            .ok_or_else(|| {
+                DepoError::other(format!(
+                    "HEAD ({}) is not tagged 'v{}' or '{}'; tag the release before publishing",
+                    head,
+                    version,
+                    version
+                ))
+            })?;
+
+        let url = match url.or(self.repository.as_deref()) {
+            Some(url) => url.to_string(),
+            None => repo
+                .find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string))
+                .ok_or_else(|| DepoError::other("No 'origin' remote; pass the URL to publish from"))?,
+        };
+
+        let mut dependencies = Vec::new();
+        for dep in &self.dependencies {
+            if dep.path.is_some() {
+                return Err(DepoError::other(format!(
+                    "'{}' is a local path dependency and cannot be published",
+                    dep.name
+                )));
+            }
+            let mut declared = dep.clone();
+            declared.version = String::new();
+            dependencies.push(declared);
+        }
+
+        let published = RegistryVersion {
+            version: version.clone(),
+            tag,
+            commit: head.to_string(),
+            sha256: Some(registry::source_checksum(&repo, head)?),
+            dependencies,
+        };
+        registry::publish(config, working_dir, name, &url, published.clone())?;
+        Ok(published)
+    }
+
+    /// Get the sources the package's dependencies are installed from
+    ///
+    /// These are the built-in sources, plus a registry source for the package's registries.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    /// * `update` - Whether Git registry indexes may be fetched from their remotes
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Sources>` used by `install`.
+    pub fn sources(&self, working_dir: &str, update: bool, config: &Config) -> Result<Sources> {
+        let mut sources = Sources::builtin(config)?;
+        sources.register(Box::new(RegistrySource::new(
+            self.open_registries(working_dir, update, config)?,
+            config,
+        )?));
+        Ok(sources)
+    }
+
     /// Add a new dependency to the package
     ///
-    /// This method adds a dependency to the package, installs it to the local filesystem,
-    /// and saves the updated package configuration.
+    /// The dependency can come from `find_dependency` or be created directly from a Git
+    /// URL with `Dependency::from_url`. This method adds it to the package, installs it and its transitive
+    /// dependencies to the local filesystem, records the resolved commits in the lockfile,
+    /// and saves the updated package configuration. If any step fails, the package, the
+    /// lockfile and `deps/` are left as they were.
     ///
     /// # Arguments
     ///
     /// * `dep` - The dependency to add
     /// * `working_dir` - The working directory where dependencies are installed
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
     ///
     /// # Returns
     ///
@@ -185,24 +585,349 @@ impl Package {
     /// # Errors
     ///
     /// This method will return an error if:
-    /// - A dependency with the same name already exists
+    /// - A dependency with the same name already exists (`DepoError::DependencyExists`)
     /// - The dependency installation fails
-    /// - The package configuration cannot be saved
-    pub fn add_dependency(&mut self, mut dep: Dependency, working_dir: &str) -> anyhow::Result<()> {
+    /// - The package configuration or lockfile cannot be saved
+    pub fn add_dependency(&mut self, dep: Dependency, working_dir: &str, config: &Config) -> Result<()> {
         if self.is_dependency_existing(dep.name.as_str()) {
-            anyhow::bail!("package already exists");
+            return Err(DepoError::DependencyExists { name: dep.name });
+        }
+
+        let sources = self.sources(working_dir, true, config)?;
+        self.transaction(working_dir, |pkg| {
+            // Without an entry in the lockfile the install solves the whole graph again
+            pkg.dependencies.push(dep);
+            pkg.install_dependencies(working_dir, InstallMode::Normal, &sources, config)
+        })
+    }
+
+    /// Install all dependencies of the package
+    ///
+    /// This method installs every dependency listed in the package together with the
+    /// dependencies declared in their own `package.yaml`, recursively. If the lockfile is
+    /// up to date, every dependency is checked out at exactly the locked commit. Otherwise
+    /// the version solver picks one version per library that satisfies the constraints of
+    /// every package requiring it, preferring the locked versions, and the lockfile is
+    /// rewritten. The CMake bridge files are regenerated for the whole graph afterwards.
+    ///
+    /// In `InstallMode::Locked` the lockfile must agree with the package and is never
+    /// rewritten. `InstallMode::Frozen` additionally only accepts dependencies that are
+    /// already present in the deps directory.
+    ///
+    /// Leftovers of installs that were interrupted earlier are removed first (see the
+    /// `journal` module). The install runs as a transaction: if it fails, `deps/`, the
+    /// lockfile and the package file are restored (see the `transaction` module).
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    /// * `mode` - How the lockfile is used during installation
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the installation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The lockfile cannot be loaded
+    /// - The lockfile is missing or out of date in locked or frozen mode
+    /// - No set of versions satisfies every version constraint (`DepoError::NoMatchingVersion`)
+    /// - Any dependency fails to install
+    /// - The dependency graph contains a cycle
+    /// - The package configuration or lockfile cannot be saved
+    pub fn install(&mut self, working_dir: &str, mode: InstallMode, config: &Config) -> Result<()> {
+        let sources = self.sources(working_dir, mode != InstallMode::Frozen, config)?;
+        self.install_with_sources(working_dir, mode, &sources, config)
+    }
+
+    /// Install all dependencies of the package from a custom set of sources
+    ///
+    /// This behaves exactly like `install`, except that every dependency is fetched by
+    /// the first source in `sources` that handles it instead of the built-in sources.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    /// * `mode` - How the lockfile is used during installation
+    /// * `sources` - The sources dependencies are fetched from
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the installation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error in the same cases as `install`, or if no source
+    /// handles one of the dependencies.
+    pub fn install_with_sources(
+        &mut self,
+        working_dir: &str,
+        mode: InstallMode,
+        sources: &Sources,
+        config: &Config,
+    ) -> Result<()> {
+        self.transaction(working_dir, |pkg| pkg.install_dependencies(working_dir, mode, sources, config))
+    }
+
+    /// Install all dependencies, outside of a transaction of its own
+    fn install_dependencies(
+        &mut self,
+        working_dir: &str,
+        mode: InstallMode,
+        sources: &Sources,
+        config: &Config,
+    ) -> anyhow::Result<()> {
+        for path in journal::recover(working_dir)? {
+            println!("Removed leftovers of an interrupted install: {}", path.display());
+        }
+
+        let mut lockfile = serialization::load_lockfile(working_dir)?;
+
+        if mode != InstallMode::Normal {
+            if !serialization::lockfile_exists(working_dir) {
+                anyhow::bail!("depo.lock not found; run `install` without --locked to create it");
+            }
+            lockfile.verify(&self.dependencies)?;
         }
 
-        dep.install(&working_dir)?;
-        self.dependencies.push(dep);
-        serialization::save_package(&self, working_dir)?;
+        // The locked versions are solved again once the declared dependencies changed, or
+        // once they turn out not to meet the requirements of the installed dependencies
+        let mut solve = mode == InstallMode::Normal && lockfile.verify(&self.dependencies).is_err();
+        let graph = loop {
+            let resolution = if solve {
+                let mut provider = GitVersionProvider::new(Cache::open(config)?, sources)
+                    .with_registries(self.open_registries(working_dir, true, config)?);
+                let mut solver = Solver::new(&mut provider);
+                for lock in &lockfile.dependencies {
+                    solver.prefer(&lock.name, &lock.commit);
+                }
+                Some(solver.solve(&self.dependencies)?)
+            } else {
+                None
+            };
+
+            let (graph, requirements) =
+                self.install_graph(working_dir, mode, sources, &mut lockfile, resolution.as_ref())?;
+            let unmet = unmet_requirements(&graph, &requirements);
+            if unmet.is_empty() {
+                break graph;
+            }
+            if mode == InstallMode::Normal && !solve {
+                solve = true;
+                continue;
+            }
+            anyhow::bail!(
+                "{}:\n  {}",
+                match mode {
+                    InstallMode::Normal => "The installed versions do not meet every requirement",
+                    _ => "depo.lock does not meet every requirement; run `install` without --locked",
+                },
+                unmet.join("\n  ")
+            );
+        };
+
+        for dep in self.dependencies.iter_mut() {
+            if let Some(installed) = graph.get(&dep.name) {
+                dep.version = installed.version.clone();
+            }
+        }
+
+        CMake::generate_dependency_bridge(&graph.topological_order()?, working_dir)?;
+
+        if mode != InstallMode::Normal {
+            return Ok(());
+        }
+
+        lockfile.prune(&self.dependencies);
+
+        serialization::save_package(self, working_dir)?;
+        serialization::save_lockfile(&lockfile, working_dir)?;
         Ok(())
     }
 
+    /// Install every dependency reachable from the package and record it in the lockfile
+    ///
+    /// Returns the installed graph together with every requirement met on the way, as the
+    /// dependency declared by the requiring package and a description of that package.
+    fn install_graph(
+        &self,
+        working_dir: &str,
+        mode: InstallMode,
+        sources: &Sources,
+        lockfile: &mut Lockfile,
+        resolution: Option<&Resolution>,
+    ) -> anyhow::Result<(DependencyGraph, Vec<(Dependency, String)>)> {
+        let vendor_dir = self.vendor_path(working_dir);
+        let vendored = VendorManifest::load(&vendor_dir)?.unwrap_or_default();
+        let mut graph = DependencyGraph::new();
+        let mut requirements = Vec::new();
+        let mut queue: VecDeque<(Dependency, String)> = self
+            .dependencies
+            .iter()
+            .map(|dep| (dep.clone(), "package.yaml".to_string()))
+            .collect();
+
+        while let Some((mut dep, required_by)) = queue.pop_front() {
+            requirements.push((dep.clone(), required_by));
+            if graph.contains(&dep.name) {
+                continue;
+            }
+
+            let solved = resolution.and_then(|r| r.locked(&dep));
+            let locked = solved
+                .as_ref()
+                .or_else(|| lockfile.find(&dep.name).filter(|l| l.matches(&dep)));
+            let mut entry = match (mode, locked, vendored.find(&dep, locked)) {
+                (InstallMode::Locked | InstallMode::Frozen, None, _) => {
+                    Err(anyhow::anyhow!("'{}' is not in depo.lock", dep.name))
+                }
+                (_, _, Some(copy)) => dep.use_vendored(&vendor_dir, copy).map_err(Into::into),
+                (_, locked, None) => sources.for_dependency(&dep).and_then(|source| {
+                    // Nested dependencies only record their version in the lockfile
+                    let mut recorded = dep.clone();
+                    if recorded.version.is_empty()
+                        && let Some(lock) = lockfile.find(&dep.name)
+                    {
+                        recorded.version = lock.version.clone();
+                    }
+                    let previous = source.installed_version(&recorded, working_dir)?;
+
+                    let entry = source.fetch(&mut dep, working_dir, locked, mode != InstallMode::Frozen)?;
+
+                    // A superseded version is only removed once the new one is in place
+                    if let Some(previous) = previous
+                        && previous != dep.version
+                    {
+                        recorded.version = previous;
+                        transaction::retire(working_dir, &recorded.install_path(working_dir))?;
+                    }
+                    Ok(entry)
+                }),
+            }
+            .with_context(|| format!("Failed to install dependency '{}'", dep.name))?;
+            println!("Installed dependency '{}'", dep.name);
+
+            let nested = dep.nested_dependencies(working_dir)?;
+            let requires: Vec<String> = nested.iter().map(|d| d.name.clone()).collect();
+            entry.requires = requires.clone();
+            lockfile.upsert(entry);
+            let parent = format!("{} {}", dep.name, dep.version);
+            queue.extend(nested.into_iter().map(|nested| (nested, parent.clone())));
+            graph.add(dep, requires);
+        }
+
+        Ok((graph, requirements))
+    }
+
+    /// Collect all installed dependencies in build order
+    ///
+    /// This method walks the direct dependencies and the `package.yaml` files of the
+    /// installed dependencies without accessing the network, and orders the result so that
+    /// every dependency comes before the dependencies that require it.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Dependency>>` containing direct and transitive dependencies
+    /// in topological order.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - A nested `package.yaml` cannot be loaded
+    /// - The dependency graph contains a cycle
+    pub fn build_order(&self, working_dir: &str) -> Result<Vec<Dependency>> {
+        let lockfile = serialization::load_lockfile(working_dir)?;
+        let vendor_dir = self.vendor_path(working_dir);
+        let vendored = VendorManifest::load(&vendor_dir)?.unwrap_or_default();
+        let mut graph = DependencyGraph::new();
+        let mut queue: VecDeque<Dependency> = self.dependencies.iter().cloned().collect();
+
+        while let Some(mut dep) = queue.pop_front() {
+            if graph.contains(&dep.name) {
+                continue;
+            }
+
+            let locked = lockfile.find(&dep.name).filter(|l| l.matches(&dep));
+            if let Some(lock) = locked {
+                dep.version = lock.version.clone();
+            }
+            if let Some(copy) = locked.and_then(|lock| vendored.find(&dep, Some(lock))) {
+                dep.use_vendored(&vendor_dir, copy)?;
+            }
+
+            let nested = dep.nested_dependencies(working_dir)?;
+            let requires = nested.iter().map(|d| d.name.clone()).collect();
+            queue.extend(nested);
+            graph.add(dep, requires);
+        }
+
+        Ok(graph.topological_order()?)
+    }
+
+    /// Get the directory dependency sources are vendored into
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns the configured vendor directory, or `vendor` if none is configured.
+    pub fn vendor_path(&self, working_dir: &str) -> PathBuf {
+        Path::new(working_dir).join(self.vendor_dir.as_deref().unwrap_or(DEFAULT_VENDOR_DIR))
+    }
+
+    /// Copy the sources of all installed dependencies into the vendor directory
+    ///
+    /// Every dependency in `depo.lock` is copied from `deps/` without its `.git`
+    /// directories and `build/` folder, and the vendor directory's `vendor.yaml` records
+    /// the URL and commit of each copy. Later installs and builds use the vendored copies.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    /// * `dir` - A new vendor directory to use and record in `package.yaml`, if any
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<VendorManifest>` describing the vendored dependencies.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - `depo.lock` is missing or out of date with `package.yaml`
+    /// - A locked dependency is not installed at its locked commit
+    /// - File system operations fail
+    pub fn vendor(&mut self, working_dir: &str, dir: Option<&str>) -> Result<VendorManifest> {
+        let _lock = ProjectLock::acquire(working_dir)?;
+        if !serialization::lockfile_exists(working_dir) {
+            return Err(DepoError::other("depo.lock not found; run `install` first"));
+        }
+        let lockfile = serialization::load_lockfile(working_dir)?;
+        lockfile.verify(&self.dependencies)?;
+
+        if let Some(dir) = dir {
+            self.vendor_dir = Some(dir.to_string());
+        }
+
+        let manifest = vendor::vendor(working_dir, &self.vendor_path(working_dir), &lockfile)?;
+        serialization::save_package(self, working_dir)?;
+        Ok(manifest)
+    }
+
     /// Remove a dependency from the package
     ///
-    /// This method removes a dependency from the package, deletes its local installation,
-    /// regenerates the CMake bridge files, and saves the updated package configuration.
+    /// This method removes a dependency from the package, deletes its local installation
+    /// along with any transitive dependencies no longer required, regenerates the CMake
+    /// bridge files, and saves the updated package configuration and lockfile. If any step
+    /// fails, the removed directories and the files are restored.
     ///
     /// # Arguments
     ///
@@ -216,102 +941,347 @@ impl Package {
     /// # Errors
     ///
     /// This method will return an error if:
-    /// - The dependency is not found in the package
+    /// - The dependency is not found in the package (`DepoError::DependencyNotFound`)
     /// - File system operations fail
     /// - CMake bridge generation fails
     /// - Package configuration cannot be saved
-    pub fn remove_dependency(&mut self, name: &str, working_dir: &str) -> anyhow::Result<()> {
+    pub fn remove_dependency(&mut self, name: &str, working_dir: &str) -> Result<()> {
         let dep_opt = self.dependencies.iter().find(|d| d.name == name).cloned();
         if dep_opt.is_none() {
-            anyhow::bail!("dependency '{}' not found", name);
+            return Err(DepoError::DependencyNotFound {
+                name: name.to_string(),
+            });
         }
         let dep = dep_opt.unwrap();
 
-        self.dependencies.retain(|d| d.name != name);
+        self.transaction(working_dir, |pkg| {
+            pkg.dependencies.retain(|d| d.name != name);
 
-        let dep_dir = format!("{}@{}", dep.name, dep.version);
-        let dep_path = Path::new(working_dir).join("deps").join(dep_dir);
+            transaction::retire(working_dir, &dep.install_path(working_dir))?;
 
-        if dep_path.exists() {
-            fs::remove_dir_all(&dep_path)?;
-        }
+            let mut lockfile = serialization::load_lockfile(working_dir)?;
+            for orphan in lockfile.prune(&pkg.dependencies) {
+                transaction::retire(working_dir, &orphan.install_path(working_dir))?;
+            }
 
-        CMake::generate_dependency_bridge(&self.dependencies, working_dir)?;
-        serialization::save_package(&self, working_dir)?;
-        Ok(())
+            CMake::generate_dependency_bridge(&pkg.build_order(working_dir)?, working_dir)?;
+            serialization::save_package(pkg, working_dir)?;
+            serialization::save_lockfile(&lockfile, working_dir)?;
+            Ok(())
+        })
     }
 
-    pub fn update_dependency(&mut self, name: &str, working_dir: &str) -> anyhow::Result<()> {
-        let index = self
+    /// Update a dependency to the newest version compatible with the whole graph
+    ///
+    /// This method drops the dependency from the lockfile and re-runs the version solver,
+    /// which picks the newest version that still satisfies every constraint. The new version
+    /// is installed next to the previous one, which is only removed once everything else
+    /// succeeded; if anything fails, the previous installation, lockfile and package file
+    /// are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to update
+    /// * `working_dir` - The working directory where dependencies are installed
+    /// * `config` - The configuration of the operation, e.g. whether it runs offline
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the update.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The dependency is not found in the package (`DepoError::DependencyNotFound`)
+    /// - No compatible version can be found
+    /// - The installation fails
+    pub fn update_dependency(&mut self, name: &str, working_dir: &str, config: &Config) -> Result<()> {
+        let old = self
             .dependencies
             .iter()
-            .position(|d| d.name == name)
-            .ok_or_else(|| anyhow::anyhow!("Dependency '{}' not found", name))?;
-
-        let dep = &mut self.dependencies[index];
-        let old_version = dep.version.clone();
-
-        let latest = dep.find_latest_matching_version()?;
+            .find(|d| d.name == name)
+            .cloned()
+            .ok_or_else(|| DepoError::DependencyNotFound {
+                name: name.to_string(),
+            })?;
 
-        if latest == old_version {
-            println!("Dependency '{}' is already up to date (version {}).", name, latest);
-            return Ok(());
+        if old.archive.is_some() {
+            return Err(DepoError::other(format!(
+                "Dependency '{}' is an archive fixed by its sha256; change the archive to update it",
+                name
+            )));
         }
 
-        let old_dep_path = {
-            let old_dir_name = format!("{}@{}", dep.name, old_version);
-            Path::new(working_dir).join("deps").join(old_dir_name)
-        };
-
-        if old_dep_path.exists() {
-            std::fs::remove_dir_all(&old_dep_path)?;
+        if old.is_pinned() {
+            return Err(DepoError::other(format!(
+                "Dependency '{}' is pinned to {}; change the pin to update it",
+                name,
+                match (&old.rev, &old.tag) {
+                    (Some(rev), _) => format!("rev '{}'", rev),
+                    (_, Some(tag)) => format!("tag '{}'", tag),
+                    _ => unreachable!(),
+                }
+            )));
         }
 
-        dep.version = latest.clone();
-        dep.install(working_dir)?;
+        let sources = self.sources(working_dir, true, config)?;
+        self.transaction(working_dir, |pkg| {
+            let mut lockfile = serialization::load_lockfile(working_dir)?;
+            lockfile.remove(name);
+            serialization::save_lockfile(&lockfile, working_dir)?;
 
-        CMake::generate_dependency_bridge(&self.dependencies, working_dir)?;
-        serialization::save_package(self, working_dir)?;
+            pkg.install_dependencies(working_dir, InstallMode::Normal, &sources, config)?;
 
-        Ok(())
+            let updated = pkg
+                .dependencies
+                .iter()
+                .find(|d| d.name == name)
+                .expect("updated dependency is still part of the package");
+
+            if updated.version == old.version {
+                println!("Dependency '{}' is already up to date (version {}).", name, old.version);
+            }
+            Ok(())
+        })
     }
 
+    /// Change the version constraint of a dependency
+    ///
+    /// The constraint replaces any pin. The new constraint takes effect with the next
+    /// `install`, which solves the versions again and retires the version it supersedes.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to change
+    /// * `new_constraint` - The new version constraint, such as `^1.2`
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the operation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - Another depo process holds the project lock for longer than `DEPO_LOCK_TIMEOUT`
+    /// - The dependency is not found in the package (`DepoError::DependencyNotFound`)
+    /// - The constraint is not a valid version requirement (`DepoError::ConstraintInvalid`)
+    /// - Package configuration cannot be saved
     pub fn modify_dependency_constraint(
         &mut self,
         name: &str,
         new_constraint: &str,
         working_dir: &str,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
+        let _lock = ProjectLock::acquire(working_dir)?;
         let dep = self
             .dependencies
             .iter_mut()
             .find(|d| d.name == name)
-            .ok_or_else(|| anyhow::anyhow!("Dependency '{}' not found", name))?;
+            .ok_or_else(|| DepoError::DependencyNotFound {
+                name: name.to_string(),
+            })?;
 
         dep.validate_version_constraint(new_constraint)?;
         dep.version_constraint = Some(new_constraint.to_string());
+        dep.rev = None;
+        dep.tag = None;
+
+        serialization::save_package(self, working_dir)?;
+        Ok(())
+    }
+
+    /// Pin a dependency to an exact rev or tag
+    ///
+    /// The pin replaces any version constraint. Pinned dependencies are installed at exactly
+    /// the requested commit and are never moved by `update_dependency`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to pin
+    /// * `rev` - A commit SHA or ref to pin to
+    /// * `tag` - A tag to pin to
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the operation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - Another depo process holds the project lock for longer than `DEPO_LOCK_TIMEOUT`
+    /// - The dependency is not found in the package (`DepoError::DependencyNotFound`)
+    /// - Neither or both of `rev` and `tag` are given
+    /// - The dependency is a local path dependency
+    /// - Package configuration cannot be saved
+    pub fn pin_dependency(
+        &mut self,
+        name: &str,
+        rev: Option<&str>,
+        tag: Option<&str>,
+        working_dir: &str,
+    ) -> Result<()> {
+        let _lock = ProjectLock::acquire(working_dir)?;
+        let dep = self
+            .dependencies
+            .iter_mut()
+            .find(|d| d.name == name)
+            .ok_or_else(|| DepoError::DependencyNotFound {
+                name: name.to_string(),
+            })?;
+
+        if rev.is_some() == tag.is_some() {
+            return Err(DepoError::other(format!("Pin '{}' to either a rev or a tag", name)));
+        }
+
+        let mut pinned = dep.clone();
+        pinned.version_constraint = None;
+        pinned.rev = rev.map(str::to_string);
+        pinned.tag = tag.map(str::to_string);
+        pinned.validate_source()?;
+        *dep = pinned;
 
-        serialization::save_package(&self, working_dir)?;
+        serialization::save_package(self, working_dir)?;
         Ok(())
     }
+
+    /// Remove the version constraint or pin of a dependency
+    ///
+    /// Without a constraint, the next `install` picks the newest version compatible with
+    /// the rest of the graph.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the dependency to change
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the operation.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - Another depo process holds the project lock for longer than `DEPO_LOCK_TIMEOUT`
+    /// - The dependency is not found in the package (`DepoError::DependencyNotFound`)
+    /// - The dependency has neither a constraint nor a pin
+    /// - Package configuration cannot be saved
     pub fn remove_dependency_constraint(
         &mut self,
         name: &str,
         working_dir: &str,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
+        let _lock = ProjectLock::acquire(working_dir)?;
         let dep = self
             .dependencies
             .iter_mut()
             .find(|d| d.name == name)
-            .ok_or_else(|| anyhow::anyhow!("Dependency '{}' not found", name))?;
+            .ok_or_else(|| DepoError::DependencyNotFound {
+                name: name.to_string(),
+            })?;
 
-        if dep.version_constraint.is_none() {
-            anyhow::bail!("Dependency '{}' has no constraint to remove", name);
+        if dep.version_constraint.is_none() && !dep.is_pinned() {
+            return Err(DepoError::other(format!(
+                "Dependency '{}' has no constraint to remove",
+                name
+            )));
         }
 
         dep.version_constraint = None;
+        dep.rev = None;
+        dep.tag = None;
 
-        serialization::save_package(&self, working_dir)?;
+        serialization::save_package(self, working_dir)?;
         Ok(())
     }
+
+    /// Run an operation on the package as a transaction
+    ///
+    /// If the operation fails, the package itself, its package file, the lockfile and
+    /// `deps/` are all restored to their state from before the operation. The project lock
+    /// is held until the transaction has been committed or rolled back.
+    fn transaction<T>(
+        &mut self,
+        working_dir: &str,
+        operation: impl FnOnce(&mut Package) -> anyhow::Result<T>,
+    ) -> Result<T> {
+        let _lock = ProjectLock::acquire(working_dir)?;
+        let transaction = Transaction::begin(working_dir)?;
+        let before = self.clone();
+        match operation(self) {
+            Ok(value) => {
+                transaction.commit()?;
+                Ok(value)
+            }
+            Err(e) => {
+                *self = before;
+                // The error of the operation is what the caller needs to act on
+                if let Err(rollback) = transaction.rollback() {
+                    eprintln!("Failed to roll back: {}", rollback);
+                }
+                Err(e.into())
+            }
+        }
+    }
+}
+
+/// Describe every version constraint that the installed version of its library does not meet
+///
+/// Libraries fixed to an exact source by any requirement are skipped, as that requirement
+/// overrides the others (see `solver::is_fixed`).
+fn unmet_requirements(graph: &DependencyGraph, requirements: &[(Dependency, String)]) -> Vec<String> {
+    let mut unmet = Vec::new();
+    for (dep, required_by) in requirements {
+        let Some(req) = dep
+            .version_constraint
+            .as_deref()
+            .and_then(|c| semver::VersionReq::parse(c).ok())
+        else {
+            continue;
+        };
+        let Some(installed) = graph.get(&dep.name) else {
+            continue;
+        };
+        if requirements
+            .iter()
+            .any(|(other, _)| other.name == dep.name && solver::is_fixed(other))
+        {
+            continue;
+        }
+
+        let version = installed.version.strip_prefix('v').unwrap_or(&installed.version);
+        if !semver::Version::parse(version).is_ok_and(|v| req.matches(&v)) {
+            unmet.push(format!(
+                "{} requires {} {}, but {} {} is installed",
+                required_by, dep.name, req, dep.name, installed.version
+            ));
+        }
+    }
+    unmet
+}
+
+/// The error for project metadata that is not well-formed
+fn invalid_package(message: impl Display) -> DepoError {
+    DepoError::PackageInvalid {
+        message: message.to_string(),
+    }
+}
+
+/// Derive a package name from the directory a package is created in
+fn default_name(path: &Path) -> String {
+    let dir = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let name: String = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '-' })
+        .collect();
+    let name = name.trim_start_matches(['.', '-']);
+    if name.is_empty() {
+        "package".to_string()
+    } else {
+        name.to_string()
+    }
 }
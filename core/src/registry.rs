@@ -0,0 +1,492 @@
+//! # Registry Index
+//!
+//! This module reads package registries: a directory or Git repository holding one
+//! `<name>.yaml` file per package, listing the package's Git URL and every published
+//! version with its tag, commit, source checksum and declared dependencies, much like
+//! the crates.io index.
+//!
+//! Registries are named in the `registries` section of `package.yaml`, and a dependency
+//! is assigned to one through its `registry` field. Git registries are cloned into the
+//! download cache and brought up to date whenever they are opened with network access.
+//! New versions are added to an index with `publish`, which commits the change and, for
+//! remote indexes, pushes it.
+
+use crate::cache::Cache;
+use crate::dependency::{self, Dependency};
+use crate::fsutil;
+use crate::git::{self, FetchTarget};
+use anyhow::Context;
+use git2::{ObjectType, Oid, Repository, ResetType, Signature, TreeWalkMode, TreeWalkResult};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tempfile::TempDir;
+
+static SYNCED: Mutex<Option<HashSet<PathBuf>>> = Mutex::new(None);
+
+/// A registry as configured in `package.yaml`
+///
+/// ```yaml
+/// registries:
+///   - name: internal
+///     index: https://git.example.com/platform/depo-index.git
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RegistryConfig {
+    /// The name dependencies use to refer to the registry
+    pub name: String,
+    /// A Git URL of the index repository, or a directory relative to the package
+    pub index: String,
+}
+
+/// A package listed in a registry index
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegistryPackage {
+    /// The name of the package
+    pub name: String,
+    /// The Git URL the package's sources are fetched from
+    pub url: String,
+    /// Every published version, in publishing order
+    #[serde(default)]
+    pub versions: Vec<RegistryVersion>,
+}
+
+/// A published version of a registry package
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegistryVersion {
+    /// The semantic version
+    pub version: String,
+    /// The tag the version was published from
+    pub tag: String,
+    /// The full commit OID the tag points at
+    pub commit: String,
+    /// The checksum of the sources at that commit, as computed by `source_checksum`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// The dependencies declared in the version's `package.yaml`
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+impl RegistryPackage {
+    /// Find a published version by version, tag or commit
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The version (`1.2.0`), tag (`v1.2.0`) or full commit OID to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&RegistryVersion)` if the version is published, `None` otherwise.
+    pub fn find_version(&self, id: &str) -> Option<&RegistryVersion> {
+        self.versions
+            .iter()
+            .find(|v| v.version == id || v.tag == id || v.commit == id)
+    }
+
+    /// List the published semantic versions, newest first
+    ///
+    /// # Returns
+    ///
+    /// Returns every published version whose version string is a semantic version.
+    pub fn sorted_versions(&self) -> Vec<(Version, &RegistryVersion)> {
+        let mut versions: Vec<(Version, &RegistryVersion)> = self
+            .versions
+            .iter()
+            .filter_map(|v| Some((Version::parse(v.version.trim_start_matches('v')).ok()?, v)))
+            .collect();
+        versions.sort_by(|a, b| b.0.cmp(&a.0));
+        versions
+    }
+
+    /// Find the newest published version satisfying a version constraint
+    ///
+    /// # Arguments
+    ///
+    /// * `constraint` - The version constraint, or `None` to accept any version
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&RegistryVersion)` with the newest matching version, `None` if none matches.
+    pub fn newest_matching(&self, constraint: Option<&str>) -> Option<&RegistryVersion> {
+        let req = constraint.and_then(|c| VersionReq::parse(c).ok());
+        self.sorted_versions()
+            .into_iter()
+            .find(|(version, _)| req.as_ref().is_none_or(|r| r.matches(version)))
+            .map(|(_, v)| v)
+    }
+}
+
+/// An opened registry whose index is available on disk
+#[derive(Debug, Clone)]
+pub struct Registry {
+    /// The name of the registry
+    pub name: String,
+    /// The directory holding the index files
+    pub dir: PathBuf,
+}
+
+impl Registry {
+    /// Open a registry, cloning or updating Git indexes as needed
+    ///
+    /// Git indexes are kept in the download cache and fetched at most once per process.
+    /// Without network access, or in offline mode, a previously cloned index is used as
+    /// it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The registry configuration
+    /// * `working_dir` - The working directory of the package, local indexes are relative to it
+    /// * `update` - Whether a Git index may be fetched from its remote
+    /// * `cache` - The download cache Git indexes are kept in
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Registry>` with the location of the index files.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - A local index directory does not exist
+    /// - A Git index cannot be cloned or updated
+    /// - A Git index has never been cloned and network access is not allowed
+    pub fn open(
+        config: &RegistryConfig,
+        working_dir: &str,
+        update: bool,
+        cache: &Cache,
+    ) -> anyhow::Result<Registry> {
+        if !dependency::is_git_url(&config.index) {
+            let dir = Path::new(working_dir).join(&config.index);
+            if !dir.is_dir() {
+                anyhow::bail!(
+                    "Index of registry '{}' not found at '{}'",
+                    config.name,
+                    dir.display()
+                );
+            }
+            return Ok(Registry {
+                name: config.name.clone(),
+                dir,
+            });
+        }
+
+        let dir = cache.registry_dir(&config.index);
+        let update = update && !cache.is_offline();
+
+        if !dir.join(".git").exists() {
+            if !update {
+                anyhow::bail!(
+                    "Index of registry '{}' ({}) has not been downloaded, and network access is disabled",
+                    config.name,
+                    config.index
+                );
+            }
+            if dir.exists() {
+                fs::remove_dir_all(&dir)?;
+            }
+            Repository::clone(&config.index, &dir).with_context(|| {
+                format!("Failed to clone index of registry '{}' from '{}'", config.name, config.index)
+            })?;
+            is_synced(&dir);
+        } else if update && !is_synced(&dir) {
+            sync_index(&dir, &config.index).with_context(|| {
+                format!("Failed to update index of registry '{}' from '{}'", config.name, config.index)
+            })?;
+        }
+
+        Ok(Registry {
+            name: config.name.clone(),
+            dir,
+        })
+    }
+
+    /// Look up a package in the index
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The package name
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` if the package is not in the registry.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The name is not a valid dependency name
+    /// - The package's index file cannot be parsed
+    pub fn package(&self, name: &str) -> anyhow::Result<Option<RegistryPackage>> {
+        let path = entry_path(&self.dir, name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        let package = serde_yaml::from_str(&content).with_context(|| {
+            format!("Failed to parse '{}' in registry '{}'", path.display(), self.name)
+        })?;
+        Ok(Some(package))
+    }
+
+    /// Create a dependency on a package of this registry
+    ///
+    /// # Arguments
+    ///
+    /// * `package` - The registry package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Dependency` assigned to this registry, with no version constraint.
+    pub fn dependency(&self, package: &RegistryPackage) -> Dependency {
+        let full_name = dependency::repository_names(&package.url)
+            .map_or_else(|| package.name.clone(), |(_, full_name)| full_name);
+        let mut dep = Dependency::new(&package.name, &full_name, &package.url, None, "");
+        dep.registry = Some(self.name.clone());
+        dep
+    }
+}
+
+/// Find the registry package a dependency is assigned to
+///
+/// # Arguments
+///
+/// * `registries` - The opened registries of the package
+/// * `dep` - The dependency to look up
+///
+/// # Returns
+///
+/// Returns `Ok(None)` if the dependency is not assigned to a registry, otherwise the
+/// registry and its index entry for the dependency.
+///
+/// # Errors
+///
+/// This function will return an error if the registry is not configured or does not
+/// list the dependency.
+pub fn find_package<'a>(
+    registries: &'a [Registry],
+    dep: &Dependency,
+) -> anyhow::Result<Option<(&'a Registry, RegistryPackage)>> {
+    let Some(name) = &dep.registry else {
+        return Ok(None);
+    };
+    let registry = registries
+        .iter()
+        .find(|r| &r.name == name)
+        .ok_or_else(|| anyhow::anyhow!("'{}' uses unknown registry '{}'", dep.name, name))?;
+    let package = registry.package(&dep.name)?.ok_or_else(|| {
+        anyhow::anyhow!("'{}' is not published in registry '{}'", dep.name, name)
+    })?;
+    Ok(Some((registry, package)))
+}
+
+/// Add a new version of a package to a registry index
+///
+/// Local indexes are written in place, and the change is committed if the directory is
+/// a Git repository. Git indexes are cloned into a temporary directory, and the commit
+/// is pushed back to the branch the remote's `HEAD` points at.
+///
+/// # Arguments
+///
+/// * `config` - The registry to publish to
+/// * `working_dir` - The working directory of the package, local indexes are relative to it
+/// * `name` - The package name
+/// * `url` - The Git URL the package's sources are fetched from
+/// * `version` - The version to add
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the publication.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The name is not a valid dependency name
+/// - The version, tag or commit is already published
+/// - The package is already published from another URL
+/// - The index cannot be cloned, written, committed or pushed
+pub fn publish(
+    config: &RegistryConfig,
+    working_dir: &str,
+    name: &str,
+    url: &str,
+    version: RegistryVersion,
+) -> anyhow::Result<()> {
+    let message = format!("Publish {} {}", name, version.version);
+
+    if !dependency::is_git_url(&config.index) {
+        let dir = Path::new(working_dir).join(&config.index);
+        if !dir.is_dir() {
+            anyhow::bail!(
+                "Index of registry '{}' not found at '{}'",
+                config.name,
+                dir.display()
+            );
+        }
+        add_version(&dir, &config.name, name, url, version)?;
+        if let Ok(repo) = Repository::open(&dir) {
+            commit_entry(&repo, name, &message)?;
+        }
+        return Ok(());
+    }
+
+    let temp_dir = TempDir::new().context("Failed to create temporary directory")?;
+    let repo = Repository::clone(&config.index, temp_dir.path()).with_context(|| {
+        format!("Failed to clone index of registry '{}' from '{}'", config.name, config.index)
+    })?;
+    add_version(temp_dir.path(), &config.name, name, url, version)?;
+    commit_entry(&repo, name, &message)?;
+
+    let head = repo.head()?;
+    let branch = head
+        .name()
+        .ok_or_else(|| anyhow::anyhow!("Index of registry '{}' has no branch", config.name))?;
+    repo.find_remote("origin")?
+        .push(&[format!("{0}:{0}", branch)], None)
+        .with_context(|| format!("Failed to push to index of registry '{}'", config.name))?;
+    Ok(())
+}
+
+fn add_version(
+    index_dir: &Path,
+    registry: &str,
+    name: &str,
+    url: &str,
+    version: RegistryVersion,
+) -> anyhow::Result<()> {
+    let path = entry_path(index_dir, name)?;
+    let mut package = if path.exists() {
+        serde_yaml::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?
+    } else {
+        RegistryPackage {
+            name: name.to_string(),
+            url: url.to_string(),
+            versions: Vec::new(),
+        }
+    };
+
+    if package.url != url {
+        anyhow::bail!(
+            "'{}' is published in registry '{}' from '{}', not '{}'",
+            name,
+            registry,
+            package.url,
+            url
+        );
+    }
+    if let Some(existing) = [&version.version, &version.tag, &version.commit]
+        .iter()
+        .find_map(|id| package.find_version(id))
+    {
+        anyhow::bail!(
+            "'{}' {} ({} at {}) is already published in registry '{}'",
+            name,
+            existing.version,
+            existing.tag,
+            existing.commit,
+            registry
+        );
+    }
+
+    package.versions.push(version);
+    fsutil::write_atomic(&path, serde_yaml::to_string(&package)?)?;
+    Ok(())
+}
+
+fn commit_entry(repo: &Repository, name: &str, message: &str) -> anyhow::Result<()> {
+    let mut index = repo.index()?;
+    index.add_path(Path::new(&format!("{}.yaml", name)))?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("depo", "depo@localhost"))?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)?;
+    Ok(())
+}
+
+/// Get the index file of a package
+///
+/// # Arguments
+///
+/// * `index_dir` - The directory holding the index
+/// * `name` - The package name
+///
+/// # Returns
+///
+/// Returns a `Result<PathBuf>` with the `<index>/<name>.yaml` path.
+///
+/// # Errors
+///
+/// This function will return an error if the name is not a valid dependency name, so
+/// that it cannot point outside the index.
+pub fn entry_path(index_dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    dependency::validate_name(name)?;
+    Ok(index_dir.join(format!("{}.yaml", name)))
+}
+
+/// Compute the checksum of the sources at a commit
+///
+/// The checksum is the SHA-256 of every file in the commit's tree, taken in tree order,
+/// each contributing its path, mode, size and contents. It therefore does not depend on
+/// how the commit was cloned or on the object format of the repository.
+///
+/// # Arguments
+///
+/// * `repo` - A repository containing the commit
+/// * `commit` - The commit whose sources should be hashed
+///
+/// # Returns
+///
+/// Returns a `Result<String>` with the checksum as a lowercase hex string.
+pub fn source_checksum(repo: &Repository, commit: Oid) -> anyhow::Result<String> {
+    let tree = repo.find_commit(commit)?.tree()?;
+    let mut hasher = Sha256::new();
+    let mut failure = None;
+
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path = format!("{}{}", root, entry.name().unwrap_or_default());
+        match entry.kind() {
+            Some(ObjectType::Blob) => match repo.find_blob(entry.id()) {
+                Ok(blob) => {
+                    hasher.update(format!("{}\0{:o}\0{}\0", path, entry.filemode(), blob.size()));
+                    hasher.update(blob.content());
+                }
+                Err(e) => {
+                    failure = Some(e);
+                    return TreeWalkResult::Abort;
+                }
+            },
+            Some(ObjectType::Commit) => {
+                hasher.update(format!("{}\0{:o}\0{}\0", path, entry.filemode(), entry.id()));
+            }
+            _ => {}
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    if let Some(e) = failure {
+        return Err(e.into());
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Record that an index was fetched, returning whether it already was
+fn is_synced(dir: &Path) -> bool {
+    let mut synced = SYNCED.lock().unwrap_or_else(|e| e.into_inner());
+    !synced.get_or_insert_with(HashSet::new).insert(dir.to_path_buf())
+}
+
+fn sync_index(dir: &Path, url: &str) -> anyhow::Result<()> {
+    let repo = Repository::open(dir)?;
+    let commit = git::fetch(&repo, url, &FetchTarget::Head, None)?;
+    repo.reset(&repo.find_object(commit, None)?, ResetType::Hard, None)?;
+    Ok(())
+}
@@ -0,0 +1,551 @@
+//! # Package Search
+//!
+//! This module finds dependencies by name. Every place packages can be looked up in
+//! implements `SearchProvider`: GitHub, GitLab and Gitea servers, and static index files
+//! listing a team's internal catalogue. Providers are configured per project under
+//! `search` in `package.yaml` and per user in `search.yaml` inside Depo's configuration
+//! directory. `search` queries every configured provider and merges the results,
+//! keeping the first of several results pointing at the same repository.
+
+use crate::config::Config;
+use crate::dependency::Dependency;
+use crate::error::DepoError;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+
+/// The future returned by `SearchProvider::search`
+pub type SearchFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<Vec<Dependency>>> + Send + 'a>>;
+
+/// A place dependencies can be searched for
+pub trait SearchProvider: Send + Sync {
+    /// Get a short description of the provider, used in messages
+    fn name(&self) -> String;
+
+    /// Search for dependencies matching a query
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The name to search for
+    /// * `limit` - The maximum number of results to return
+    ///
+    /// # Returns
+    ///
+    /// Returns a future resolving to the matching dependencies, best match first.
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a>;
+}
+
+/// A search provider as configured in `package.yaml` or the user's `search.yaml`
+///
+/// ```yaml
+/// search:
+///   - kind: index
+///     path: third_party/catalogue.yaml
+///   - kind: gitlab
+///     url: https://gitlab.example.com
+///     token_env: GITLAB_TOKEN
+///   - kind: github
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SearchProviderConfig {
+    /// The GitHub search API, optionally at another base URL
+    Github {
+        /// Base URL of the API (defaults to `Config::github_api_url`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    /// A GitLab server
+    Gitlab {
+        /// Base URL of the server, e.g. `https://gitlab.com`
+        url: String,
+        /// Environment variable holding an access token, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token_env: Option<String>,
+    },
+    /// A Gitea or Forgejo server
+    Gitea {
+        /// Base URL of the server, e.g. `https://codeberg.org`
+        url: String,
+        /// Environment variable holding an access token, if any
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token_env: Option<String>,
+    },
+    /// A static index file listing packages
+    Index {
+        /// Path of the index file, or an `http(s)://` URL to download it from
+        path: String,
+    },
+}
+
+impl SearchProviderConfig {
+    /// Create the provider described by this configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration supplying the GitHub API, token and `User-Agent`
+    ///
+    /// # Returns
+    ///
+    /// Returns the configured `SearchProvider`.
+    pub fn provider(&self, config: &Config) -> Box<dyn SearchProvider> {
+        let user_agent = config.user_agent.clone();
+        match self {
+            SearchProviderConfig::Github { url } => Box::new(GitHubProvider {
+                api_url: url.clone().unwrap_or_else(|| config.github_api_url.clone()),
+                auth_header: config.get_auth_header(),
+                user_agent,
+            }),
+            SearchProviderConfig::Gitlab { url, token_env } => Box::new(GitLabProvider {
+                url: url.clone(),
+                token: token_env.as_ref().and_then(|name| std::env::var(name).ok()),
+                user_agent,
+            }),
+            SearchProviderConfig::Gitea { url, token_env } => Box::new(GiteaProvider {
+                url: url.clone(),
+                token: token_env.as_ref().and_then(|name| std::env::var(name).ok()),
+                user_agent,
+            }),
+            SearchProviderConfig::Index { path } => Box::new(IndexProvider {
+                location: path.clone(),
+                user_agent,
+            }),
+        }
+    }
+
+    /// Resolve a relative index file path against a base directory
+    ///
+    /// # Arguments
+    ///
+    /// * `base` - The directory the configuration was read from
+    ///
+    /// # Returns
+    ///
+    /// Returns the configuration with an absolute index path; other providers are unchanged.
+    pub fn resolve(self, base: &Path) -> SearchProviderConfig {
+        match self {
+            SearchProviderConfig::Index { path } if !is_remote(&path) && Path::new(&path).is_relative() => {
+                SearchProviderConfig::Index {
+                    path: base.join(path).to_string_lossy().into_owned(),
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct UserSearchConfig {
+    #[serde(default)]
+    search: Vec<SearchProviderConfig>,
+}
+
+/// Get the location of the user's search configuration
+///
+/// # Returns
+///
+/// Returns `DEPO_SEARCH_CONFIG` if set, otherwise `depo/search.yaml` inside the user's
+/// configuration directory, or `None` if there is no configuration directory.
+pub fn user_config_path() -> Option<PathBuf> {
+    match std::env::var("DEPO_SEARCH_CONFIG") {
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => dirs::config_dir().map(|dir| dir.join("depo").join("search.yaml")),
+    }
+}
+
+/// Load the search providers configured by the user
+///
+/// Relative index paths are resolved against the directory of the configuration file.
+///
+/// # Returns
+///
+/// Returns the configured providers, or an empty list if the user has no search configuration.
+///
+/// # Errors
+///
+/// This function will return an error if the configuration file exists but cannot be parsed.
+pub fn load_user_providers() -> anyhow::Result<Vec<SearchProviderConfig>> {
+    let Some(path) = user_config_path().filter(|p| p.exists()) else {
+        return Ok(Vec::new());
+    };
+    let content = fs::read_to_string(&path)?;
+    let config: UserSearchConfig = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(config.search.into_iter().map(|p| p.resolve(base)).collect())
+}
+
+/// Search several providers and merge their results
+///
+/// Providers are queried in order. Results pointing at a repository already found by
+/// an earlier provider are dropped. A provider that fails is reported and skipped, as
+/// long as at least one provider succeeds.
+///
+/// # Arguments
+///
+/// * `providers` - The providers to query
+/// * `query` - The name to search for
+/// * `limit` - The maximum number of results per provider
+///
+/// # Returns
+///
+/// Returns a `Result<Vec<Dependency>>` with the merged results.
+///
+/// # Errors
+///
+/// This function will return the error of the first provider if every provider fails.
+pub async fn search(
+    providers: &[Box<dyn SearchProvider>],
+    query: &str,
+    limit: usize,
+) -> anyhow::Result<Vec<Dependency>> {
+    let mut results: Vec<Dependency> = Vec::new();
+    let mut first_error = None;
+    let mut succeeded = false;
+
+    for provider in providers {
+        match provider.search(query, limit).await {
+            Ok(found) => {
+                succeeded = true;
+                for dep in found {
+                    let key = repository_key(&dep.url);
+                    if !results.iter().any(|r| repository_key(&r.url) == key) {
+                        results.push(dep);
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("Search with {} failed: {:#}", provider.name(), e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) if !succeeded => Err(e),
+        _ => Ok(results),
+    }
+}
+
+/// Normalise a repository URL so different spellings of the same repository compare equal
+///
+/// # Arguments
+///
+/// * `url` - A Git URL such as `https://host/owner/repo.git` or `git@host:owner/repo`
+///
+/// # Returns
+///
+/// Returns the lowercase `host/owner/repo` part of the URL.
+pub fn repository_key(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let url = url.split_once('@').map_or(url, |(_, rest)| rest);
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .replace(':', "/")
+        .to_lowercase()
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn dependencies_from(items: &[Value], name: &str, full_name: &str, url: &str) -> Vec<Dependency> {
+    items
+        .iter()
+        .filter_map(|repo| {
+            Some(Dependency::new(
+                repo[name].as_str()?,
+                repo[full_name].as_str()?,
+                repo[url].as_str()?,
+                None,
+                "",
+            ))
+        })
+        .collect()
+}
+
+/// Searches C++ repositories through the GitHub API
+pub struct GitHubProvider {
+    /// Base URL of the API
+    pub api_url: String,
+    /// Authorization header sent with every request, if any
+    pub auth_header: Option<String>,
+    /// `User-Agent` header sent with every request
+    pub user_agent: String,
+}
+
+impl SearchProvider for GitHubProvider {
+    fn name(&self) -> String {
+        format!("GitHub ({})", self.api_url)
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        Box::pin(async move {
+            let query = format!("{} language:C++", query);
+            let limit = limit.to_string();
+            let params = [
+                ("q", query.as_str()),
+                ("sort", "stars"),
+                ("order", "desc"),
+                ("per_page", limit.as_str()),
+            ];
+
+            let mut request = reqwest::Client::new()
+                .get(format!("{}/search/repositories", self.api_url.trim_end_matches('/')))
+                .header("User-Agent", &self.user_agent)
+                .query(&params);
+
+            if let Some(auth_header) = &self.auth_header {
+                request = request.header("Authorization", auth_header);
+            }
+
+            let response = request.send().await?;
+
+            if response.status() == 403 || response.status() == 429 {
+                let limited = rate_limited(
+                    "GitHub",
+                    &response,
+                    "x-ratelimit-reset",
+                    self.auth_header.is_none().then_some("Please add a GitHub token to .pkg.env file"),
+                );
+                let error_text = response.text().await?;
+                if error_text.contains("rate limit") {
+                    return Err(limited);
+                }
+                anyhow::bail!("GitHub API error: {}", error_text);
+            }
+
+            let data: Value = response.error_for_status()?.json().await?;
+            let items = data["items"].as_array().cloned().unwrap_or_default();
+            Ok(dependencies_from(&items, "name", "full_name", "clone_url"))
+        })
+    }
+}
+
+/// Searches projects on a GitLab server
+pub struct GitLabProvider {
+    /// Base URL of the server
+    pub url: String,
+    /// Access token sent as `PRIVATE-TOKEN`, if any
+    pub token: Option<String>,
+    /// `User-Agent` header sent with every request
+    pub user_agent: String,
+}
+
+impl SearchProvider for GitLabProvider {
+    fn name(&self) -> String {
+        format!("GitLab ({})", self.url)
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        Box::pin(async move {
+            let limit = limit.to_string();
+            let params = [
+                ("search", query),
+                ("order_by", "star_count"),
+                ("sort", "desc"),
+                ("per_page", limit.as_str()),
+            ];
+
+            let mut request = reqwest::Client::new()
+                .get(format!("{}/api/v4/projects", self.url.trim_end_matches('/')))
+                .header("User-Agent", &self.user_agent)
+                .query(&params);
+
+            if let Some(token) = &self.token {
+                request = request.header("PRIVATE-TOKEN", token);
+            }
+
+            let response = request.send().await?;
+            if response.status() == 429 {
+                return Err(rate_limited(
+                    "GitLab",
+                    &response,
+                    "ratelimit-reset",
+                    self.token.is_none().then_some(
+                        "Set token_env of the gitlab search provider to a variable holding an access token",
+                    ),
+                ));
+            }
+            let data: Value = response.error_for_status()?.json().await?;
+            let items = data.as_array().cloned().unwrap_or_default();
+            Ok(dependencies_from(&items, "path", "path_with_namespace", "http_url_to_repo"))
+        })
+    }
+}
+
+/// Searches repositories on a Gitea or Forgejo server
+pub struct GiteaProvider {
+    /// Base URL of the server
+    pub url: String,
+    /// Access token sent in the `Authorization` header, if any
+    pub token: Option<String>,
+    /// `User-Agent` header sent with every request
+    pub user_agent: String,
+}
+
+impl SearchProvider for GiteaProvider {
+    fn name(&self) -> String {
+        format!("Gitea ({})", self.url)
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        Box::pin(async move {
+            let limit = limit.to_string();
+            let params = [
+                ("q", query),
+                ("sort", "stars"),
+                ("order", "desc"),
+                ("limit", limit.as_str()),
+            ];
+
+            let mut request = reqwest::Client::new()
+                .get(format!("{}/api/v1/repos/search", self.url.trim_end_matches('/')))
+                .header("User-Agent", &self.user_agent)
+                .query(&params);
+
+            if let Some(token) = &self.token {
+                request = request.header("Authorization", format!("token {}", token));
+            }
+
+            let response = request.send().await?;
+            if response.status() == 429 {
+                return Err(rate_limited(
+                    "Gitea",
+                    &response,
+                    "x-ratelimit-reset",
+                    self.token.is_none().then_some(
+                        "Set token_env of the gitea search provider to a variable holding an access token",
+                    ),
+                ));
+            }
+            let data: Value = response.error_for_status()?.json().await?;
+            let items = data["data"].as_array().cloned().unwrap_or_default();
+            Ok(dependencies_from(&items, "name", "full_name", "clone_url"))
+        })
+    }
+}
+
+/// The error for a search a service refused because its rate limit was exceeded
+///
+/// # Arguments
+///
+/// * `service` - The name of the service
+/// * `response` - The response refusing the request
+/// * `reset_header` - The header holding the time the limit resets, if the service sends one
+/// * `hint` - How to raise the limit, or `None` if the request was already authenticated
+fn rate_limited(
+    service: &str,
+    response: &reqwest::Response,
+    reset_header: &str,
+    hint: Option<&str>,
+) -> anyhow::Error {
+    let reset = response
+        .headers()
+        .get(reset_header)
+        .and_then(|reset| reset.to_str().ok())
+        .and_then(|reset| reset.parse().ok());
+    DepoError::RateLimited {
+        service: service.to_string(),
+        reset,
+        hint: hint.unwrap_or("Try again later").to_string(),
+    }
+    .into()
+}
+
+/// A package listed in a static index file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IndexEntry {
+    /// The short name of the package
+    pub name: String,
+    /// The full name shown in search results (defaults to `name`)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub full_name: String,
+    /// The Git URL of the package
+    pub url: String,
+    /// A description, also searched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The contents of a static index file
+///
+/// ```yaml
+/// packages:
+///   - name: logging
+///     full_name: platform/logging
+///     url: https://git.example.com/platform/logging.git
+///     description: Structured logging for internal services
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct IndexFile {
+    /// Every package in the index
+    pub packages: Vec<IndexEntry>,
+}
+
+/// Searches a static index file, read from disk or downloaded over HTTP(S)
+pub struct IndexProvider {
+    /// Path or URL of the index file
+    pub location: String,
+    /// `User-Agent` header sent with every request
+    pub user_agent: String,
+}
+
+impl IndexProvider {
+    async fn load(&self) -> anyhow::Result<IndexFile> {
+        let content = if is_remote(&self.location) {
+            reqwest::Client::new()
+                .get(&self.location)
+                .header("User-Agent", &self.user_agent)
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        } else {
+            let path = self.location.strip_prefix("file://").unwrap_or(&self.location);
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read package index '{}'", path))?
+        };
+        serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse package index '{}'", self.location))
+    }
+}
+
+impl SearchProvider for IndexProvider {
+    fn name(&self) -> String {
+        format!("index '{}'", self.location)
+    }
+
+    fn search<'a>(&'a self, query: &'a str, limit: usize) -> SearchFuture<'a> {
+        Box::pin(async move {
+            let needle = query.to_lowercase();
+            let index = self.load().await?;
+            Ok(index
+                .packages
+                .iter()
+                .filter(|entry| {
+                    entry.name.to_lowercase().contains(&needle)
+                        || entry.full_name.to_lowercase().contains(&needle)
+                        || entry
+                            .description
+                            .as_ref()
+                            .is_some_and(|d| d.to_lowercase().contains(&needle))
+                })
+                .take(limit)
+                .map(|entry| {
+                    let full_name = if entry.full_name.is_empty() {
+                        &entry.name
+                    } else {
+                        &entry.full_name
+                    };
+                    Dependency::new(&entry.name, full_name, &entry.url, None, "")
+                })
+                .collect())
+        })
+    }
+}
@@ -1,12 +1,135 @@
 //! # Serialization and Persistence
 //!
 //! This module handles serialization and persistence of package configurations.
-//! It provides functions for saving and loading package data to/from YAML files.
+//! It provides functions for saving and loading package data and lockfiles to/from files.
+//! A package is described either by a `package.yaml` or by a `depo.toml`; both are read
+//! into the same document model (a `serde_yaml::Value`), migrated, and deserialised into
+//! the same `Package`. Saving a package updates an existing package file in place (see the
+//! `document` module), so comments, key order and unknown keys written by hand are
+//! preserved.
 
+use crate::document::{self, Document, TomlDocument};
+use crate::error::{DepoError, Result};
+use crate::fsutil;
+use crate::lockfile::Lockfile;
+use crate::migration;
 use crate::package::Package;
 use anyhow::bail;
+use serde_yaml::Value;
+use std::fmt::Display;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// The file formats a package can be described in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// `package.yaml`, the default
+    Yaml,
+    /// `depo.toml`
+    Toml,
+}
+
+impl ManifestFormat {
+    /// Every supported format, in the order they are looked for
+    pub const ALL: [ManifestFormat; 2] = [ManifestFormat::Yaml, ManifestFormat::Toml];
+
+    /// The name of the package file in this format
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ManifestFormat::Yaml => "package.yaml",
+            ManifestFormat::Toml => "depo.toml",
+        }
+    }
+
+    /// Find the format of the package file in a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory path to look for a package file in
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Option<ManifestFormat>>` with the format of the package file, or
+    /// `None` if the directory has none.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the directory has both a `package.yaml` and a
+    /// `depo.toml`, as it would be unclear which one describes the package.
+    pub fn detect(path: &str) -> Result<Option<ManifestFormat>> {
+        let found: Vec<ManifestFormat> = Self::ALL
+            .into_iter()
+            .filter(|format| Path::new(path).join(format.file_name()).exists())
+            .collect();
+        match found.as_slice() {
+            [] => Ok(None),
+            [format] => Ok(Some(*format)),
+            _ => Err(DepoError::ManifestConflict {
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    /// Parse the contents of a package file into the common document model
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The contents of a package file in this format
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Value>` with the parsed document.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the content is not valid in this format.
+    pub fn parse(self, content: &str) -> Result<Value> {
+        Ok(match self {
+            ManifestFormat::Yaml => serde_yaml::from_str(content)?,
+            ManifestFormat::Toml => {
+                serde_yaml::to_value(toml::from_str::<toml::Value>(content).map_err(DepoError::other)?)?
+            }
+        })
+    }
+
+    /// Serialise a document in this format
+    ///
+    /// # Arguments
+    ///
+    /// * `doc` - The document to serialise
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<String>` with the contents of the package file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the document cannot be represented in this format.
+    pub fn to_string(self, doc: &Value) -> Result<String> {
+        Ok(match self {
+            ManifestFormat::Yaml => serde_yaml::to_string(doc)?,
+            ManifestFormat::Toml => {
+                toml::to_string(&document::without_nulls(doc)).map_err(DepoError::other)?
+            }
+        })
+    }
+
+    /// Update the text of a package file in this format to hold a new document
+    fn edit(self, original: &str, known: &Value, new: &Value) -> anyhow::Result<String> {
+        Ok(match self {
+            ManifestFormat::Yaml => {
+                let mut document = Document::parse(original);
+                document.update(known, new)?;
+                document.to_string()
+            }
+            ManifestFormat::Toml => {
+                let mut document = TomlDocument::parse(original)?;
+                document.update(known, new)?;
+                document.to_string()
+            }
+        })
+    }
+}
 
 /// Check if a package configuration file exists in the given directory
 ///
@@ -16,12 +139,41 @@ use std::path::Path;
 ///
 /// # Returns
 ///
-/// Returns `true` if a `package.yaml` file exists in the directory, `false` otherwise.
+/// Returns `true` if a `package.yaml` or `depo.toml` file exists in the directory, `false`
+/// otherwise.
 pub fn package_exists(path: &str) -> bool {
-    Path::new(path).join("package.yaml").exists()
+    ManifestFormat::ALL
+        .iter()
+        .any(|format| Path::new(path).join(format.file_name()).exists())
+}
+
+/// Get the package file of the given directory
+///
+/// # Arguments
+///
+/// * `path` - The directory path containing the package configuration file
+///
+/// # Returns
+///
+/// Returns a `Result<(PathBuf, ManifestFormat)>` with the path and format of the package file.
+///
+/// # Errors
+///
+/// This function will return `DepoError::ManifestNotFound` if the directory has no package
+/// file, and `DepoError::ManifestConflict` if it has both a `package.yaml` and a `depo.toml`.
+pub fn package_file(path: &str) -> Result<(PathBuf, ManifestFormat)> {
+    match ManifestFormat::detect(path)? {
+        Some(format) => Ok((Path::new(path).join(format.file_name()), format)),
+        None => Err(DepoError::ManifestNotFound {
+            path: path.to_string(),
+        }),
+    }
 }
 
-/// Load a package configuration from a YAML file
+/// Load a package configuration from a `package.yaml` or `depo.toml` file
+///
+/// Package files written with an older schema version are migrated in memory; the file
+/// itself is only upgraded when the package is saved or `migrate_package` is called.
 ///
 /// # Arguments
 ///
@@ -34,21 +186,132 @@ pub fn package_exists(path: &str) -> bool {
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The package file does not exist
+/// - The package file does not exist (`DepoError::ManifestNotFound`), or both formats exist
 /// - The file cannot be read
-/// - The YAML content cannot be parsed into a Package structure
-pub fn load_package(path: &str) -> anyhow::Result<Package> {
-    let file = Path::new(path).join("package.yaml");
-    if !package_exists(path) {
-        bail!("Package file not found at path: {}", path);
+/// - The file was written by a newer version of Depo, the content cannot be parsed into a
+///   Package structure or the project metadata is invalid (`DepoError::ManifestInvalid`)
+pub fn load_package(path: &str) -> Result<Package> {
+    let (file, format) = package_file(path)?;
+    let content = fs::read_to_string(&file)?;
+    parse_package(&content, format).map_err(|e| invalid_package_file(&file, e))
+}
+
+/// The error for a package file that cannot be read as a package
+fn invalid_package_file(file: &Path, e: impl Display) -> DepoError {
+    DepoError::ManifestInvalid {
+        path: file.display().to_string(),
+        message: e.to_string(),
     }
+}
 
-    let content = fs::read_to_string(file)?;
-    let package: Package = serde_yaml::from_str(&content)?;
+/// Parse the contents of a package configuration file
+///
+/// # Arguments
+///
+/// * `content` - The contents of a `package.yaml` or `depo.toml`
+/// * `format` - The format of `content`
+///
+/// # Returns
+///
+/// Returns a `Result<Package>` with the package, migrated to the current schema version.
+///
+/// # Errors
+///
+/// This function will return an error if the content cannot be migrated, parsed or validated.
+pub fn parse_package(content: &str, format: ManifestFormat) -> Result<Package> {
+    let mut doc = format.parse(content)?;
+    migration::migrate(&mut doc)?;
+    let package: Package = serde_yaml::from_value(doc)?;
+    package.validate()?;
     Ok(package)
 }
 
-/// Save a package configuration to a YAML file
+/// Upgrade a package configuration file to the current schema version
+///
+/// # Arguments
+///
+/// * `path` - The directory path containing the package configuration file
+///
+/// # Returns
+///
+/// Returns a `Result<u32>` with the schema version the file had before; the file is only
+/// rewritten if that is older than the current one.
+///
+/// # Errors
+///
+/// This function will return an error if the package cannot be loaded or saved.
+pub fn migrate_package(path: &str) -> Result<u32> {
+    let (file, format) = package_file(path)?;
+    let content = fs::read_to_string(&file)?;
+    let original = migration::schema_version(&format.parse(&content)?)?;
+    let package = load_package(path)?;
+    if original < migration::SCHEMA_VERSION {
+        save_package(&package, path)?;
+    }
+    Ok(original)
+}
+
+/// Convert the package file of a directory to another format
+///
+/// The package is written in the new format and the old file is removed. Every value,
+/// including keys Depo does not know about, is carried over; comments are not.
+///
+/// # Arguments
+///
+/// * `path` - The directory path containing the package configuration file
+/// * `to` - The format to convert to
+///
+/// # Returns
+///
+/// Returns a `Result<PathBuf>` with the path of the new package file.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The package cannot be loaded or is already in the requested format
+/// - The package cannot be represented in the requested format without changing it
+/// - The new file cannot be written or the old one cannot be removed
+pub fn convert_package(path: &str, to: ManifestFormat) -> Result<PathBuf> {
+    let (file, from) = package_file(path)?;
+    if from == to {
+        return Err(DepoError::other(format!(
+            "{} is already a {}",
+            file.display(),
+            to.file_name()
+        )));
+    }
+
+    let content = fs::read_to_string(&file)?;
+    let package = parse_package(&content, from).map_err(|e| invalid_package_file(&file, e))?;
+    let mut doc = from.parse(&content)?;
+    migration::migrate(&mut doc)?;
+
+    let converted = to.to_string(&doc)?;
+    let check = parse_package(&converted, to)?;
+    if serde_yaml::to_value(&check)? != serde_yaml::to_value(&package)? {
+        return Err(DepoError::other(format!(
+            "{} cannot be converted to {} without changing it",
+            file.display(),
+            to.file_name()
+        )));
+    }
+
+    let target = Path::new(path).join(to.file_name());
+    fsutil::write_atomic(&target, converted)?;
+    fs::remove_file(&file)?;
+    Ok(target)
+}
+
+/// Save a package configuration to its package file
+///
+/// The package is saved in the format of the existing package file, or as `package.yaml`
+/// if there is none. An existing file is edited in place: only the parts of it that changed
+/// are rewritten, keeping comments, formatting and unknown keys. Should the edited file not
+/// read back as exactly `package`, a warning is printed and the whole file is serialised
+/// again instead.
+///
+/// Saving does not take the project lock; operations that change the dependencies of a
+/// project hold it from loading the package until it is saved (see the `lock` module).
 ///
 /// # Arguments
 ///
@@ -62,11 +325,99 @@ pub fn load_package(path: &str) -> anyhow::Result<Package> {
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The package cannot be serialized to YAML
+/// - Both a `package.yaml` and a `depo.toml` exist
+/// - The package cannot be serialized
+/// - The file cannot be written to the specified path
+pub fn save_package(package: &Package, path: &str) -> Result<()> {
+    let format = ManifestFormat::detect(path)?.unwrap_or(ManifestFormat::Yaml);
+    let file = Path::new(path).join(format.file_name());
+    let content = match fs::read_to_string(&file) {
+        Ok(original) => match edit_package(&original, format, package) {
+            Ok(edited) => edited,
+            Err(e) => {
+                eprintln!("Editing {} in place failed: {:#}", file.display(), e);
+                eprintln!("Falling back to writing the whole file, without its comments and formatting...");
+                format.to_string(&serde_yaml::to_value(package)?)?
+            }
+        },
+        Err(_) => format.to_string(&serde_yaml::to_value(package)?)?,
+    };
+    fsutil::write_atomic(&file, content)?;
+    Ok(())
+}
+
+/// Edit the text of a package file in place to hold `package`
+fn edit_package(original: &str, format: ManifestFormat, package: &Package) -> anyhow::Result<String> {
+    let known = serde_yaml::to_value(parse_package(original, format)?)?;
+    let new = serde_yaml::to_value(package)?;
+    let edited = format.edit(original, &known, &new)?;
+
+    // The edit must still deserialise into exactly the package being saved
+    if serde_yaml::to_value(parse_package(&edited, format)?)? != new {
+        bail!("Editing the package file in place changed its meaning");
+    }
+    Ok(edited)
+}
+
+/// Check if a lockfile exists in the given directory
+///
+/// # Arguments
+///
+/// * `path` - The directory path to check for a lockfile
+///
+/// # Returns
+///
+/// Returns `true` if a `depo.lock` file exists in the directory, `false` otherwise.
+pub fn lockfile_exists(path: &str) -> bool {
+    Path::new(path).join("depo.lock").exists()
+}
+
+/// Load the lockfile from the given directory
+///
+/// A missing lockfile is not an error; an empty lockfile is returned instead.
+///
+/// # Arguments
+///
+/// * `path` - The directory path containing the lockfile
+///
+/// # Returns
+///
+/// Returns a `Result<Lockfile>` containing the loaded lockfile or an error if loading fails.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The file cannot be read
+/// - The YAML content cannot be parsed into a Lockfile structure
+pub fn load_lockfile(path: &str) -> Result<Lockfile> {
+    if !lockfile_exists(path) {
+        return Ok(Lockfile::new());
+    }
+
+    let content = fs::read_to_string(Path::new(path).join("depo.lock"))?;
+    let lockfile: Lockfile = serde_yaml::from_str(&content)?;
+    Ok(lockfile)
+}
+
+/// Save the lockfile to the given directory
+///
+/// # Arguments
+///
+/// * `lockfile` - The lockfile to save
+/// * `path` - The directory path where the lockfile should be saved
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the save operation.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The lockfile cannot be serialized to YAML
 /// - The file cannot be written to the specified path
-pub fn save_package(package: &Package, path: &str) -> anyhow::Result<()> {
-    let file = Path::new(path).join("package.yaml");
-    let yaml_str = serde_yaml::to_string(package)?;
-    fs::write(file, yaml_str)?;
+pub fn save_lockfile(lockfile: &Lockfile, path: &str) -> Result<()> {
+    let file = Path::new(path).join("depo.lock");
+    let yaml_str = serde_yaml::to_string(lockfile)?;
+    fsutil::write_atomic(&file, yaml_str)?;
     Ok(())
 }
@@ -0,0 +1,527 @@
+//! # Version Solving
+//!
+//! This module picks a single version for every library in the dependency graph.
+//! It performs a backtracking search over the semantic version tags of each
+//! repository, newest first, and checks every choice against the version
+//! constraints of all packages that require the library. When no combination
+//! satisfies every constraint, the error explains which chains of requirements
+//! conflict.
+//!
+//! Libraries that any package fixes to an exact source (see `is_fixed`) are not solved
+//! but resolved at install time. Such a requirement overrides the version constraints
+//! other packages place on the library, which lets a project replace a library deep in
+//! the graph, for example with a local checkout.
+
+use crate::cache::Cache;
+use crate::dependency::Dependency;
+use crate::error::DepoError;
+use crate::git::{self, FetchTarget};
+use crate::lockfile::LockedDependency;
+use crate::registry::{self, Registry};
+use crate::serialization::{self, ManifestFormat};
+use crate::source::Sources;
+use anyhow::Context;
+use git2::{Oid, Repository};
+use semver::{Version, VersionReq};
+use std::collections::{HashMap, HashSet};
+use tempfile::TempDir;
+
+/// A version of a library that the solver can choose
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The semantic version parsed from the tag
+    pub version: Version,
+    /// The tag name as it appears in the repository (e.g. `v1.2.0`)
+    pub tag: String,
+    /// The full commit OID the tag points at
+    pub commit: String,
+}
+
+/// Source of available versions and their declared dependencies
+///
+/// The solver only talks to repositories through this trait, which keeps the search
+/// independent of how versions are discovered.
+pub trait VersionProvider {
+    /// List the available versions of a dependency, newest first
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency whose versions should be listed
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Candidate>>` with every semantic version tag of the dependency.
+    fn versions(&mut self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>>;
+
+    /// List the dependencies declared by a specific version of a dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency being inspected
+    /// * `candidate` - The version whose `package.yaml` should be read
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Dependency>>` with the dependencies of that version.
+    fn dependencies(
+        &mut self,
+        dep: &Dependency,
+        candidate: &Candidate,
+    ) -> anyhow::Result<Vec<Dependency>>;
+}
+
+/// Version provider that inspects the tags of each repository with git
+///
+/// The versions of a dependency are those offered by its source (see `Source::versions`),
+/// such as the tags advertised by a Git remote. The `package.yaml` of a version is read
+/// from the download cache when that version is cached, and otherwise after fetching only
+/// that tag with depth 1 into a temporary bare repository; remotes that reject shallow
+/// fetches get the tag's full history instead.
+///
+/// Dependencies assigned to a registry read their dependencies from the registry's index
+/// instead.
+pub struct GitVersionProvider<'a> {
+    cache: Cache,
+    sources: &'a Sources,
+    registries: Vec<Registry>,
+    repos: HashMap<String, (TempDir, Repository)>,
+}
+
+impl<'a> GitVersionProvider<'a> {
+    /// Create a new provider with no repositories fetched yet
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The download cache used for cached sources
+    /// * `sources` - The sources listing the versions of each dependency
+    pub fn new(cache: Cache, sources: &'a Sources) -> GitVersionProvider<'a> {
+        GitVersionProvider {
+            cache,
+            sources,
+            registries: Vec::new(),
+            repos: HashMap::new(),
+        }
+    }
+
+    /// Use registry indexes for dependencies assigned to a registry
+    ///
+    /// # Arguments
+    ///
+    /// * `registries` - The opened registries of the package
+    pub fn with_registries(mut self, registries: Vec<Registry>) -> GitVersionProvider<'a> {
+        self.registries = registries;
+        self
+    }
+
+    fn repo(&mut self, url: &str) -> anyhow::Result<&Repository> {
+        if !self.repos.contains_key(url) {
+            let temp_dir = TempDir::new().context("Failed to create temporary directory")?;
+            let repo = Repository::init_bare(temp_dir.path())?;
+            self.repos.insert(url.to_string(), (temp_dir, repo));
+        }
+        Ok(&self.repos[url].1)
+    }
+}
+
+impl VersionProvider for GitVersionProvider<'_> {
+    fn versions(&mut self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        self.sources.for_dependency(dep)?.versions(dep)
+    }
+
+    fn dependencies(
+        &mut self,
+        dep: &Dependency,
+        candidate: &Candidate,
+    ) -> anyhow::Result<Vec<Dependency>> {
+        if let Some((_, package)) = registry::find_package(&self.registries, dep)?
+            && let Some(published) = package.find_version(&candidate.commit)
+        {
+            return Ok(published.dependencies.clone());
+        }
+
+        if let Some(src) = self.cache.source_path(&dep.url, &candidate.commit) {
+            let src = src.to_string_lossy();
+            if !serialization::package_exists(&src) {
+                return Ok(Vec::new());
+            }
+            let nested = serialization::load_package(&src).with_context(|| {
+                format!("Failed to read the package file of '{}' {}", dep.name, candidate.tag)
+            })?;
+            return Ok(nested.dependencies);
+        }
+
+        if self.cache.is_offline() {
+            anyhow::bail!(
+                "Offline mode: tag '{}' ({}) of '{}' ({}) is not cached",
+                candidate.tag,
+                candidate.commit,
+                dep.name,
+                dep.url
+            );
+        }
+
+        let repo = self.repo(&dep.url)?;
+        let commit = Oid::from_str(&candidate.commit)?;
+
+        if repo.find_commit(commit).is_err() {
+            let target = FetchTarget::Tag(candidate.tag.clone());
+            if git::fetch(repo, &dep.url, &target, Some(1)).is_err() {
+                git::fetch(repo, &dep.url, &target, None)?;
+            }
+        }
+
+        // A commit can only be described by one of the package file formats
+        let Some((format, object)) = ManifestFormat::ALL.into_iter().find_map(|format| {
+            repo.revparse_single(&format!("{}:{}", candidate.commit, format.file_name()))
+                .ok()
+                .map(|object| (format, object))
+        }) else {
+            return Ok(Vec::new());
+        };
+        let blob = object.peel_to_blob()?;
+        let content = std::str::from_utf8(blob.content())?;
+        let nested = serialization::parse_package(content, format).with_context(|| {
+            format!("Failed to read {} of '{}' {}", format.file_name(), dep.name, candidate.tag)
+        })?;
+        Ok(nested.dependencies)
+    }
+}
+
+/// The version chosen for each library in the graph
+#[derive(Debug, Default)]
+pub struct Resolution {
+    selected: HashMap<String, (Dependency, Candidate)>,
+}
+
+impl Resolution {
+    /// Get the version chosen for a library
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the library
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&Candidate)` if the solver picked a version, `None` if the library
+    /// was not part of the solve (for example because it is pinned to a branch).
+    pub fn get(&self, name: &str) -> Option<&Candidate> {
+        self.selected.get(name).map(|(_, c)| c)
+    }
+
+    /// Describe the chosen version of a dependency as a lockfile entry
+    ///
+    /// `Package::install_graph` passes the entry to `Source::fetch` to check out exactly the
+    /// chosen commit.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency as declared by the package that requires it
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(LockedDependency)` if a version was chosen from the same URL, `None` otherwise.
+    pub fn locked(&self, dep: &Dependency) -> Option<LockedDependency> {
+        let (solved, candidate) = self.selected.get(&dep.name)?;
+        if solved.url != dep.url {
+            return None;
+        }
+        Some(LockedDependency {
+            name: dep.name.clone(),
+            url: dep.url.clone(),
+            version: candidate.tag.clone(),
+            commit: candidate.commit.clone(),
+            tag: Some(candidate.tag.clone()),
+            version_constraint: dep.version_constraint.clone(),
+            rev: None,
+            path: None,
+            archive: None,
+            sha256: None,
+            requires: Vec::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Requirement {
+    dep: Dependency,
+    req: VersionReq,
+    required_by: Vec<String>,
+}
+
+impl Requirement {
+    fn describe(&self) -> String {
+        format!(
+            "{} requires {} {}",
+            self.required_by.join(" -> "),
+            self.dep.name,
+            self.dep.version_constraint.as_deref().unwrap_or("*")
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    requirements: Vec<Requirement>,
+    selected: Vec<(String, Candidate)>,
+    unmanaged: HashSet<String>,
+}
+
+impl State {
+    fn selected(&self, name: &str) -> Option<&Candidate> {
+        self.selected
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, c)| c)
+    }
+
+    fn next_unresolved(&self) -> Option<String> {
+        self.requirements
+            .iter()
+            .map(|r| &r.dep.name)
+            .find(|name| self.selected(name).is_none() && !self.unmanaged.contains(*name))
+            .cloned()
+    }
+}
+
+/// Backtracking solver picking one version per library
+pub struct Solver<'a, P: VersionProvider> {
+    provider: &'a mut P,
+    preferred: HashMap<String, String>,
+    versions: HashMap<String, Vec<Candidate>>,
+    conflict: Option<DepoError>,
+}
+
+impl<'a, P: VersionProvider> Solver<'a, P> {
+    /// Create a new solver using the given version provider
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The provider used to list versions and read nested dependencies
+    pub fn new(provider: &'a mut P) -> Solver<'a, P> {
+        Solver {
+            provider,
+            preferred: HashMap::new(),
+            versions: HashMap::new(),
+            conflict: None,
+        }
+    }
+
+    /// Prefer a specific commit of a library when it satisfies all constraints
+    ///
+    /// This is used to keep versions recorded in the lockfile stable across solves.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the library
+    /// * `commit` - The commit OID to try first
+    pub fn prefer(&mut self, name: &str, commit: &str) {
+        self.preferred.insert(name.to_string(), commit.to_string());
+    }
+
+    /// Pick a version for every library reachable from the given dependencies
+    ///
+    /// # Arguments
+    ///
+    /// * `roots` - The direct dependencies declared in `package.yaml`
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Resolution>` with one chosen version per library.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - Versions or nested manifests cannot be read from a repository
+    /// - No combination of versions satisfies every constraint, with an explanation
+    ///   of the conflicting requirement chains
+    pub fn solve(&mut self, roots: &[Dependency]) -> anyhow::Result<Resolution> {
+        let mut state = State::default();
+        for dep in roots {
+            state
+                .requirements
+                .push(requirement(dep, vec!["package.yaml".to_string()]));
+        }
+
+        match self.search(state)? {
+            Some(state) => {
+                let mut resolution = Resolution::default();
+                for (name, candidate) in state.selected {
+                    let dep = state
+                        .requirements
+                        .iter()
+                        .find(|r| r.dep.name == name)
+                        .map(|r| r.dep.clone())
+                        .expect("selected library has a requirement");
+                    resolution.selected.insert(name, (dep, candidate));
+                }
+                Ok(resolution)
+            }
+            None => Err(self
+                .conflict
+                .take()
+                .map_or_else(|| anyhow::anyhow!("Version solving failed"), Into::into)),
+        }
+    }
+
+    fn search(&mut self, mut state: State) -> anyhow::Result<Option<State>> {
+        let Some(name) = state.next_unresolved() else {
+            return Ok(Some(state));
+        };
+
+        let reqs: Vec<Requirement> = state
+            .requirements
+            .iter()
+            .filter(|r| r.dep.name == name)
+            .cloned()
+            .collect();
+
+        let constrained = reqs.iter().any(|r| r.dep.version_constraint.is_some());
+        if reqs.iter().any(|r| is_fixed(&r.dep)) {
+            state.unmanaged.insert(name);
+            return self.search(state);
+        }
+
+        let available = self.versions(&reqs[0].dep)?;
+        if available.is_empty() && !constrained {
+            state.unmanaged.insert(name);
+            return self.search(state);
+        }
+
+        let mut candidates: Vec<Candidate> = available
+            .iter()
+            .filter(|c| reqs.iter().all(|r| r.req.matches(&c.version)))
+            .cloned()
+            .collect();
+
+        if candidates.is_empty() {
+            self.record_conflict(&name, &reqs, &available);
+            return Ok(None);
+        }
+
+        if let Some(commit) = self.preferred.get(&name)
+            && let Some(pos) = candidates.iter().position(|c| &c.commit == commit)
+        {
+            let preferred = candidates.remove(pos);
+            candidates.insert(0, preferred);
+        }
+
+        'candidates: for candidate in candidates {
+            let mut next = state.clone();
+            let mut path = reqs[0].required_by.clone();
+            path.push(format!("{} {}", name, candidate.version));
+
+            let children = self.provider.dependencies(&reqs[0].dep, &candidate)?;
+            next.selected.push((name.clone(), candidate));
+
+            for child in &children {
+                let child_req = requirement(child, path.clone());
+                let mut conflicting: Vec<Requirement> = next
+                    .requirements
+                    .iter()
+                    .filter(|r| r.dep.name == child.name)
+                    .cloned()
+                    .collect();
+
+                // A library is only left unmanaged without being fixed if it has no version
+                // tags, which no constraint added later can be met by
+                if next.unmanaged.contains(&child.name)
+                    && child.version_constraint.is_some()
+                    && !is_fixed(child)
+                    && !conflicting.iter().any(|r| is_fixed(&r.dep))
+                {
+                    conflicting.push(child_req);
+                    let available = self.versions(child)?;
+                    self.record_conflict(&child.name, &conflicting, &available);
+                    continue 'candidates;
+                }
+
+                if let Some(selected) = next.selected(&child.name)
+                    && !child_req.req.matches(&selected.version)
+                {
+                    conflicting.push(child_req);
+                    let available = self.versions(child)?;
+                    self.record_conflict(&child.name, &conflicting, &available);
+                    continue 'candidates;
+                }
+                next.requirements.push(child_req);
+            }
+
+            if let Some(solved) = self.search(next)? {
+                return Ok(Some(solved));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn versions(&mut self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        if !self.versions.contains_key(&dep.name) {
+            let versions = self.provider.versions(dep)?;
+            self.versions.insert(dep.name.clone(), versions);
+        }
+        Ok(self.versions[&dep.name].clone())
+    }
+
+    fn record_conflict(&mut self, name: &str, reqs: &[Requirement], available: &[Candidate]) {
+        let mut message = format!(
+            "Could not find a version of '{}' that satisfies all requirements:",
+            name
+        );
+        for req in reqs {
+            message.push_str(&format!("\n  {}", req.describe()));
+        }
+
+        if available.is_empty() {
+            message.push_str(&format!("\n'{}' has no semantic version tags", name));
+        } else {
+            let versions: Vec<String> = available.iter().map(|c| c.version.to_string()).collect();
+            message.push_str(&format!(
+                "\nAvailable versions of '{}': {}",
+                name,
+                versions.join(", ")
+            ));
+        }
+
+        self.conflict = Some(DepoError::NoMatchingVersion {
+            name: name.to_string(),
+            message,
+        });
+    }
+}
+
+/// Check whether a requirement fixes a library to an exact source instead of a version range
+///
+/// Dependencies with a local path, an archive, a `rev` or `tag` pin, or a constraint that
+/// is not a semantic version requirement (such as a branch name) are fixed. The version
+/// constraints other packages place on a fixed library are not checked.
+///
+/// # Arguments
+///
+/// * `dep` - The dependency as declared by the package that requires it
+///
+/// # Returns
+///
+/// Returns `true` if the dependency is resolved at install time, `false` if the solver
+/// picks its version.
+pub fn is_fixed(dep: &Dependency) -> bool {
+    dep.path.is_some()
+        || dep.archive.is_some()
+        || dep.is_pinned()
+        || dep
+            .version_constraint
+            .as_deref()
+            .is_some_and(|c| VersionReq::parse(c).is_err())
+}
+
+fn requirement(dep: &Dependency, required_by: Vec<String>) -> Requirement {
+    let req = dep
+        .version_constraint
+        .as_deref()
+        .and_then(|c| VersionReq::parse(c).ok())
+        .unwrap_or(VersionReq::STAR);
+    Requirement {
+        dep: dep.clone(),
+        req,
+        required_by,
+    }
+}
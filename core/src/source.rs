@@ -0,0 +1,1081 @@
+//! # Dependency Sources
+//!
+//! This module defines where dependencies come from. Each kind of source (a Git
+//! repository, a local directory or a release archive) implements the `Source` trait,
+//! which covers listing the versions a source offers, fetching a version into the
+//! dependency's directory and identifying the version that is installed.
+//!
+//! `Package::install` picks a source for every dependency from a `Sources` collection,
+//! so new kinds of sources can be added, and existing ones replaced with fakes in
+//! tests, without changing the package logic.
+
+use crate::archive::{self, ArchiveFormat};
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::dependency::{Dependency, LOCAL_VERSION};
+use crate::error::DepoError;
+use crate::fsutil;
+use crate::git::{self, FetchTarget, RemoteRefs};
+use crate::journal::{self, Stage};
+use crate::lockfile::LockedDependency;
+use crate::registry::{self, Registry};
+use crate::solver::Candidate;
+use crate::transaction;
+use anyhow::Context;
+use git2::{Oid, Repository};
+use semver::{Version, VersionReq};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File written into an unpacked archive dependency, holding the archive's checksum
+const ARCHIVE_MARKER: &str = ".depo-archive";
+
+/// A kind of place dependencies are fetched from
+pub trait Source {
+    /// Check whether this source provides a dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency as declared in `package.yaml`
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if this source should fetch the dependency, `false` otherwise.
+    fn handles(&self, dep: &Dependency) -> bool;
+
+    /// List the versions this source can install, newest first
+    ///
+    /// These are the versions the version solver chooses from. Sources whose dependencies
+    /// are fixed by their declaration, such as local paths and archives, offer none.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency whose versions should be listed
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<Candidate>>` with the available versions.
+    fn versions(&self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>>;
+
+    /// Fetch a version of a dependency into its directory
+    ///
+    /// On success the dependency's `version` is set to the version that was installed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to fetch
+    /// * `working_dir` - The working directory where dependencies are installed
+    /// * `locked` - The lockfile entry to reproduce, if one exists
+    /// * `network` - Whether the source may access the network
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<LockedDependency>` describing exactly what was installed.
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        locked: Option<&LockedDependency>,
+        network: bool,
+    ) -> anyhow::Result<LockedDependency>;
+
+    /// Identify the version of a dependency that is currently installed
+    ///
+    /// `Package::install` uses this to remove the previously installed version once a
+    /// different one has been fetched.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency, with the version recorded in `package.yaml` or `depo.lock`
+    /// * `working_dir` - The working directory where dependencies are installed
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` if the dependency is not installed at the recorded version.
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// The sources available to a package
+#[derive(Default)]
+pub struct Sources {
+    sources: Vec<Box<dyn Source>>,
+}
+
+impl Sources {
+    /// Create an empty registry
+    pub fn new() -> Sources {
+        Sources {
+            sources: Vec::new(),
+        }
+    }
+
+    /// Create a registry with the built-in Git, archive and local path sources
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the running operation, e.g. whether it is offline
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the download cache cannot be opened.
+    pub fn builtin(config: &Config) -> anyhow::Result<Sources> {
+        let mut sources = Sources::new();
+        sources.register(Box::new(GitSource::new(config)?));
+        sources.register(Box::new(ArchiveSource::new(config)));
+        sources.register(Box::new(PathSource));
+        Ok(sources)
+    }
+
+    /// Register a source
+    ///
+    /// Sources registered later take precedence, which allows replacing a built-in source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source to add
+    pub fn register(&mut self, source: Box<dyn Source>) {
+        self.sources.insert(0, source);
+    }
+
+    /// Find the source that provides a dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<&dyn Source>` with the first registered source handling the dependency.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if no registered source handles the dependency.
+    pub fn for_dependency(&self, dep: &Dependency) -> anyhow::Result<&dyn Source> {
+        self.sources
+            .iter()
+            .find(|source| source.handles(dep))
+            .map(|source| source.as_ref())
+            .ok_or_else(|| anyhow::anyhow!("No source can provide dependency '{}'", dep.name))
+    }
+}
+
+/// Source for dependencies hosted in Git repositories
+///
+/// Versions are the semantic version tags advertised by the remote, or in offline mode
+/// those of them present in the download cache. A dependency is installed at its locked
+/// commit, its `rev` or `tag` pin, the newest tag matching its version constraint, or a
+/// branch named by the constraint, going through the download cache whenever possible.
+pub struct GitSource {
+    cache: Cache,
+}
+
+impl Source for GitSource {
+    fn handles(&self, dep: &Dependency) -> bool {
+        dep.path.is_none() && dep.archive.is_none()
+    }
+
+    fn versions(&self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        let cache = &self.cache;
+        let refs = cache.list_refs(&dep.url)?;
+        let mut candidates = Vec::new();
+
+        for tag in refs.tags {
+            let Ok(version) = Version::parse(tag.name.strip_prefix('v').unwrap_or(&tag.name))
+            else {
+                continue;
+            };
+            if cache.is_offline() && !cache.contains(&dep.url, &tag.commit.to_string()) {
+                continue;
+            }
+            candidates.push(Candidate {
+                version,
+                tag: tag.name,
+                commit: tag.commit.to_string(),
+            });
+        }
+
+        candidates.sort_by(|a, b| b.version.cmp(&a.version));
+        Ok(candidates)
+    }
+
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        locked: Option<&LockedDependency>,
+        network: bool,
+    ) -> anyhow::Result<LockedDependency> {
+        if network {
+            return self.install(dep, working_dir, locked);
+        }
+        let lock = locked.ok_or_else(|| anyhow::anyhow!("'{}' is not in depo.lock", dep.name))?;
+        self.install_frozen(dep, working_dir, lock)
+    }
+
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>> {
+        if dep.version.is_empty() {
+            return Ok(None);
+        }
+        match Repository::open(dep.install_path(working_dir)) {
+            Ok(repo) => Ok(Some(self.detect_checked_out_version(dep, &repo)?)),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl GitSource {
+    /// Create a Git source using the configured download cache
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration of the running operation
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the download cache cannot be opened.
+    pub fn new(config: &Config) -> anyhow::Result<GitSource> {
+        Ok(GitSource {
+            cache: Cache::open(config)?,
+        })
+    }
+
+    /// Install the dependency to the local filesystem
+    ///
+    /// This method resolves either the locked commit or the version matching the constraint
+    /// from the remote's advertised references, downloads only that commit with a shallow
+    /// fetch, and installs the dependency to the deps directory with version information.
+    /// Remotes that do not support shallow fetches get the full history of that reference,
+    /// and if the reference cannot be fetched at all the whole repository is cloned instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to install
+    /// * `working_dir` - The working directory where dependencies should be installed
+    /// * `locked` - The lockfile entry for this dependency, if one exists
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<LockedDependency>` describing the exact commit that was installed.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The repository cannot be cloned
+    /// - The locked commit or version constraint cannot be resolved
+    /// - File system operations fail
+    fn install(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        locked: Option<&LockedDependency>,
+    ) -> anyhow::Result<LockedDependency> {
+        dep.validate_source()?;
+        let deps_dir = Path::new(working_dir).join("deps");
+        fs::create_dir_all(&deps_dir)?;
+
+        let locked = locked.filter(|l| l.matches(dep));
+        if let Some(lock) = locked {
+            dep.version = lock.version.clone();
+        }
+
+        let temp_path = deps_dir.join(format!("{}@temp", dep.name));
+
+        // An installed copy at another commit is only replaced once the locked one is fetched
+        let mut outdated = None;
+        let final_path = if dep.version.is_empty() {
+            None
+        } else {
+            Some(self.get_final_path(dep, &deps_dir)?)
+        };
+        if let Some(final_path) = final_path.filter(|path| path.exists()) {
+            let repo = Repository::open(&final_path)?;
+            let head = repo.head()?.peel_to_commit()?.id();
+            match locked {
+                Some(lock) if lock.commit != head.to_string() => {
+                    outdated = Some(final_path);
+                }
+                None if dep.is_pinned() && !pin_matches(dep, &repo, head) => {
+                    outdated = Some(final_path);
+                }
+                _ => {
+                    dep.version = self.detect_checked_out_version(dep, &repo)?;
+                    return self.lock_entry(dep, &repo);
+                }
+            }
+        }
+
+        cleanup_path(&temp_path)?;
+        journal::begin(working_dir, &dep.name, &temp_path, &temp_path, Stage::Fetching)?;
+        let installed = (|| {
+            let repo = self.fetch_repo(dep, &temp_path, locked)?;
+            dep.version = self.detect_checked_out_version(dep, &repo)?;
+            let entry = self.lock_entry(dep, &repo)?;
+            drop(repo);
+
+            let versioned_path = deps_dir.join(format!("{}@{}", dep.name, dep.version));
+            journal::set_stage(working_dir, &dep.name, &versioned_path, Stage::Installing)?;
+            if let Some(outdated) = &outdated {
+                transaction::retire(working_dir, outdated)?;
+            }
+            transaction::retire(working_dir, &versioned_path)?;
+            move_to_final_path(&temp_path, &versioned_path)?;
+            Ok(entry)
+        })();
+
+        // A failed install stays in the journal so the next run removes what it left behind
+        match &installed {
+            Ok(_) => journal::finish(working_dir, &dep.name)?,
+            Err(_) => cleanup_path(&temp_path)?,
+        }
+        installed
+    }
+
+    /// Install the dependency without any network access
+    ///
+    /// This method only accepts an already-present `deps/name@version` directory whose
+    /// checked out commit is exactly the locked one, or materialises the locked commit
+    /// from the download cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to install
+    /// * `working_dir` - The working directory where dependencies are installed
+    /// * `locked` - The lockfile entry for this dependency
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<LockedDependency>` describing the installed commit.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if:
+    /// - The locked directory is not present and the locked commit is not cached
+    /// - The directory is checked out at a different commit than the locked one
+    fn install_frozen(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        locked: &LockedDependency,
+    ) -> anyhow::Result<LockedDependency> {
+        dep.version = locked.version.clone();
+        let path = dep.install_path(working_dir);
+
+        if !path.exists() {
+            let cache = &self.cache;
+            if !cache.contains(&locked.url, &locked.commit) {
+                anyhow::bail!(
+                    "'{}' is not installed at '{}' nor cached, and network access is disabled",
+                    dep.name,
+                    path.display()
+                );
+            }
+            cache.materialize(&locked.url, &locked.commit, &path)?;
+        }
+
+        let repo = Repository::open(&path)?;
+        let head = repo.head()?.peel_to_commit()?.id();
+        if head.to_string() != locked.commit {
+            anyhow::bail!(
+                "'{}' is checked out at {} but depo.lock requires {}",
+                dep.name,
+                head,
+                locked.commit
+            );
+        }
+
+        self.lock_entry(dep, &repo)
+    }
+
+    fn detect_checked_out_version(&self, dep: &Dependency, repo: &Repository) -> anyhow::Result<String> {
+        let head_oid = repo.head()?.peel_to_commit()?.id();
+        match self.find_head_tag(dep, repo)? {
+            Some(tag) => Ok(tag),
+            None => Ok(head_oid.to_string()[..7].to_string()),
+        }
+    }
+
+    fn find_head_tag(&self, dep: &Dependency, repo: &Repository) -> anyhow::Result<Option<String>> {
+        let head_oid = repo.head()?.peel_to_commit()?.id();
+
+        if let Some(pinned) = &dep.tag
+            && let Ok(tag_ref) = repo.revparse_single(&format!("refs/tags/{}", pinned))
+            && tag_ref.peel_to_commit()?.id() == head_oid
+        {
+            return Ok(Some(pinned.clone()));
+        }
+
+        let tag_names = repo.tag_names(None)?;
+        let head_tags: Vec<&str> = tag_names
+            .iter()
+            .flatten()
+            .filter(|tag_name| {
+                repo.revparse_single(tag_name)
+                    .and_then(|tag_ref| tag_ref.peel_to_commit())
+                    .is_ok_and(|tag_commit| tag_commit.id() == head_oid)
+            })
+            .collect();
+
+        // Prefer a version tag when a commit carries several tags
+        let version_tag = head_tags
+            .iter()
+            .find(|tag| Version::parse(tag.strip_prefix('v').unwrap_or(tag)).is_ok());
+        Ok(version_tag.or(head_tags.first()).map(|tag| tag.to_string()))
+    }
+
+    fn lock_entry(&self, dep: &Dependency, repo: &Repository) -> anyhow::Result<LockedDependency> {
+        let head_oid = repo.head()?.peel_to_commit()?.id();
+        Ok(LockedDependency {
+            name: dep.name.clone(),
+            url: dep.url.clone(),
+            version: dep.version.clone(),
+            commit: head_oid.to_string(),
+            tag: self.find_head_tag(dep, repo)?,
+            version_constraint: dep.version_constraint.clone(),
+            rev: dep.rev.clone(),
+            path: None,
+            archive: None,
+            sha256: None,
+            requires: Vec::new(),
+        })
+    }
+
+    fn get_final_path(&self, dep: &Dependency, deps_dir: &Path) -> anyhow::Result<PathBuf> {
+        if dep.version.is_empty() {
+            anyhow::bail!("'{}' has no version to install into", dep.name);
+        }
+        Ok(deps_dir.join(format!("{}@{}", dep.name, dep.version)))
+    }
+
+    fn fetch_repo(
+        &self,
+        dep: &Dependency,
+        dest: &Path,
+        locked: Option<&LockedDependency>,
+    ) -> anyhow::Result<Repository> {
+        let cache = &self.cache;
+        if let Some(lock) = locked
+            && cache.contains(&dep.url, &lock.commit)
+        {
+            cache.materialize(&dep.url, &lock.commit, dest)?;
+            return Ok(Repository::open(dest)?);
+        }
+
+        if let Some(lock) = locked
+            && cache.is_offline()
+        {
+            anyhow::bail!(
+                "Offline mode: commit {} of '{}' ({}) is not cached",
+                lock.commit,
+                dep.name,
+                dep.url
+            );
+        }
+
+        let repo = self.download_repo(dep, cache, dest, locked)?;
+        let head = repo.head()?.peel_to_commit()?.id();
+        cache.store(&dep.url, &head.to_string(), dest)?;
+        Ok(repo)
+    }
+
+    fn download_repo(
+        &self,
+        dep: &Dependency,
+        cache: &Cache,
+        dest: &Path,
+        locked: Option<&LockedDependency>,
+    ) -> anyhow::Result<Repository> {
+        let refs = match cache.list_refs(&dep.url) {
+            Ok(refs) => refs,
+            Err(e) if cache.is_offline() => return Err(e),
+            Err(e) => {
+                eprintln!("Listing references of '{}' failed: {:#}", dep.url, e);
+                eprintln!("Falling back to a full clone...");
+                return self.clone_and_checkout(dep, dest, locked);
+            }
+        };
+
+        let target = self.fetch_target(dep, &refs, locked)?;
+        if let Some(commit) = refs.resolve(&target)
+            && cache.contains(&dep.url, &commit.to_string())
+        {
+            cache.materialize(&dep.url, &commit.to_string(), dest)?;
+            return Ok(Repository::open(dest)?);
+        }
+
+        if cache.is_offline() {
+            match refs.resolve(&target) {
+                Some(commit) => anyhow::bail!(
+                    "Offline mode: {} ({}) of '{}' ({}) is not cached",
+                    target,
+                    commit,
+                    dep.name,
+                    dep.url
+                ),
+                None => anyhow::bail!(
+                    "Offline mode: {} of '{}' ({}) is not known to the cache",
+                    target,
+                    dep.name,
+                    dep.url
+                ),
+            }
+        }
+
+        if let Ok(repo) = git::fetch_ref(&dep.url, &target, &refs, dest, Some(1)) {
+            return Ok(repo);
+        }
+
+        // Some transports (e.g. local paths) cannot fetch shallowly
+        cleanup_path(dest)?;
+        match git::fetch_ref(&dep.url, &target, &refs, dest, None) {
+            Ok(repo) => Ok(repo),
+            Err(e) => {
+                eprintln!("Fetching {} of '{}' failed: {:#}", target, dep.url, e);
+                eprintln!("Falling back to a full clone...");
+                cleanup_path(dest)?;
+                self.clone_and_checkout(dep, dest, locked)
+            }
+        }
+    }
+
+    fn fetch_target(
+        &self,
+        dep: &Dependency,
+        refs: &RemoteRefs,
+        locked: Option<&LockedDependency>,
+    ) -> anyhow::Result<FetchTarget> {
+        if let Some(lock) = locked {
+            let commit = Oid::from_str(&lock.commit)?;
+            return Ok(match &lock.tag {
+                Some(tag) if refs.tag(tag) == Some(commit) => FetchTarget::Tag(tag.clone()),
+                _ => FetchTarget::Commit(commit),
+            });
+        }
+
+        if let Some(rev) = &dep.rev {
+            return self.rev_target(dep, refs, rev);
+        }
+
+        if let Some(tag) = &dep.tag {
+            if refs.tag(tag).is_none() {
+                anyhow::bail!("Tag '{}' of '{}' not found in '{}'", tag, dep.name, dep.url);
+            }
+            return Ok(FetchTarget::Tag(tag.clone()));
+        }
+
+        let Some(constraint) = &dep.version_constraint else {
+            return Ok(FetchTarget::Head);
+        };
+
+        if let Ok(version_req) = VersionReq::parse(constraint) {
+            let tags = get_matching_tags(refs.tags.iter().map(|t| t.name.as_str()), &version_req);
+            return match tags.first() {
+                Some(tag) => Ok(FetchTarget::Tag(tag.clone())),
+                None => Err(no_matching_version(&dep.name, constraint)),
+            };
+        }
+
+        if refs.branch(constraint).is_some() {
+            return Ok(FetchTarget::Branch(constraint.clone()));
+        }
+
+        if refs.tag(constraint).is_some() {
+            return Ok(FetchTarget::Tag(constraint.clone()));
+        }
+
+        anyhow::bail!(
+            "Could not resolve constraint '{}'. No matching version, branch, or tag found.",
+            constraint
+        );
+    }
+
+    fn rev_target(&self, dep: &Dependency, refs: &RemoteRefs, rev: &str) -> anyhow::Result<FetchTarget> {
+        if rev.len() == 40
+            && let Ok(commit) = Oid::from_str(rev)
+        {
+            return Ok(FetchTarget::Commit(commit));
+        }
+
+        if refs.tag(rev).is_some() {
+            return Ok(FetchTarget::Tag(rev.to_string()));
+        }
+
+        if refs.branch(rev).is_some() {
+            return Ok(FetchTarget::Branch(rev.to_string()));
+        }
+
+        // Abbreviated SHAs can only be resolved against advertised references
+        let advertised = refs
+            .tags
+            .iter()
+            .map(|t| t.commit)
+            .chain(refs.branches.iter().map(|(_, oid)| *oid))
+            .chain(refs.head);
+        for commit in advertised {
+            if rev.len() >= 7 && commit.to_string().starts_with(&rev.to_lowercase()) {
+                return Ok(FetchTarget::Commit(commit));
+            }
+        }
+
+        anyhow::bail!(
+            "Could not resolve rev '{}' of '{}'. Use a full commit SHA, a tag or a branch name.",
+            rev,
+            dep.name
+        );
+    }
+
+    fn clone_and_checkout(
+        &self,
+        dep: &Dependency,
+        dest: &Path,
+        locked: Option<&LockedDependency>,
+    ) -> anyhow::Result<Repository> {
+        let mut repo = self.clone_repo(dep, dest)?;
+        match locked {
+            Some(lock) => git::checkout(&repo, Oid::from_str(&lock.commit)?)
+                .with_context(|| format!("Failed to check out locked commit {} of '{}'", lock.commit, dep.name))?,
+            None => self.apply_version_constraint(dep, &mut repo)?,
+        }
+        Ok(repo)
+    }
+
+    fn clone_repo(&self, dep: &Dependency, dest: &Path) -> anyhow::Result<Repository> {
+        Repository::clone(&dep.url, dest)
+            .map_err(|e| anyhow::anyhow!("Failed to clone '{}' into '{}': {}", dep.url, dest.display(), e))
+    }
+
+    fn apply_version_constraint(&self, dep: &Dependency, repo: &mut Repository) -> anyhow::Result<()> {
+        if let Some(rev) = &dep.rev {
+            let commit = repo
+                .revparse_single(rev)
+                .or_else(|_| repo.revparse_single(&format!("origin/{}", rev)))
+                .with_context(|| format!("Could not resolve rev '{}' of '{}'", rev, dep.name))?
+                .peel_to_commit()?
+                .id();
+            return git::checkout(repo, commit);
+        }
+
+        if let Some(tag) = &dep.tag {
+            let commit = repo
+                .revparse_single(&format!("refs/tags/{}", tag))
+                .with_context(|| format!("Tag '{}' of '{}' not found", tag, dep.name))?
+                .peel_to_commit()?
+                .id();
+            return git::checkout(repo, commit);
+        }
+
+        if let Some(ref constraint) = dep.version_constraint {
+            self.resolve_and_checkout(repo, &dep.name, constraint)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_and_checkout(
+        &self,
+        repo: &mut Repository,
+        name: &str,
+        constraint: &str,
+    ) -> anyhow::Result<()> {
+        if let Ok(version_req) = VersionReq::parse(constraint) {
+            let tags = get_matching_tags(repo.tag_names(None)?.iter().flatten(), &version_req);
+            if let Some(tag) = tags.first() {
+                let commit = repo.revparse_single(tag)?.peel_to_commit()?.id();
+                return git::checkout(repo, commit);
+            } else {
+                return Err(no_matching_version(name, constraint));
+            }
+        }
+
+        if let Ok(branch_ref) = repo.find_branch(constraint, git2::BranchType::Local) {
+            let commit = branch_ref.get().peel_to_commit()?.id();
+            return git::checkout(repo, commit);
+        }
+
+        if let Ok(branch_ref) =
+            repo.find_branch(&format!("origin/{}", constraint), git2::BranchType::Remote)
+        {
+            let commit = branch_ref.get().peel_to_commit()?.id();
+            return git::checkout(repo, commit);
+        }
+
+        anyhow::bail!(
+            "Could not resolve constraint '{}'. No matching version, branch, or tag found.",
+            constraint
+        );
+    }
+}
+
+/// Source for Git dependencies assigned to a registry
+///
+/// Only versions published in the registry are offered and installed, in offline mode
+/// only those present in the download cache. Without a lock or pin the newest published
+/// version matching the constraint is chosen, and the installed commit must be a
+/// published one whose source checksum matches the registry's.
+pub struct RegistrySource {
+    registries: Vec<Registry>,
+    git: GitSource,
+}
+
+impl RegistrySource {
+    /// Create a source for the given registries
+    ///
+    /// # Arguments
+    ///
+    /// * `registries` - The opened registries of the package
+    /// * `config` - The configuration of the running operation
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the download cache cannot be opened.
+    pub fn new(registries: Vec<Registry>, config: &Config) -> anyhow::Result<RegistrySource> {
+        Ok(RegistrySource {
+            registries,
+            git: GitSource::new(config)?,
+        })
+    }
+}
+
+impl Source for RegistrySource {
+    fn handles(&self, dep: &Dependency) -> bool {
+        dep.registry.is_some() && self.git.handles(dep)
+    }
+
+    fn versions(&self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        let Some((_, package)) = registry::find_package(&self.registries, dep)? else {
+            return self.git.versions(dep);
+        };
+        let cache = &self.git.cache;
+        let url = if dep.url.is_empty() { &package.url } else { &dep.url };
+        Ok(package
+            .sorted_versions()
+            .into_iter()
+            .filter(|(_, v)| !cache.is_offline() || cache.contains(url, &v.commit))
+            .map(|(version, v)| Candidate {
+                version,
+                tag: v.tag.clone(),
+                commit: v.commit.clone(),
+            })
+            .collect())
+    }
+
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        locked: Option<&LockedDependency>,
+        network: bool,
+    ) -> anyhow::Result<LockedDependency> {
+        let Some((registry, package)) = registry::find_package(&self.registries, dep)? else {
+            return self.git.fetch(dep, working_dir, locked, network);
+        };
+        if dep.url.is_empty() {
+            dep.url = package.url.clone();
+        }
+
+        let locked = locked.filter(|l| l.matches(dep));
+        let newest = match locked {
+            None if !dep.is_pinned() => {
+                let newest = package
+                    .newest_matching(dep.version_constraint.as_deref())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No version of '{}' in registry '{}' matches '{}'",
+                            dep.name,
+                            registry.name,
+                            dep.version_constraint.as_deref().unwrap_or("*")
+                        )
+                    })?;
+                Some(LockedDependency {
+                    name: dep.name.clone(),
+                    url: dep.url.clone(),
+                    version: newest.tag.clone(),
+                    commit: newest.commit.clone(),
+                    tag: Some(newest.tag.clone()),
+                    version_constraint: dep.version_constraint.clone(),
+                    rev: None,
+                    path: None,
+                    archive: None,
+                    sha256: None,
+                    requires: Vec::new(),
+                })
+            }
+            _ => None,
+        };
+
+        let entry = self.git.fetch(dep, working_dir, locked.or(newest.as_ref()), network)?;
+        let published = package.find_version(&entry.commit).ok_or_else(|| {
+            anyhow::anyhow!(
+                "'{}' resolved to commit {}, which is not a version published in registry '{}'",
+                dep.name,
+                entry.commit,
+                registry.name
+            )
+        })?;
+
+        if let Some(expected) = &published.sha256 {
+            let repo = Repository::open(dep.install_path(working_dir))?;
+            let actual = registry::source_checksum(&repo, Oid::from_str(&entry.commit)?)?;
+            if !actual.eq_ignore_ascii_case(expected) {
+                anyhow::bail!(
+                    "Checksum mismatch for '{}' {}: registry '{}' lists sha256 {}, got {}",
+                    dep.name,
+                    published.version,
+                    registry.name,
+                    expected,
+                    actual
+                );
+            }
+        }
+
+        Ok(entry)
+    }
+
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>> {
+        self.git.installed_version(dep, working_dir)
+    }
+}
+
+/// Source for dependencies in a local directory
+///
+/// Local dependencies are never cloned, copied or moved; their sources are built and
+/// referenced directly from the configured directory.
+pub struct PathSource;
+
+impl Source for PathSource {
+    fn handles(&self, dep: &Dependency) -> bool {
+        dep.path.is_some()
+    }
+
+    fn versions(&self, _dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        Ok(Vec::new())
+    }
+
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        _locked: Option<&LockedDependency>,
+        _network: bool,
+    ) -> anyhow::Result<LockedDependency> {
+        dep.validate_source()?;
+        let path = dep.source_path(working_dir);
+        if !path.is_dir() {
+            anyhow::bail!(
+                "Local dependency '{}' not found at '{}'",
+                dep.name,
+                path.display()
+            );
+        }
+
+        dep.version = LOCAL_VERSION.to_string();
+        Ok(LockedDependency {
+            name: dep.name.clone(),
+            url: dep.url.clone(),
+            version: dep.version.clone(),
+            commit: String::new(),
+            tag: None,
+            version_constraint: dep.version_constraint.clone(),
+            rev: None,
+            path: dep.path.clone(),
+            archive: None,
+            sha256: None,
+            requires: Vec::new(),
+        })
+    }
+
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>> {
+        Ok(dep
+            .source_path(working_dir)
+            .is_dir()
+            .then(|| LOCAL_VERSION.to_string()))
+    }
+}
+
+/// Source for dependencies published as release archives
+///
+/// The archive is downloaded, verified against its SHA-256 checksum and unpacked into
+/// `deps/name@version`, where the version is taken from the archive's file name (e.g.
+/// `fmt-10.2.1.tar.gz`) or, if it has none, from the checksum. An installed archive
+/// with the same checksum is reused.
+pub struct ArchiveSource {
+    offline: bool,
+    user_agent: String,
+}
+
+impl ArchiveSource {
+    /// Create an archive source
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configuration supplying offline mode and the `User-Agent`
+    pub fn new(config: &Config) -> ArchiveSource {
+        ArchiveSource {
+            offline: config.offline,
+            user_agent: config.user_agent.clone(),
+        }
+    }
+}
+
+impl Source for ArchiveSource {
+    fn handles(&self, dep: &Dependency) -> bool {
+        dep.archive.is_some()
+    }
+
+    fn versions(&self, _dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        Ok(Vec::new())
+    }
+
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        _locked: Option<&LockedDependency>,
+        network: bool,
+    ) -> anyhow::Result<LockedDependency> {
+        dep.validate_source()?;
+        let (Some(url), Some(checksum)) = (dep.archive.clone(), dep.sha256.clone()) else {
+            anyhow::bail!("'{}' is not an archive dependency", dep.name);
+        };
+        let format = ArchiveFormat::from_url(&url)
+            .ok_or_else(|| anyhow::anyhow!("Unsupported archive format for '{}': {}", dep.name, url))?;
+
+        dep.version = archive::version(&url, &checksum);
+        let path = dep.install_path(working_dir);
+        let marker = path.join(ARCHIVE_MARKER);
+        let installed = fs::read_to_string(&marker)
+            .is_ok_and(|installed| installed.trim().eq_ignore_ascii_case(checksum.trim()));
+
+        if !installed {
+            if !network {
+                anyhow::bail!(
+                    "'{}' is not installed at '{}', and network access is disabled",
+                    dep.name,
+                    path.display()
+                );
+            }
+            if !url.starts_with("file://") && self.offline {
+                anyhow::bail!("Offline mode: archive '{}' of '{}' cannot be downloaded", url, dep.name);
+            }
+
+            let data = archive::download(&url, &self.user_agent)?;
+            archive::verify(&data, &checksum)
+                .with_context(|| format!("Archive of '{}' from '{}' failed verification", dep.name, url))?;
+            fs::create_dir_all(Path::new(working_dir).join("deps"))?;
+            let staging = archive::staging_path(&path);
+            journal::begin(working_dir, &dep.name, &staging, &path, Stage::Installing)?;
+            transaction::retire(working_dir, &path)?;
+            archive::unpack(&data, format, &path)?;
+            fsutil::write_atomic(&marker, &checksum)?;
+            journal::finish(working_dir, &dep.name)?;
+        }
+
+        Ok(LockedDependency {
+            name: dep.name.clone(),
+            url: String::new(),
+            version: dep.version.clone(),
+            commit: String::new(),
+            tag: None,
+            version_constraint: None,
+            rev: None,
+            path: None,
+            archive: Some(url),
+            sha256: Some(checksum),
+            requires: Vec::new(),
+        })
+    }
+
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>> {
+        if dep.version.is_empty() {
+            return Ok(None);
+        }
+        let marker = dep.install_path(working_dir).join(ARCHIVE_MARKER);
+        Ok(marker.is_file().then(|| dep.version.clone()))
+    }
+}
+
+fn cleanup_path(path: &Path) -> anyhow::Result<()> {
+    if path.exists() {
+        fs::remove_dir_all(path).map_err(|e| {
+            anyhow::anyhow!("Failed to remove old temp dir '{}': {}", path.display(), e)
+        })?;
+    }
+    Ok(())
+}
+
+fn move_to_final_path(src: &Path, dst: &Path) -> anyhow::Result<()> {
+    if !src.exists() {
+        anyhow::bail!("Temp path '{}' not found", src.display());
+    }
+
+    // On Windows the handles of a just dropped repository can linger for a moment
+    let mut renamed = fs::rename(src, dst);
+    for _ in 0..5 {
+        if renamed.is_ok() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        renamed = fs::rename(src, dst);
+    }
+
+    if let Err(e) = renamed {
+        eprintln!(
+            "Rename failed: {} → {}: {}",
+            src.display(),
+            dst.display(),
+            e
+        );
+        eprintln!("Falling back to recursive copy...");
+
+        fsutil::copy_dir_all(src, dst)?;
+        fs::remove_dir_all(src)?;
+    }
+    Ok(())
+}
+
+fn get_matching_tags<'a>(
+    tag_names: impl IntoIterator<Item = &'a str>,
+    version_req: &VersionReq,
+) -> Vec<String> {
+    let mut tags = Vec::new();
+
+    for tag_name in tag_names {
+        let version_str = tag_name.strip_prefix('v').unwrap_or(tag_name);
+
+        if let Ok(version) = Version::parse(version_str)
+            && version_req.matches(&version)
+        {
+            tags.push(tag_name.to_string());
+        }
+    }
+
+    tags.sort_by(|a, b| {
+        let version_a = Version::parse(a.strip_prefix('v').unwrap_or(a))
+            .unwrap_or_else(|_| Version::new(0, 0, 0));
+        let version_b = Version::parse(b.strip_prefix('v').unwrap_or(b))
+            .unwrap_or_else(|_| Version::new(0, 0, 0));
+        version_b.cmp(&version_a)
+    });
+
+    tags
+}
+
+/// Whether the `rev` or `tag` a dependency is pinned to resolves to `head` in a checkout
+fn pin_matches(dep: &Dependency, repo: &Repository, head: Oid) -> bool {
+    dep.rev
+        .as_ref()
+        .or(dep.tag.as_ref())
+        .and_then(|pin| repo.revparse_single(pin).ok())
+        .and_then(|object| object.peel_to_commit().ok())
+        .is_some_and(|commit| commit.id() == head)
+}
+
+/// The error for a constraint that none of a dependency's version tags satisfies
+fn no_matching_version(name: &str, constraint: &str) -> anyhow::Error {
+    DepoError::NoMatchingVersion {
+        name: name.to_string(),
+        message: format!(
+            "No version of '{}' matching constraint '{}' found",
+            name, constraint
+        ),
+    }
+    .into()
+}
@@ -0,0 +1,274 @@
+//! # Transactions
+//!
+//! This module makes operations that change several dependencies at once all-or-nothing.
+//! A `Transaction` records the package file, the lockfile and the files and directories
+//! in `deps/` when it begins. While it is open, installed dependencies that are replaced
+//! or removed are not deleted but retired into the transaction with `retire`, so new
+//! versions are staged next to the old ones. Committing deletes the retired directories;
+//! rolling back removes everything the operation added to `deps/`, moves the retired
+//! directories back and restores the recorded files.
+//!
+//! The state of an open transaction lives in `deps/.depo-transaction`, so a transaction
+//! interrupted by a crash or Ctrl-C is rolled back when the next one begins.
+
+use crate::fsutil;
+use crate::journal;
+use crate::lock::ProjectLock;
+use crate::serialization::ManifestFormat;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TRANSACTION_DIR: &str = ".depo-transaction";
+const STATE_FILE: &str = "transaction.yaml";
+
+/// A file recorded when the transaction began
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordedFile {
+    /// The recorded file
+    path: PathBuf,
+    /// The name of its copy in the transaction directory, `None` if it did not exist
+    backup: Option<String>,
+}
+
+/// What a transaction needs to roll back
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    files: Vec<RecordedFile>,
+    /// The names of the entries of `deps/` when the transaction began
+    entries: Vec<String>,
+}
+
+/// An open transaction over the dependencies of a package
+///
+/// A transaction that is dropped without being committed is rolled back.
+pub struct Transaction {
+    working_dir: String,
+    finished: bool,
+}
+
+fn deps_dir(working_dir: &str) -> PathBuf {
+    Path::new(working_dir).join("deps")
+}
+
+fn transaction_dir(working_dir: &str) -> PathBuf {
+    deps_dir(working_dir).join(TRANSACTION_DIR)
+}
+
+/// Whether an entry of `deps/` is bookkeeping rather than part of the installed state
+fn is_internal(name: &str) -> bool {
+    name.starts_with(".depo-")
+}
+
+impl Transaction {
+    /// Begin a transaction
+    ///
+    /// If an earlier transaction was interrupted, it is rolled back first.
+    ///
+    /// # Arguments
+    ///
+    /// * `working_dir` - The working directory of the package
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Transaction>` with the open transaction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an interrupted transaction cannot be rolled
+    /// back or the current state cannot be recorded.
+    pub fn begin(working_dir: &str) -> anyhow::Result<Transaction> {
+        recover(working_dir)?;
+
+        let dir = transaction_dir(working_dir);
+        let deps = deps_dir(working_dir);
+        let mut paths: Vec<PathBuf> = ManifestFormat::ALL
+            .iter()
+            .map(|format| Path::new(working_dir).join(format.file_name()))
+            .collect();
+        paths.push(Path::new(working_dir).join("depo.lock"));
+        let mut entries = Vec::new();
+        if deps.is_dir() {
+            for entry in fs::read_dir(&deps)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if is_internal(&name) {
+                    continue;
+                }
+                if entry.file_type()?.is_file() {
+                    paths.push(entry.path());
+                }
+                entries.push(name);
+            }
+        }
+
+        fs::create_dir_all(dir.join("trash"))?;
+        let mut files = Vec::new();
+        for (index, path) in paths.into_iter().enumerate() {
+            let backup = match fs::read(&path) {
+                Ok(content) => {
+                    let backup = format!("file-{}", index);
+                    fs::write(dir.join(&backup), content)?;
+                    Some(backup)
+                }
+                Err(_) => None,
+            };
+            files.push(RecordedFile { path, backup });
+        }
+        fsutil::write_atomic(
+            &dir.join(STATE_FILE),
+            serde_yaml::to_string(&State { files, entries })?,
+        )?;
+
+        Ok(Transaction {
+            working_dir: working_dir.to_string(),
+            finished: false,
+        })
+    }
+
+    /// Commit the transaction, deleting the directories retired during it
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the commit.
+    pub fn commit(mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        fs::remove_dir_all(transaction_dir(&self.working_dir))?;
+        Ok(())
+    }
+
+    /// Roll the transaction back, restoring `deps/`, the package file and the lockfile
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the rollback.
+    pub fn rollback(mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        rollback(&self.working_dir)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished
+            && let Err(e) = rollback(&self.working_dir)
+        {
+            eprintln!("Failed to roll back: {}", e);
+        }
+    }
+}
+
+/// Roll back a transaction that was interrupted
+///
+/// Front ends call this before loading the package, as an interrupted transaction can
+/// leave the package file in an intermediate state.
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+///
+/// # Returns
+///
+/// Returns a `Result<bool>` that is `true` if an interrupted transaction was rolled back.
+///
+/// # Errors
+///
+/// This function will return an error if the interrupted transaction cannot be rolled back.
+pub fn recover(working_dir: &str) -> anyhow::Result<bool> {
+    if !transaction_dir(working_dir).exists() {
+        return Ok(false);
+    }
+    // The transaction may belong to another process that is still running
+    let _lock = ProjectLock::acquire(working_dir)?;
+    if !transaction_dir(working_dir).exists() {
+        return Ok(false);
+    }
+    rollback(working_dir)?;
+    println!("Rolled back an operation that was interrupted");
+    Ok(true)
+}
+
+/// Roll back the transaction recorded in the working directory
+fn rollback(working_dir: &str) -> anyhow::Result<()> {
+    let deps = deps_dir(working_dir);
+    let dir = transaction_dir(working_dir);
+    let Ok(content) = fs::read_to_string(dir.join(STATE_FILE)) else {
+        // The transaction was interrupted before it recorded anything
+        fs::remove_dir_all(&dir)?;
+        return Ok(());
+    };
+    let state: State = serde_yaml::from_str(&content)?;
+
+    for entry in fs::read_dir(&deps)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_internal(&name) || state.entries.contains(&name) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    for entry in fs::read_dir(dir.join("trash"))? {
+        let entry = entry?;
+        let target = deps.join(entry.file_name());
+        if target.exists() {
+            fs::remove_dir_all(&target)?;
+        }
+        fs::rename(entry.path(), target)?;
+    }
+
+    for file in &state.files {
+        match &file.backup {
+            Some(backup) => fsutil::write_atomic(&file.path, fs::read(dir.join(backup))?)?,
+            None if file.path.exists() => fs::remove_file(&file.path)?,
+            None => {}
+        }
+    }
+
+    // Installs the journal still lists belonged to the rolled back operation, whose
+    // leftovers are gone now; recovering them again would remove restored directories
+    journal::clear(working_dir)?;
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+/// Remove an installed dependency directory in a way that can be rolled back
+///
+/// Inside a transaction the directory is moved into the transaction and only deleted
+/// when the transaction commits; outside of one it is deleted right away.
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory of the package
+/// * `path` - The directory in `deps/` to remove
+///
+/// # Returns
+///
+/// Returns a `Result<()>` indicating success or failure of the removal.
+pub fn retire(working_dir: &str, path: &Path) -> anyhow::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    // Only directories that existed when the transaction began are restored on rollback,
+    // and only their first retired copy
+    let dir = transaction_dir(working_dir);
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+    let recorded = match (fs::read_to_string(dir.join(STATE_FILE)), &name) {
+        (Ok(content), Some(name)) if path.parent() == Some(deps_dir(working_dir).as_path()) => {
+            let state: State = serde_yaml::from_str(&content)?;
+            state.entries.contains(name)
+        }
+        _ => false,
+    };
+
+    match name.map(|name| dir.join("trash").join(name)) {
+        Some(retired) if recorded && !retired.exists() => fs::rename(path, retired)?,
+        _ => fs::remove_dir_all(path)?,
+    }
+    Ok(())
+}
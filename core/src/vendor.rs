@@ -0,0 +1,220 @@
+//! # Vendoring
+//!
+//! This module copies the sources of installed dependencies into the project so they
+//! can be committed alongside it. Each dependency is written to `<vendor dir>/name@version`
+//! without its `.git` directories or the `build/` folder created by `CMake::build_dependency`,
+//! and `vendor.yaml` records the URL and commit every copy was taken from.
+//!
+//! Once a project is vendored, `Package::install` and `Package::build_order` use the
+//! vendored copies in place of `deps/name@version` for every dependency whose locked
+//! commit matches the vendored one.
+
+use crate::dependency::Dependency;
+use crate::fsutil;
+use crate::lockfile::{Lockfile, LockedDependency};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The vendor directory used when `package.yaml` does not configure one
+pub const DEFAULT_VENDOR_DIR: &str = "vendor";
+
+const MANIFEST_FILE: &str = "vendor.yaml";
+
+/// The origin of every vendored dependency
+///
+/// The manifest is stored as `vendor.yaml` inside the vendor directory.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VendorManifest {
+    /// Vendored dependencies with the URL and commit they were copied from
+    pub dependencies: Vec<LockedDependency>,
+}
+
+impl VendorManifest {
+    /// Load the manifest of a vendor directory
+    ///
+    /// # Arguments
+    ///
+    /// * `vendor_dir` - The vendor directory
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` if the directory has not been vendored into.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the manifest exists but cannot be parsed.
+    pub fn load(vendor_dir: &Path) -> anyhow::Result<Option<VendorManifest>> {
+        let path = vendor_dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(Some(serde_yaml::from_str(&content)?))
+    }
+
+    /// Save the manifest into a vendor directory
+    ///
+    /// # Arguments
+    ///
+    /// * `vendor_dir` - The vendor directory
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<()>` indicating success or failure of the save.
+    pub fn save(&self, vendor_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(vendor_dir)?;
+        fsutil::write_atomic(&vendor_dir.join(MANIFEST_FILE), serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Find the vendored copy of a dependency
+    ///
+    /// # Arguments
+    ///
+    /// * `dep` - The dependency to look up
+    /// * `locked` - The commit the dependency is locked or resolved to, if known
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&LockedDependency)` if the dependency is vendored from the same URL
+    /// and, when a lock is given, at the same commit; `None` otherwise.
+    pub fn find(
+        &self,
+        dep: &Dependency,
+        locked: Option<&LockedDependency>,
+    ) -> Option<&LockedDependency> {
+        self.dependencies.iter().find(|v| {
+            v.name == dep.name
+                && v.url == dep.url
+                && v.sha256 == dep.sha256
+                && locked.is_none_or(|l| l.commit == v.commit)
+        })
+    }
+}
+
+/// Get the directory a vendored dependency is stored in
+///
+/// # Arguments
+///
+/// * `vendor_dir` - The vendor directory
+/// * `entry` - The vendored dependency
+///
+/// # Returns
+///
+/// Returns the `<vendor dir>/name@version` path.
+pub fn entry_path(vendor_dir: &Path, entry: &LockedDependency) -> PathBuf {
+    vendor_dir.join(format!("{}@{}", entry.name, entry.version))
+}
+
+/// Copy every locked dependency from `deps/` into a vendor directory
+///
+/// Local path dependencies are skipped. Dependencies already vendored at their locked
+/// commit are left untouched, and
+/// vendored copies of dependencies that are no longer locked are removed.
+///
+/// # Arguments
+///
+/// * `working_dir` - The working directory where dependencies are installed
+/// * `vendor_dir` - The vendor directory to write to
+/// * `lockfile` - The lockfile listing the dependencies to vendor
+///
+/// # Returns
+///
+/// Returns a `Result<VendorManifest>` describing the vendored dependencies.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A locked dependency is neither vendored nor installed in `deps/`
+/// - An installed dependency is checked out at a different commit than the locked one
+/// - File system operations fail
+pub fn vendor(
+    working_dir: &str,
+    vendor_dir: &Path,
+    lockfile: &Lockfile,
+) -> anyhow::Result<VendorManifest> {
+    let previous = VendorManifest::load(vendor_dir)?.unwrap_or_default();
+    let deps_dir = Path::new(working_dir).join("deps");
+    let mut manifest = VendorManifest::default();
+
+    for lock in &lockfile.dependencies {
+        if lock.path.is_some() {
+            // Local path dependencies are already part of the developer's tree
+            continue;
+        }
+
+        let dst = entry_path(vendor_dir, lock);
+        let up_to_date = previous
+            .dependencies
+            .iter()
+            .any(|v| {
+                v.name == lock.name
+                    && v.url == lock.url
+                    && v.commit == lock.commit
+                    && v.sha256 == lock.sha256
+            });
+
+        if !(up_to_date && dst.exists()) {
+            let src = deps_dir.join(format!("{}@{}", lock.name, lock.version));
+            copy_sources(lock, &src, &dst)?;
+            println!("Vendored '{}' at {}", lock.name, lock.commit);
+        }
+        manifest.dependencies.push(lock.clone());
+    }
+
+    for stale in &previous.dependencies {
+        let still_vendored = manifest
+            .dependencies
+            .iter()
+            .any(|v| v.name == stale.name && v.version == stale.version);
+        let path = entry_path(vendor_dir, stale);
+        if !still_vendored && path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+    }
+
+    manifest.save(vendor_dir)?;
+    Ok(manifest)
+}
+
+fn copy_sources(lock: &LockedDependency, src: &Path, dst: &Path) -> anyhow::Result<()> {
+    if !src.exists() {
+        anyhow::bail!(
+            "'{}' is not installed at '{}'; run `install` first",
+            lock.name,
+            src.display()
+        );
+    }
+
+    if let Ok(repo) = Repository::open(src) {
+        let head = repo.head()?.peel_to_commit()?.id();
+        if head.to_string() != lock.commit {
+            anyhow::bail!(
+                "'{}' is checked out at {} but depo.lock requires {}; run `install` first",
+                lock.name,
+                head,
+                lock.commit
+            );
+        }
+    }
+
+    let temp = dst.with_file_name(format!("{}@{}.tmp", lock.name, lock.version));
+    if temp.exists() {
+        fs::remove_dir_all(&temp)?;
+    }
+    fsutil::copy_dir_filtered(src, &temp, &|relative| {
+        relative != Path::new("build")
+            && !relative
+                .components()
+                .any(|c| c == Component::Normal(OsStr::new(".git")))
+    })?;
+
+    if dst.exists() {
+        fs::remove_dir_all(dst)?;
+    }
+    fs::rename(&temp, dst)?;
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use depo_core::archive;
+
+const CHECKSUM: &str = "312151a2d13c8327f5c9c586ac6cf7cddc1658e8f53edae0ec56509c8fa516c9";
+
+fn version(file: &str) -> String {
+    archive::version(&format!("https://example.com/releases/{}?download=1", file), CHECKSUM)
+}
+
+#[test]
+fn versions_are_taken_from_common_archive_names() {
+    assert_eq!(version("fmt-10.2.1.tar.gz"), "10.2.1");
+    assert_eq!(version("boost_1_84_0.tar.gz"), "1.84.0");
+    assert_eq!(version("curl-8_6_0.tar.xz"), "8.6.0");
+    assert_eq!(version("v1.0.0.zip"), "v1.0.0");
+    assert_eq!(version("json-v3.11.3.tgz"), "v3.11.3");
+    assert_eq!(version("3.4.0.tar.gz"), "3.4.0");
+    assert_eq!(version("SDL2-2.30.0.zip"), "2.30.0");
+    assert_eq!(version("sdl2_2_30_0.zip"), "2.30.0");
+    assert_eq!(version("sqlite-autoconf-3450100.tar.gz"), "3450100");
+}
+
+#[test]
+fn names_without_a_version_use_the_checksum() {
+    assert_eq!(version("name-without-version.tar.gz"), "312151a2d13c");
+    assert_eq!(version("fmt-11.0.0-rc1.zip"), "312151a2d13c");
+    assert_eq!(version("source.zip"), "312151a2d13c");
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+mod common;
+
+use depo_core::build::{BuildSystem, CMake};
+use depo_core::dependency::Dependency;
+use depo_core::error::{BuildStage, DepoError};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::OnceLock;
+use tempfile::TempDir;
+
+static FAKE_CMAKE: OnceLock<TempDir> = OnceLock::new();
+
+/// Put a `cmake` on the `PATH` that prints to both streams and fails `--build` when the
+/// source directory contains a file named `FAIL`
+fn fake_cmake() {
+    FAKE_CMAKE.get_or_init(|| {
+        let bin = TempDir::new().unwrap();
+        let script = bin.path().join("cmake");
+        fs::write(
+            &script,
+            "#!/bin/sh\n\
+             if [ \"$1\" = --build ]; then\n\
+             \x20 echo compiling\n\
+             \x20 if [ -e ../FAIL ]; then echo 'error: boom' >&2; exit 2; fi\n\
+             else\n\
+             \x20 echo \"configuring $1\"\n\
+             fi\n",
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let path = std::env::var("PATH").unwrap_or_default();
+        // SAFETY: `get_or_init` blocks every other test until the variable is set
+        unsafe { std::env::set_var("PATH", format!("{}:{}", bin.path().display(), path)) };
+        bin
+    });
+}
+
+fn local_dependency(working_dir: &std::path::Path, name: &str) -> Dependency {
+    fs::create_dir_all(working_dir.join(name)).unwrap();
+    let mut dep = Dependency::new(name, name, "", None, "");
+    dep.path = Some(name.to_string());
+    dep
+}
+
+#[test]
+fn failed_steps_return_their_output() {
+    let (dir, _) = common::project();
+    fake_cmake();
+    let dep = local_dependency(dir.path(), "liba");
+    fs::write(dir.path().join("liba/CMakeLists.txt"), "").unwrap();
+    fs::write(dir.path().join("liba/FAIL"), "").unwrap();
+
+    let error = CMake::build_dependency(&dep, common::path(&dir)).unwrap_err();
+    let DepoError::BuildFailed { name, stage, log } = error else {
+        panic!("unexpected error: {:?}", error);
+    };
+    assert_eq!((name.as_str(), stage), ("liba", BuildStage::Build));
+    assert!(log.contains("compiling\n"), "{}", log);
+    assert!(log.contains("error: boom\n"), "{}", log);
+    assert!(!log.contains("configuring"), "{}", log);
+}
+
+#[test]
+fn successful_builds_and_missing_build_files() {
+    let (dir, _) = common::project();
+    fake_cmake();
+    let dep = local_dependency(dir.path(), "liba");
+    let working_dir = common::path(&dir);
+
+    let error = CMake::build_dependency(&dep, working_dir).unwrap_err();
+    let DepoError::BuildFailed { stage, log, .. } = error else {
+        panic!("unexpected error: {:?}", error);
+    };
+    assert_eq!(stage, BuildStage::Configure);
+    assert!(log.starts_with("Build file not found"), "{}", log);
+
+    fs::write(dir.path().join("liba/CMakeLists.txt"), "").unwrap();
+    CMake::build_dependency(&dep, working_dir).unwrap();
+    assert!(dir.path().join("liba/build").is_dir());
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use depo_core::cache::parse_size;
+use depo_core::config::Config;
+use depo_core::dependency::Dependency;
+use depo_core::package::{InstallMode, Package};
+use depo_core::serialization;
+use std::fs;
+use tempfile::TempDir;
+
+/// The configuration with offline mode switched on, as the CLI's `--offline` does
+fn offline() -> Config {
+    Config {
+        offline: true,
+        ..common::config()
+    }
+}
+
+#[test]
+fn sizes_are_parsed_with_binary_suffixes() {
+    assert_eq!(parse_size("1048576").unwrap(), 1048576);
+    assert_eq!(parse_size("512k").unwrap(), 512 * 1024);
+    assert_eq!(parse_size(" 500M ").unwrap(), 500 * 1024 * 1024);
+    assert_eq!(parse_size("10G").unwrap(), 10 * 1024 * 1024 * 1024);
+    assert!(parse_size("ten").is_err());
+}
+
+#[test]
+fn sizes_that_overflow_are_rejected() {
+    let error = parse_size("99999999999999G").unwrap_err();
+    assert_eq!(error.to_string(), "Size too large: 99999999999999G");
+    assert_eq!(parse_size(&u64::MAX.to_string()).unwrap(), u64::MAX);
+}
+
+#[test]
+fn offline_installs_need_the_cached_references() {
+    let repos = TempDir::new().unwrap();
+    let url = common::git_repo(&repos.path().join("unlisted"), &[("v1.0.0", None)]);
+    let dep = Dependency::new("unlisted", "unlisted", &url, None, "");
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    let error = pkg.add_dependency(dep.clone(), working_dir, &offline()).unwrap_err();
+    assert!(
+        error.to_string().contains(&format!(
+            "Offline mode: references of '{}' are not cached; run once online to cache them",
+            url
+        )),
+        "{}",
+        error
+    );
+    assert!(pkg.dependencies.is_empty());
+
+    // Once installed online, other projects can install it from the cache alone
+    pkg.add_dependency(dep.clone(), working_dir, &common::config()).unwrap();
+    let (other, mut pkg) = common::project();
+    pkg.add_dependency(dep, common::path(&other), &offline()).unwrap();
+    assert!(other.path().join("deps/unlisted@v1.0.0").is_dir());
+}
+
+#[test]
+fn offline_installs_need_the_locked_commit_in_the_cache() {
+    let repos = TempDir::new().unwrap();
+    let url = common::git_repo(&repos.path().join("uncached"), &[("v1.0.0", None), ("v2.0.0", None)]);
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    let mut dep = Dependency::new("uncached", "uncached", &url, None, "");
+    dep.version_constraint = Some("^1.0".to_string());
+    pkg.add_dependency(dep, working_dir, &common::config()).unwrap();
+
+    // Lock v2.0.0, which was listed but never downloaded
+    let v2 = git2::Repository::open(repos.path().join("uncached"))
+        .unwrap()
+        .revparse_single("v2.0.0")
+        .unwrap()
+        .id()
+        .to_string();
+    let (other, _) = common::project();
+    let other_dir = common::path(&other);
+    let manifest = fs::read_to_string(dir.path().join("package.yaml")).unwrap();
+    let lock = fs::read_to_string(dir.path().join("depo.lock")).unwrap();
+    let v1 = serialization::load_lockfile(working_dir).unwrap().dependencies[0].commit.clone();
+    fs::write(other.path().join("package.yaml"), manifest.replace("v1.0.0", "v2.0.0")).unwrap();
+    fs::write(other.path().join("depo.lock"), lock.replace(&v1, &v2).replace("v1.0.0", "v2.0.0")).unwrap();
+
+    let mut pkg = serialization::load_package(other_dir).unwrap();
+    let error = pkg.install(other_dir, InstallMode::Normal, &offline()).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .contains(&format!("Offline mode: commit {} of 'uncached' ({}) is not cached", v2, url)),
+        "{}",
+        error
+    );
+    assert!(!other.path().join("deps/uncached@v2.0.0").exists());
+}
+
+#[tokio::test]
+async fn offline_search_only_finds_cached_repositories() {
+    let error = Package::new()
+        .find_dependency("never-downloaded", ".", &offline())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Offline mode: no cached repository matches 'never-downloaded'"
+    );
+}
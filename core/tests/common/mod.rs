@@ -0,0 +1,87 @@
+//! Helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use depo_core::config::Config;
+use depo_core::package::Package;
+use git2::{Repository, Signature};
+use std::fs;
+use std::path::Path;
+use std::sync::{Once, OnceLock};
+use tempfile::TempDir;
+
+static SETUP: Once = Once::new();
+static CACHE: OnceLock<TempDir> = OnceLock::new();
+
+/// Point the download cache at a temporary directory for the whole test binary
+///
+/// Every test calls this before anything else, so the environment is only changed while
+/// no other test reads it.
+pub fn setup() {
+    SETUP.call_once(|| {
+        let cache = CACHE.get_or_init(|| TempDir::new().unwrap());
+        // SAFETY: `call_once` blocks every other test until the variable is set
+        unsafe {
+            std::env::set_var("DEPO_CACHE_DIR", cache.path());
+            std::env::remove_var("DEPO_OFFLINE");
+        }
+    });
+}
+
+/// Create an empty package in a new temporary directory
+pub fn project() -> (TempDir, Package) {
+    setup();
+    let dir = TempDir::new().unwrap();
+    let pkg = Package::init(path(&dir)).unwrap();
+    (dir, pkg)
+}
+
+/// The path of a temporary directory as the `&str` the core API expects
+pub fn path(dir: &TempDir) -> &str {
+    dir.path().to_str().unwrap()
+}
+
+/// Create a Git repository with one commit and tag per version
+///
+/// # Arguments
+///
+/// * `dir` - The directory to create the repository in
+/// * `versions` - The tag of every commit, with the `package.yaml` it contains, if any
+pub fn git_repo(dir: &Path, versions: &[(&str, Option<&str>)]) -> String {
+    fs::create_dir_all(dir).unwrap();
+    let repo = Repository::init(dir).unwrap();
+    let signature = Signature::now("depo", "depo@example.com").unwrap();
+
+    for (tag, manifest) in versions {
+        fs::write(dir.join("VERSION"), tag).unwrap();
+        match manifest {
+            Some(manifest) => fs::write(dir.join("package.yaml"), manifest).unwrap(),
+            None => {
+                let _ = fs::remove_file(dir.join("package.yaml"));
+            }
+        }
+
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, tag, &tree, &parents)
+            .unwrap();
+        repo.tag_lightweight(tag, &repo.find_object(commit, None).unwrap(), false)
+            .unwrap();
+    }
+
+    format!("file://{}", dir.display())
+}
+
+/// The configuration operations run with, loaded after `setup`
+pub fn config() -> Config {
+    setup();
+    Config::load().unwrap()
+}
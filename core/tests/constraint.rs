@@ -0,0 +1,108 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::package::InstallMode;
+use depo_core::serialization;
+use git2::Repository;
+use tempfile::TempDir;
+
+/// `liba` with versions 1.0.0, 1.1.0 and 2.0.0
+fn liba(dir: &TempDir) -> String {
+    common::git_repo(
+        &dir.path().join("liba"),
+        &[("v1.0.0", None), ("v1.1.0", None), ("v2.0.0", None)],
+    )
+}
+
+fn commit_of(dir: &TempDir, tag: &str) -> String {
+    Repository::open(dir.path().join("liba"))
+        .unwrap()
+        .revparse_single(tag)
+        .unwrap()
+        .peel_to_commit()
+        .unwrap()
+        .id()
+        .to_string()
+}
+
+#[test]
+fn a_tag_pin_installs_the_tag_and_blocks_updates() {
+    let repos = TempDir::new().unwrap();
+    let url = liba(&repos);
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.add_dependency(Dependency::new("liba", "liba", &url, None, ""), working_dir, &common::config())
+        .unwrap();
+
+    pkg.pin_dependency("liba", None, Some("v1.0.0"), working_dir)
+        .unwrap();
+    pkg.install(working_dir, InstallMode::Normal, &common::config()).unwrap();
+
+    assert_eq!(pkg.dependencies[0].version, "v1.0.0");
+    assert!(dir.path().join("deps/liba@v1.0.0").is_dir());
+    assert!(!dir.path().join("deps/liba@v2.0.0").exists());
+    let locked = serialization::load_lockfile(working_dir).unwrap();
+    assert_eq!(locked.find("liba").unwrap().commit, commit_of(&repos, "v1.0.0"));
+
+    let error = pkg.update_dependency("liba", working_dir, &common::config()).unwrap_err();
+    assert!(error.to_string().contains("pinned to tag 'v1.0.0'"), "{}", error);
+}
+
+#[test]
+fn a_rev_pin_installs_the_exact_commit() {
+    let repos = TempDir::new().unwrap();
+    let url = liba(&repos);
+    let commit = commit_of(&repos, "v1.1.0");
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.add_dependency(Dependency::new("liba", "liba", &url, None, ""), working_dir, &common::config())
+        .unwrap();
+
+    pkg.pin_dependency("liba", Some(&commit), None, working_dir)
+        .unwrap();
+    pkg.install(working_dir, InstallMode::Normal, &common::config()).unwrap();
+
+    let locked = serialization::load_lockfile(working_dir).unwrap();
+    assert_eq!(locked.find("liba").unwrap().commit, commit);
+    assert_eq!(pkg.dependencies[0].rev.as_deref(), Some(commit.as_str()));
+    assert_eq!(pkg.dependencies[0].version_constraint, None);
+}
+
+#[test]
+fn pins_need_exactly_one_of_rev_and_tag() {
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.dependencies
+        .push(Dependency::new("liba", "liba", "https://example.com/liba.git", None, ""));
+
+    assert!(pkg.pin_dependency("liba", None, None, working_dir).is_err());
+    assert!(pkg.pin_dependency("liba", Some("abc"), Some("v1"), working_dir).is_err());
+    let error = pkg.pin_dependency("libz", Some("abc"), None, working_dir).unwrap_err();
+    assert!(matches!(error, DepoError::DependencyNotFound { .. }), "{}", error);
+}
+
+#[test]
+fn constraints_replace_pins_and_can_be_removed() {
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    let mut dep = Dependency::new("liba", "liba", "https://example.com/liba.git", None, "");
+    dep.tag = Some("v1.0.0".to_string());
+    pkg.dependencies.push(dep);
+
+    let error = pkg
+        .modify_dependency_constraint("liba", "not a constraint", working_dir)
+        .unwrap_err();
+    assert!(matches!(error, DepoError::ConstraintInvalid { .. }), "{}", error);
+
+    pkg.modify_dependency_constraint("liba", "^1.1", working_dir)
+        .unwrap();
+    let saved = serialization::load_package(working_dir).unwrap();
+    assert_eq!(saved.dependencies[0].version_constraint.as_deref(), Some("^1.1"));
+    assert_eq!(saved.dependencies[0].tag, None);
+
+    pkg.remove_dependency_constraint("liba", working_dir).unwrap();
+    let saved = serialization::load_package(working_dir).unwrap();
+    assert_eq!(saved.dependencies[0].version_constraint, None);
+    assert!(pkg.remove_dependency_constraint("liba", working_dir).is_err());
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::serialization::{self, ManifestFormat};
+use tempfile::TempDir;
+
+#[test]
+fn names_that_leave_the_deps_directory_are_rejected() {
+    let url = "https://github.com/nlohmann/json.git";
+    for name in ["../../x", "a/b", "a\\b", "..", ".hidden", ""] {
+        let error = Dependency::from_url(url, Some(name)).unwrap_err();
+        assert!(matches!(error, DepoError::DependencyInvalid { .. }), "{}", name);
+    }
+    assert_eq!(Dependency::from_url(url, Some("nlohmann.json")).unwrap().name, "nlohmann.json");
+
+    let manifest = "schema_version: 1\ndependencies:\n- name: ../../x\n  url: https://example.com/x.git\n  version: ''\n";
+    let error = serialization::parse_package(manifest, ManifestFormat::Yaml).unwrap_err();
+    assert!(error.to_string().contains("Invalid dependency name '../../x'"), "{}", error);
+}
+
+#[test]
+fn nested_local_dependencies_must_stay_inside_their_package() {
+    let repos = TempDir::new().unwrap();
+    let manifest = "schema_version: 1\ndependencies:\n- name: outside\n  path: ../../outside\n  version: ''\n";
+    let libc = common::git_repo(&repos.path().join("libc"), &[("v1.0.0", Some(manifest))]);
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    let error = pkg
+        .add_dependency(Dependency::new("libc", "libc", &libc, None, ""), working_dir, &common::config())
+        .unwrap_err();
+
+    assert!(
+        error.to_string().contains("'libc' declares the local dependency 'outside' at '../../outside'"),
+        "{}",
+        error
+    );
+    assert!(pkg.dependencies.is_empty());
+    assert!(!dir.path().join("deps/libc@v1.0.0").exists());
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::package::Package;
+use depo_core::serialization;
+use std::fs;
+use tempfile::TempDir;
+
+const BLOCK: &str = "\
+# My project
+schema_version: 1
+name: app # the name
+
+# Libraries
+dependencies:
+  # JSON
+  - name: json
+    url: \"https://github.com/nlohmann/json.git\"
+    version_constraint: '^3.11'
+    version: v3.11.3
+  - name: fmt
+    url: https://github.com/fmtlib/fmt.git
+    version: '10.2.1'
+# end
+";
+
+const FLOW: &str = "\
+schema_version: 1 # current
+dependencies: [{name: json, url: 'https://github.com/nlohmann/json.git', version: v3.11.3}]
+# trailing
+";
+
+/// Save a package file after changing the package it holds, returning the new text
+fn edit(text: &str, change: impl FnOnce(&mut Package)) -> String {
+    common::setup();
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("package.yaml");
+    fs::write(&file, text).unwrap();
+
+    let mut pkg = serialization::load_package(common::path(&dir)).unwrap();
+    change(&mut pkg);
+    serialization::save_package(&pkg, common::path(&dir)).unwrap();
+    fs::read_to_string(file).unwrap()
+}
+
+#[test]
+fn saving_an_unchanged_package_keeps_the_file() {
+    assert_eq!(edit(BLOCK, |_| {}), BLOCK);
+    assert_eq!(edit(FLOW, |_| {}), FLOW);
+}
+
+#[test]
+fn modifying_a_dependency_keeps_comments_and_quoted_scalars() {
+    let edited = edit(BLOCK, |pkg| {
+        pkg.dependencies[1].version_constraint = Some("^10".to_string());
+    });
+    assert_eq!(
+        edited,
+        BLOCK.replace(
+            "    url: https://github.com/fmtlib/fmt.git\n",
+            "    url: https://github.com/fmtlib/fmt.git\n    version_constraint: ^10\n"
+        )
+    );
+
+    let edited = edit(BLOCK, |pkg| pkg.dependencies[0].version_constraint = None);
+    assert_eq!(edited, BLOCK.replace("    version_constraint: '^3.11'\n", ""));
+}
+
+#[test]
+fn adding_a_dependency_appends_it_to_a_block_sequence() {
+    let edited = edit(BLOCK, |pkg| {
+        pkg.dependencies.push(Dependency::new(
+            "spdlog",
+            "gabime/spdlog",
+            "https://github.com/gabime/spdlog.git",
+            Some("^1.12".to_string()),
+            "v1.12.0",
+        ));
+    });
+    assert_eq!(
+        edited,
+        BLOCK.replace(
+            "# end\n",
+            "  - name: spdlog
+    full_name: gabime/spdlog
+    url: https://github.com/gabime/spdlog.git
+    version_constraint: ^1.12
+    version: v1.12.0
+# end
+"
+        )
+    );
+}
+
+#[test]
+fn removing_a_dependency_removes_its_comments() {
+    let edited = edit(BLOCK, |pkg| {
+        pkg.dependencies.remove(0);
+    });
+    assert_eq!(
+        edited,
+        BLOCK.replace(
+            "  # JSON
+  - name: json
+    url: \"https://github.com/nlohmann/json.git\"
+    version_constraint: '^3.11'
+    version: v3.11.3
+",
+            ""
+        )
+    );
+}
+
+#[test]
+fn changing_a_flow_sequence_rewrites_it_as_a_block_sequence() {
+    let edited = edit(FLOW, |pkg| {
+        pkg.dependencies.push(Dependency::new(
+            "fmt",
+            "fmtlib/fmt",
+            "https://github.com/fmtlib/fmt.git",
+            None,
+            "10.2.1",
+        ));
+    });
+    assert_eq!(
+        edited,
+        "\
+schema_version: 1 # current
+dependencies:
+- name: json
+  url: https://github.com/nlohmann/json.git
+  version: v3.11.3
+- name: fmt
+  full_name: fmtlib/fmt
+  url: https://github.com/fmtlib/fmt.git
+  version: 10.2.1
+# trailing
+"
+    );
+}
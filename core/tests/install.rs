@@ -0,0 +1,246 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::lockfile::LockedDependency;
+use depo_core::package::InstallMode;
+use depo_core::serialization;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// `liba` with versions 1.0.0, 1.1.0 and 2.0.0, and `libb` 1.0.0 requiring `liba ^1.0`
+fn libraries(dir: &Path) -> (String, String) {
+    let liba = common::git_repo(
+        &dir.join("liba"),
+        &[("v1.0.0", None), ("v1.1.0", None), ("v2.0.0", None)],
+    );
+    let manifest = format!(
+        "schema_version: 1\ndependencies:\n- name: liba\n  url: {}\n  version_constraint: ^1.0\n  version: ''\n",
+        liba
+    );
+    let libb = common::git_repo(&dir.join("libb"), &[("v1.0.0", Some(&manifest))]);
+    (liba, libb)
+}
+
+fn dependency(name: &str, url: &str, constraint: Option<&str>) -> Dependency {
+    Dependency::new(name, name, url, constraint.map(str::to_string), "")
+}
+
+#[test]
+fn add_reports_a_conflict_with_a_nested_requirement() {
+    let repos = TempDir::new().unwrap();
+    let (liba, libb) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("liba", &liba, Some("^2.0")), working_dir, &common::config())
+        .unwrap();
+    let error = pkg
+        .add_dependency(dependency("libb", &libb, None), working_dir, &common::config())
+        .unwrap_err();
+
+    let DepoError::NoMatchingVersion { name, message } = &error else {
+        panic!("unexpected error: {}", error);
+    };
+    assert_eq!(name, "liba");
+    assert!(message.contains("package.yaml requires liba ^2.0"), "{}", message);
+    assert!(message.contains("libb 1.0.0 requires liba ^1.0"), "{}", message);
+
+    // The failed add is rolled back
+    assert_eq!(pkg.dependencies.len(), 1);
+    let lockfile = serialization::load_lockfile(working_dir).unwrap();
+    assert!(lockfile.find("libb").is_none());
+    assert_eq!(lockfile.find("liba").unwrap().version, "v2.0.0");
+}
+
+#[test]
+fn add_picks_a_version_meeting_every_requirement() {
+    let repos = TempDir::new().unwrap();
+    let (liba, libb) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("liba", &liba, None), working_dir, &common::config())
+        .unwrap();
+    assert_eq!(pkg.dependencies[0].version, "v2.0.0");
+
+    pkg.add_dependency(dependency("libb", &libb, None), working_dir, &common::config())
+        .unwrap();
+
+    let lockfile = serialization::load_lockfile(working_dir).unwrap();
+    assert_eq!(lockfile.find("liba").unwrap().version, "v1.1.0");
+    assert_eq!(lockfile.find("libb").unwrap().requires, vec!["liba".to_string()]);
+    assert_eq!(pkg.dependencies[0].version, "v1.1.0");
+}
+
+#[test]
+fn install_solves_again_when_the_lock_breaks_a_nested_requirement() {
+    let repos = TempDir::new().unwrap();
+    let (liba, libb) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("liba", &liba, Some("^2.0")), working_dir, &common::config())
+        .unwrap();
+
+    // A lock written without checking libb's requirement against liba v2.0.0
+    let libb_commit = git2::Repository::open(repos.path().join("libb"))
+        .unwrap()
+        .revparse_single("v1.0.0")
+        .unwrap()
+        .id()
+        .to_string();
+    let mut lockfile = serialization::load_lockfile(working_dir).unwrap();
+    lockfile.upsert(LockedDependency {
+        name: "libb".to_string(),
+        url: libb.clone(),
+        version: "v1.0.0".to_string(),
+        commit: libb_commit,
+        tag: Some("v1.0.0".to_string()),
+        version_constraint: None,
+        rev: None,
+        path: None,
+        archive: None,
+        sha256: None,
+        requires: vec!["liba".to_string()],
+    });
+    serialization::save_lockfile(&lockfile, working_dir).unwrap();
+    pkg.dependencies.push(dependency("libb", &libb, None));
+    serialization::save_package(&pkg, working_dir).unwrap();
+
+    let locked = pkg.install(working_dir, InstallMode::Locked, &common::config()).unwrap_err();
+    assert!(
+        locked.to_string().contains("libb v1.0.0 requires liba ^1.0, but liba v2.0.0 is installed"),
+        "{}",
+        locked
+    );
+
+    let error = pkg.install(working_dir, InstallMode::Normal, &common::config()).unwrap_err();
+    assert!(matches!(error, DepoError::NoMatchingVersion { .. }), "{}", error);
+}
+
+#[test]
+fn install_retires_the_version_a_constraint_change_supersedes() {
+    let repos = TempDir::new().unwrap();
+    let (liba, _) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("liba", &liba, Some("^1.0")), working_dir, &common::config())
+        .unwrap();
+    assert!(dir.path().join("deps/liba@v1.1.0").is_dir());
+
+    pkg.modify_dependency_constraint("liba", "^2.0", working_dir)
+        .unwrap();
+    pkg.install(working_dir, InstallMode::Normal, &common::config()).unwrap();
+
+    assert!(dir.path().join("deps/liba@v2.0.0").is_dir());
+    assert!(!dir.path().join("deps/liba@v1.1.0").exists());
+    assert!(!dir.path().join("deps/.depo-transaction").exists());
+}
+
+#[test]
+fn update_installs_the_newest_version_and_retires_the_previous_one() {
+    let repos = TempDir::new().unwrap();
+    let (liba, _) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("liba", &liba, Some("^1.0")), working_dir, &common::config())
+        .unwrap();
+    common::git_repo(&repos.path().join("liba"), &[("v1.2.0", None)]);
+
+    pkg.update_dependency("liba", working_dir, &common::config()).unwrap();
+
+    assert_eq!(pkg.dependencies[0].version, "v1.2.0");
+    assert!(dir.path().join("deps/liba@v1.2.0").is_dir());
+    assert!(!dir.path().join("deps/liba@v1.1.0").exists());
+}
+
+#[test]
+fn remove_retires_the_dependency_and_its_orphans() {
+    let repos = TempDir::new().unwrap();
+    let (_, libb) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+
+    pkg.add_dependency(dependency("libb", &libb, None), working_dir, &common::config())
+        .unwrap();
+    assert!(dir.path().join("deps/liba@v1.1.0").is_dir());
+
+    pkg.remove_dependency("libb", working_dir).unwrap();
+
+    assert!(pkg.dependencies.is_empty());
+    assert!(!dir.path().join("deps/libb@v1.0.0").exists());
+    assert!(!dir.path().join("deps/liba@v1.1.0").exists());
+    let lockfile = serialization::load_lockfile(working_dir).unwrap();
+    assert!(lockfile.dependencies.is_empty());
+}
+
+#[test]
+fn locked_install_fails_when_the_lock_is_stale() {
+    let repos = TempDir::new().unwrap();
+    let (liba, _) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.add_dependency(dependency("liba", &liba, Some("^1.0")), working_dir, &common::config())
+        .unwrap();
+    let lock = std::fs::read_to_string(dir.path().join("depo.lock")).unwrap();
+
+    pkg.dependencies[0].version_constraint = Some("^2.0".to_string());
+    serialization::save_package(&pkg, working_dir).unwrap();
+
+    let error = pkg.install(working_dir, InstallMode::Locked, &common::config()).unwrap_err();
+    assert!(
+        error.to_string().contains("constraint of 'liba' changed from '^1.0' to '^2.0'"),
+        "{}",
+        error
+    );
+    assert_eq!(std::fs::read_to_string(dir.path().join("depo.lock")).unwrap(), lock);
+    assert!(!dir.path().join("deps/liba@v2.0.0").exists());
+}
+
+#[test]
+fn frozen_install_never_writes_the_lockfile() {
+    let repos = TempDir::new().unwrap();
+    let (liba, _) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.dependencies.push(dependency("liba", &liba, Some("^1.0")));
+    serialization::save_package(&pkg, working_dir).unwrap();
+
+    let error = pkg.install(working_dir, InstallMode::Frozen, &common::config()).unwrap_err();
+    assert!(error.to_string().contains("depo.lock not found"), "{}", error);
+    assert!(!dir.path().join("depo.lock").exists());
+
+    pkg.install(working_dir, InstallMode::Normal, &common::config()).unwrap();
+    let lock = std::fs::read_to_string(dir.path().join("depo.lock")).unwrap();
+    pkg.install(working_dir, InstallMode::Frozen, &common::config()).unwrap();
+    assert_eq!(std::fs::read_to_string(dir.path().join("depo.lock")).unwrap(), lock);
+}
+
+#[test]
+fn frozen_install_checks_out_the_exact_locked_commit() {
+    let repos = TempDir::new().unwrap();
+    let (liba, _) = libraries(repos.path());
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.add_dependency(dependency("liba", &liba, Some("^1.0")), working_dir, &common::config())
+        .unwrap();
+    let commit = serialization::load_lockfile(working_dir)
+        .unwrap()
+        .find("liba")
+        .unwrap()
+        .commit
+        .clone();
+
+    // A newer matching version and a fresh checkout must not move the frozen install
+    common::git_repo(&repos.path().join("liba"), &[("v1.2.0", None)]);
+    std::fs::remove_dir_all(dir.path().join("deps/liba@v1.1.0")).unwrap();
+    pkg.install(working_dir, InstallMode::Frozen, &common::config()).unwrap();
+
+    let checkout = git2::Repository::open(dir.path().join("deps/liba@v1.1.0")).unwrap();
+    let head = checkout.head().unwrap().peel_to_commit().unwrap().id().to_string();
+    assert_eq!(head, commit);
+    assert!(!dir.path().join("deps/liba@v1.2.0").exists());
+}
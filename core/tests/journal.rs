@@ -0,0 +1,80 @@
+use depo_core::journal;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn create(dir: &Path, name: &str) {
+    fs::create_dir_all(dir.join(name)).unwrap();
+    fs::write(dir.join(name).join("CMakeLists.txt"), "").unwrap();
+}
+
+#[test]
+fn recover_removes_what_interrupted_installs_left_behind() {
+    let project = TempDir::new().unwrap();
+    let deps = project.path().join("deps");
+    for name in [
+        "liba@temp",
+        "liba@v1.0.0",
+        "libb@v2.0.0.unpack",
+        "libb@v2.0.0",
+        "libc@temp",
+        "libd@v1.0.0",
+    ] {
+        create(&deps, name);
+    }
+    fs::write(
+        deps.join(".depo-journal.yaml"),
+        format!(
+            "installs:
+- name: liba
+  temp: {deps}/liba@temp
+  target: {deps}/liba@v1.0.0
+  stage: fetching
+- name: libb
+  temp: {deps}/libb@v2.0.0.unpack
+  target: {deps}/libb@v2.0.0
+  stage: installing
+",
+            deps = deps.display()
+        ),
+    )
+    .unwrap();
+
+    let mut removed = journal::recover(project.path().to_str().unwrap()).unwrap();
+    removed.sort();
+
+    assert_eq!(
+        removed,
+        vec![
+            deps.join("liba@temp"),
+            deps.join("libb@v2.0.0"),
+            deps.join("libb@v2.0.0.unpack"),
+            deps.join("libc@temp"),
+        ]
+    );
+    // A fetch that was interrupted never touched the installed version
+    assert!(deps.join("liba@v1.0.0").is_dir());
+    assert!(deps.join("libd@v1.0.0").is_dir());
+    assert!(!deps.join(".depo-journal.yaml").exists());
+
+    assert!(journal::recover(project.path().to_str().unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn finished_installs_leave_nothing_to_recover() {
+    let project = TempDir::new().unwrap();
+    let working_dir = project.path().to_str().unwrap();
+    let deps = project.path().join("deps");
+    create(&deps, "liba@temp");
+
+    let target = deps.join("liba@v1.0.0");
+    journal::begin(working_dir, "liba", &deps.join("liba@temp"), &target, journal::Stage::Fetching)
+        .unwrap();
+    journal::set_stage(working_dir, "liba", &target, journal::Stage::Installing).unwrap();
+    fs::rename(deps.join("liba@temp"), &target).unwrap();
+    journal::finish(working_dir, "liba").unwrap();
+
+    assert!(!deps.join(".depo-journal.yaml").exists());
+    assert!(journal::recover(working_dir).unwrap().is_empty());
+    assert!(target.is_dir());
+}
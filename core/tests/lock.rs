@@ -0,0 +1,122 @@
+use depo_core::error::DepoError;
+use depo_core::lock::ProjectLock;
+use depo_core::package::Package;
+use depo_core::serialization::{self, ManifestFormat};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn lock_file(dir: &TempDir) -> String {
+    fs::read_to_string(dir.path().join("deps/.depo-lock")).unwrap()
+}
+
+/// Hold the lock of a project on another thread until `release` is sent
+fn hold_elsewhere(dir: &TempDir) -> (std::sync::mpsc::Sender<()>, thread::JoinHandle<()>) {
+    let path = dir.path().to_str().unwrap().to_string();
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel();
+    let holder = thread::spawn(move || {
+        let _lock = ProjectLock::acquire_with_timeout(&path, Duration::ZERO).unwrap();
+        locked_tx.send(()).unwrap();
+        release_rx.recv().unwrap();
+    });
+    locked_rx.recv().unwrap();
+    (release_tx, holder)
+}
+
+#[test]
+fn the_lock_is_reentrant_on_the_same_thread() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let outer = ProjectLock::acquire_with_timeout(path, Duration::ZERO).unwrap();
+    let inner = ProjectLock::acquire_with_timeout(path, Duration::ZERO).unwrap();
+    assert_eq!(lock_file(&dir), std::process::id().to_string());
+
+    drop(inner);
+    assert_eq!(lock_file(&dir), std::process::id().to_string());
+    drop(outer);
+    assert_eq!(lock_file(&dir), "");
+}
+
+#[test]
+fn another_holder_makes_acquiring_time_out() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let (release, holder) = hold_elsewhere(&dir);
+
+    let started = Instant::now();
+    let error = ProjectLock::acquire_with_timeout(path, Duration::from_millis(300))
+        .err()
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    match error.downcast_ref::<DepoError>() {
+        Some(DepoError::ProjectLocked { pid, .. }) => assert_eq!(*pid, Some(std::process::id())),
+        _ => panic!("unexpected error: {}", error),
+    }
+
+    release.send(()).unwrap();
+    holder.join().unwrap();
+}
+
+#[test]
+fn acquiring_waits_for_the_holder_to_release_the_lock() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let (release, holder) = hold_elsewhere(&dir);
+
+    let releaser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        release.send(()).unwrap();
+    });
+    let lock = ProjectLock::acquire_with_timeout(path, Duration::from_secs(10)).unwrap();
+    assert_eq!(lock_file(&dir), std::process::id().to_string());
+
+    drop(lock);
+    releaser.join().unwrap();
+    holder.join().unwrap();
+}
+
+#[test]
+fn the_timeout_is_taken_from_depo_lock_timeout() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+    let (release, holder) = hold_elsewhere(&dir);
+
+    // SAFETY: no other test in this binary reads the environment
+    unsafe { std::env::set_var("DEPO_LOCK_TIMEOUT", "1") };
+    let error = ProjectLock::acquire(path).err().unwrap();
+    match error.downcast_ref::<DepoError>() {
+        Some(DepoError::ProjectLocked { timeout, .. }) => assert_eq!(*timeout, 1),
+        _ => panic!("unexpected error: {}", error),
+    }
+
+    release.send(()).unwrap();
+    holder.join().unwrap();
+}
+
+#[test]
+fn a_stale_lock_left_by_a_crashed_process_is_taken_over() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+    fs::create_dir_all(dir.path().join("deps")).unwrap();
+    fs::write(dir.path().join("deps/.depo-lock"), "4242").unwrap();
+
+    let lock = ProjectLock::acquire_with_timeout(path, Duration::ZERO).unwrap();
+    assert_eq!(lock_file(&dir), std::process::id().to_string());
+    drop(lock);
+}
+
+#[test]
+fn editing_only_the_package_file_does_not_create_deps() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().to_str().unwrap();
+
+    let pkg = Package::init(path).unwrap();
+    serialization::save_package(&pkg, path).unwrap();
+    serialization::migrate_package(path).unwrap();
+    serialization::convert_package(path, ManifestFormat::Toml).unwrap();
+
+    assert!(!dir.path().join("deps").exists());
+}
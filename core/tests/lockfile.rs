@@ -0,0 +1,103 @@
+use depo_core::dependency::Dependency;
+use depo_core::lockfile::{LockedDependency, Lockfile};
+
+fn dependency(name: &str, constraint: Option<&str>) -> Dependency {
+    let url = format!("https://example.com/org/{}.git", name);
+    Dependency::new(name, name, &url, constraint.map(str::to_string), "")
+}
+
+/// Lock a dependency at `v1.0.0`, requiring the given dependencies
+fn locked(dep: &Dependency, requires: &[&str]) -> LockedDependency {
+    LockedDependency {
+        name: dep.name.clone(),
+        url: dep.url.clone(),
+        version: "v1.0.0".to_string(),
+        commit: "1".repeat(40),
+        tag: Some("v1.0.0".to_string()),
+        version_constraint: dep.version_constraint.clone(),
+        rev: dep.rev.clone(),
+        path: dep.path.clone(),
+        archive: dep.archive.clone(),
+        sha256: dep.sha256.clone(),
+        requires: requires.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+#[test]
+fn entries_match_the_dependency_they_were_resolved_from() {
+    let dep = dependency("liba", Some("^1.0"));
+    let lock = locked(&dep, &[]);
+    assert!(lock.matches(&dep));
+
+    let mut installed = dep.clone();
+    installed.version = "v1.0.0".to_string();
+    installed.tag = Some("v1.0.0".to_string());
+    assert!(lock.matches(&installed));
+
+    let changes: [fn(&mut Dependency); 7] = [
+        |d| d.url = "https://example.com/fork/liba.git".to_string(),
+        |d| d.version_constraint = Some("^2.0".to_string()),
+        |d| d.version_constraint = None,
+        |d| d.rev = Some("main".to_string()),
+        |d| d.tag = Some("v1.1.0".to_string()),
+        |d| d.path = Some("../liba".to_string()),
+        |d| d.sha256 = Some("0".repeat(64)),
+    ];
+    for change in changes {
+        let mut changed = dep.clone();
+        change(&mut changed);
+        assert!(!lock.matches(&changed), "{:?}", changed);
+    }
+}
+
+#[test]
+fn verification_lists_every_disagreement() {
+    let liba = dependency("liba", Some("^1.0"));
+    let libb = dependency("libb", None);
+    let mut lockfile = Lockfile::new();
+    lockfile.upsert(locked(&liba, &["libc"]));
+    lockfile.upsert(locked(&libb, &[]));
+    lockfile.upsert(locked(&dependency("libc", None), &[]));
+    lockfile.verify(&[liba.clone(), libb.clone()]).unwrap();
+
+    let mut moved = liba.clone();
+    moved.url = "https://example.com/fork/liba.git".to_string();
+    let mut pinned = libb.clone();
+    pinned.tag = Some("v2.0.0".to_string());
+    let error = lockfile
+        .verify(&[moved, pinned, dependency("libd", None)])
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "depo.lock is out of date with package.yaml:
+  URL of 'liba' changed from 'https://example.com/org/liba.git' to 'https://example.com/fork/liba.git'
+  'libb' is locked at tag 'v1.0.0' but pinned to tag 'v2.0.0'
+  'libd' is not in depo.lock"
+    );
+
+    let error = lockfile.verify(std::slice::from_ref(&libb)).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "depo.lock is out of date with package.yaml:
+  'liba' is locked but not required by package.yaml
+  'libc' is locked but not required by package.yaml"
+    );
+}
+
+#[test]
+fn pruning_keeps_only_entries_reachable_from_the_roots() {
+    let liba = dependency("liba", None);
+    let mut lockfile = Lockfile::new();
+    lockfile.upsert(locked(&liba, &["libb"]));
+    lockfile.upsert(locked(&dependency("libb", None), &["libc"]));
+    lockfile.upsert(locked(&dependency("libc", None), &["libb"]));
+    lockfile.upsert(locked(&dependency("libd", None), &["libc"]));
+
+    let removed = lockfile.prune(std::slice::from_ref(&liba));
+    let names = |entries: &[LockedDependency]| entries.iter().map(|d| d.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&removed), ["libd"]);
+    assert_eq!(names(&lockfile.dependencies), ["liba", "libb", "libc"]);
+
+    assert_eq!(names(&lockfile.prune(&[])), ["liba", "libb", "libc"]);
+    assert!(lockfile.dependencies.is_empty());
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::package::Package;
+use depo_core::serialization::{self, ManifestFormat};
+use std::fs;
+
+const YAML: &str = "schema_version: 1
+name: app
+version: 0.1.0
+authors:
+- Jane Doe
+dependencies:
+- name: util
+  path: ../util
+  version_constraint: null
+  version: ''
+- name: zlib
+  archive: https://example.com/zlib-1.3.tar.gz
+  sha256: 0000000000000000000000000000000000000000000000000000000000000000
+  version_constraint: null
+  version: '1.3'
+- name: liba
+  full_name: org/liba
+  url: https://example.com/org/liba.git
+  registry: main
+  version_constraint: ^1.0
+  version: v1.2.0
+registries:
+- name: main
+  index: https://example.com/index.git
+tooling:
+  formatter: clang-format
+";
+
+#[test]
+fn yaml_manifests_are_converted_to_toml_and_back() {
+    let (dir, _) = common::project();
+    let path = common::path(&dir);
+    fs::write(dir.path().join("package.yaml"), YAML).unwrap();
+    let original = serialization::load_package(path).unwrap();
+
+    let target = serialization::convert_package(path, ManifestFormat::Toml).unwrap();
+    assert_eq!(target, dir.path().join("depo.toml"));
+    assert!(!dir.path().join("package.yaml").exists());
+    let toml = fs::read_to_string(&target).unwrap();
+    assert!(toml.contains("[[dependencies]]"), "{}", toml);
+    assert!(toml.contains("[tooling]\nformatter = \"clang-format\""), "{}", toml);
+    assert!(!toml.contains("version_constraint = \"\""), "{}", toml);
+
+    let converted = serialization::load_package(path).unwrap();
+    assert_eq!(serde_yaml::to_value(&converted).unwrap(), serde_yaml::to_value(&original).unwrap());
+    let deps = &converted.dependencies;
+    assert_eq!(deps[0].path.as_deref(), Some("../util"));
+    assert_eq!(deps[1].archive.as_deref(), Some("https://example.com/zlib-1.3.tar.gz"));
+    assert_eq!(deps[1].sha256.as_deref(), Some("0".repeat(64).as_str()));
+    assert_eq!(deps[2].registry.as_deref(), Some("main"));
+    assert_eq!(deps[2].version_constraint.as_deref(), Some("^1.0"));
+
+    let target = serialization::convert_package(path, ManifestFormat::Yaml).unwrap();
+    assert_eq!(target, dir.path().join("package.yaml"));
+    assert!(!dir.path().join("depo.toml").exists());
+    let round_trip = serialization::load_package(path).unwrap();
+    assert_eq!(serde_yaml::to_value(&round_trip).unwrap(), serde_yaml::to_value(&original).unwrap());
+    let yaml = fs::read_to_string(&target).unwrap();
+    assert!(yaml.contains("tooling:\n  formatter: clang-format"), "{}", yaml);
+}
+
+#[test]
+fn converting_to_the_current_format_is_an_error() {
+    let (dir, _) = common::project();
+    let path = common::path(&dir);
+    serialization::save_package(&Package::new(), path).unwrap();
+
+    let error = serialization::convert_package(path, ManifestFormat::Yaml).unwrap_err();
+    assert!(error.to_string().ends_with("is already a package.yaml"), "{}", error);
+    assert!(dir.path().join("package.yaml").exists());
+}
+
+#[test]
+fn projects_with_both_manifests_are_rejected() {
+    let (dir, _) = common::project();
+    let path = common::path(&dir);
+    fs::write(dir.path().join("package.yaml"), YAML).unwrap();
+    fs::write(dir.path().join("depo.toml"), "schema_version = 1\ndependencies = []\n").unwrap();
+
+    assert!(serialization::load_package(path).is_err());
+    assert!(serialization::convert_package(path, ManifestFormat::Toml).is_err());
+    assert_eq!(fs::read_to_string(dir.path().join("package.yaml")).unwrap(), YAML);
+}
+
+/// An edit that makes a valid package invalid
+type Change = fn(&mut Package);
+
+#[test]
+fn invalid_metadata_is_rejected() {
+    let valid = || {
+        let mut pkg = Package::new();
+        pkg.name = Some("my-lib_2.x".to_string());
+        pkg.version = Some("1.2.0-rc.1".to_string());
+        pkg.license = Some("MIT OR Apache-2.0".to_string());
+        pkg.authors = vec!["Jane Doe <jane@example.com>".to_string()];
+        pkg.repository = Some("https://example.com/org/my-lib.git".to_string());
+        pkg.cpp_standard = Some("c++20".to_string());
+        pkg.targets = vec!["my-lib".to_string(), "my-lib-extras".to_string()];
+        pkg
+    };
+    valid().validate().unwrap();
+
+    let cases: [(Change, &str); 9] = [
+        (|p| p.name = Some("my lib".to_string()), "Invalid package name 'my lib'"),
+        (|p| p.name = Some(".hidden".to_string()), "Invalid package name '.hidden'"),
+        (|p| p.version = Some("1.2".to_string()), "Invalid package version '1.2'"),
+        (|p| p.license = Some(" ".to_string()), "The package license must not be empty"),
+        (|p| p.authors.push(String::new()), "Package authors must not be empty"),
+        (|p| p.repository = Some("my-lib".to_string()), "Invalid repository 'my-lib'"),
+        (|p| p.cpp_standard = Some("c++19".to_string()), "Unknown C++ standard 'c++19'"),
+        (|p| p.targets.push("my lib".to_string()), "Invalid target name 'my lib'"),
+        (|p| p.targets.push("my-lib".to_string()), "Target 'my-lib' is listed twice"),
+    ];
+    for (change, message) in cases {
+        let mut pkg = valid();
+        change(&mut pkg);
+        let error = pkg.validate().unwrap_err();
+        assert!(matches!(error, DepoError::PackageInvalid { .. }), "{:?}", error);
+        assert!(error.to_string().starts_with(message), "{}", error);
+    }
+}
+
+#[test]
+fn dependencies_listed_twice_are_rejected() {
+    let (dir, _) = common::project();
+    let manifest = "schema_version: 1
+dependencies:
+- name: liba
+  url: https://example.com/org/liba.git
+  version_constraint: null
+  version: ''
+- name: liba
+  url: https://example.com/fork/liba.git
+  version_constraint: null
+  version: ''
+";
+    fs::write(dir.path().join("package.yaml"), manifest).unwrap();
+
+    let error = serialization::load_package(common::path(&dir)).unwrap_err();
+    assert!(error.to_string().contains("Dependency 'liba' is listed twice"), "{}", error);
+
+    let mut pkg = Package::new();
+    pkg.dependencies = vec![
+        Dependency::new("liba", "org/liba", "https://example.com/org/liba.git", None, ""),
+        Dependency::new("libb", "fork/liba", "https://example.com/fork/liba.git", None, ""),
+    ];
+    pkg.validate().unwrap();
+    pkg.dependencies[1].name = "liba".to_string();
+    let DepoError::DependencyInvalid { name, .. } = pkg.validate().unwrap_err() else {
+        panic!("duplicate dependency accepted");
+    };
+    assert_eq!(name, "liba");
+}
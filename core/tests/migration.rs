@@ -0,0 +1,74 @@
+mod common;
+
+use depo_core::migration::{self, SCHEMA_VERSION};
+use depo_core::serialization::{self, ManifestFormat};
+use std::fs;
+
+const V0: &str = "name: app
+dependencies:
+- name: json
+  full_name: nlohmann/json
+  url: https://github.com/nlohmann/json.git
+";
+
+#[test]
+fn unversioned_manifests_are_migrated_to_version_1() {
+    let mut doc: serde_yaml::Value = serde_yaml::from_str("name: app\ndependencies: ~\n").unwrap();
+    assert_eq!(migration::migrate(&mut doc).unwrap(), 0);
+    assert_eq!(
+        serde_yaml::to_string(&doc).unwrap(),
+        "schema_version: 1\nname: app\ndependencies: []\n"
+    );
+
+    let mut doc: serde_yaml::Value = serde_yaml::from_str("name: app\n").unwrap();
+    migration::migrate(&mut doc).unwrap();
+    assert_eq!(doc["dependencies"], serde_yaml::Value::Sequence(Vec::new()));
+
+    let pkg = serialization::parse_package(V0, ManifestFormat::Yaml).unwrap();
+    assert_eq!(pkg.schema_version, SCHEMA_VERSION);
+    assert_eq!(pkg.dependencies[0].name, "json");
+    assert_eq!(pkg.dependencies[0].version, "");
+
+    let (dir, _) = common::project();
+    fs::write(dir.path().join("package.yaml"), V0).unwrap();
+    assert_eq!(serialization::migrate_package(common::path(&dir)).unwrap(), 0);
+    let migrated = fs::read_to_string(dir.path().join("package.yaml")).unwrap();
+    assert!(migrated.starts_with("schema_version: 1\n"), "{}", migrated);
+    let pkg = serialization::load_package(common::path(&dir)).unwrap();
+    assert_eq!(pkg.name.as_deref(), Some("app"));
+    assert_eq!(pkg.dependencies[0].url, "https://github.com/nlohmann/json.git");
+}
+
+#[test]
+fn manifests_from_newer_versions_are_rejected() {
+    let (dir, _) = common::project();
+    let manifest = format!("schema_version: {}\ndependencies: []\n", SCHEMA_VERSION + 1);
+    fs::write(dir.path().join("package.yaml"), &manifest).unwrap();
+
+    let error = serialization::load_package(common::path(&dir)).unwrap_err();
+    assert!(
+        error.to_string().contains(&format!(
+            "uses schema version {}, but this version of Depo only supports up to {}",
+            SCHEMA_VERSION + 1,
+            SCHEMA_VERSION
+        )),
+        "{}",
+        error
+    );
+    assert!(serialization::migrate_package(common::path(&dir)).is_err());
+    assert_eq!(fs::read_to_string(dir.path().join("package.yaml")).unwrap(), manifest);
+
+    let mut doc: serde_yaml::Value = serde_yaml::from_str("schema_version: -1\n").unwrap();
+    let error = migration::migrate(&mut doc).unwrap_err();
+    assert_eq!(error.to_string(), "schema_version must be a non-negative integer");
+}
+
+#[test]
+fn migrating_a_current_manifest_leaves_it_untouched() {
+    let (dir, _) = common::project();
+    let manifest = "schema_version: 1\n# keep this comment\nname: app\ndependencies: []\n";
+    fs::write(dir.path().join("package.yaml"), manifest).unwrap();
+
+    assert_eq!(serialization::migrate_package(common::path(&dir)).unwrap(), SCHEMA_VERSION);
+    assert_eq!(fs::read_to_string(dir.path().join("package.yaml")).unwrap(), manifest);
+}
@@ -0,0 +1,260 @@
+mod common;
+
+use depo_core::cache::Cache;
+use depo_core::package::Package;
+use depo_core::registry::{self, Registry, RegistryConfig, RegistryPackage};
+use git2::Repository;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const LIBA: &str = "name: liba
+url: https://example.com/org/liba.git
+versions:
+- version: 1.0.0
+  tag: v1.0.0
+  commit: 1111111111111111111111111111111111111111
+- version: 1.2.0
+  tag: v1.2.0
+  commit: 2222222222222222222222222222222222222222
+- version: 2.0.0
+  tag: v2.0.0
+  commit: 3333333333333333333333333333333333333333
+";
+
+fn cache() -> Cache {
+    Cache::open(&common::config()).unwrap()
+}
+
+fn index(working_dir: &Path, dir: &str, entries: &[(&str, &str)]) -> RegistryConfig {
+    fs::create_dir_all(working_dir.join(dir)).unwrap();
+    for (name, content) in entries {
+        fs::write(working_dir.join(dir).join(format!("{}.yaml", name)), content).unwrap();
+    }
+    RegistryConfig {
+        name: dir.to_string(),
+        index: dir.to_string(),
+    }
+}
+
+#[test]
+fn packages_are_looked_up_by_name_and_version() {
+    let (dir, _) = common::project();
+    let config = index(dir.path(), "main", &[("liba", LIBA)]);
+    let opened = Registry::open(&config, common::path(&dir), true, &cache()).unwrap();
+
+    let package = opened.package("liba").unwrap().unwrap();
+    assert_eq!(package.url, "https://example.com/org/liba.git");
+    assert_eq!(package.find_version("v1.2.0").unwrap().version, "1.2.0");
+    assert_eq!(
+        package.find_version("3333333333333333333333333333333333333333").unwrap().tag,
+        "v2.0.0"
+    );
+    assert_eq!(package.newest_matching(Some("^1.0")).unwrap().version, "1.2.0");
+    assert_eq!(package.newest_matching(None).unwrap().version, "2.0.0");
+    assert!(package.newest_matching(Some("^3.0")).is_none());
+    assert!(opened.package("libb").unwrap().is_none());
+
+    let dep = opened.dependency(&package);
+    assert_eq!(dep.name, "liba");
+    assert_eq!(dep.full_name, "org/liba");
+    assert_eq!(dep.registry.as_deref(), Some("main"));
+}
+
+#[test]
+fn names_that_leave_the_index_are_rejected() {
+    let (dir, _) = common::project();
+    let config = index(dir.path(), "main", &[("liba", LIBA)]);
+    fs::write(dir.path().join("secret.yaml"), LIBA).unwrap();
+    let opened = Registry::open(&config, common::path(&dir), true, &cache()).unwrap();
+
+    for name in ["../secret", "a/b", "..", ""] {
+        let error = opened.package(name).unwrap_err();
+        assert!(error.to_string().contains("Invalid dependency name"), "{}: {}", name, error);
+        assert!(registry::entry_path(&opened.dir, name).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn registries_are_searched_in_the_configured_order() {
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    let other = LIBA.replace("example.com/org", "example.com/mirror");
+    pkg.registries = vec![
+        index(dir.path(), "first", &[("liba", LIBA)]),
+        index(dir.path(), "second", &[("liba", &other), ("libb", &other.replace("liba", "libb"))]),
+    ];
+
+    let dep = pkg.find_in_registries("liba", None, working_dir, &common::config()).unwrap().unwrap();
+    assert_eq!(dep.registry.as_deref(), Some("first"));
+    assert_eq!(dep.url, "https://example.com/org/liba.git");
+
+    let dep = pkg.find_in_registries("liba", Some("second"), working_dir, &common::config()).unwrap().unwrap();
+    assert_eq!(dep.registry.as_deref(), Some("second"));
+    assert_eq!(dep.url, "https://example.com/mirror/liba.git");
+
+    let dep = pkg.find_in_registries("libb", None, working_dir, &common::config()).unwrap().unwrap();
+    assert_eq!(dep.registry.as_deref(), Some("second"));
+    assert!(pkg.find_in_registries("libc", None, working_dir, &common::config()).unwrap().is_none());
+
+    let error = pkg.find_in_registries("liba", Some("third"), working_dir, &common::config()).unwrap_err();
+    assert_eq!(error.to_string(), "Registry 'third' is not configured in package.yaml");
+}
+
+#[test]
+fn missing_indexes_and_unlisted_dependencies_are_errors() {
+    let (dir, _) = common::project();
+    let working_dir = common::path(&dir);
+    let missing = RegistryConfig {
+        name: "missing".to_string(),
+        index: "nowhere".to_string(),
+    };
+    let error = Registry::open(&missing, working_dir, true, &cache()).unwrap_err();
+    assert!(error.to_string().starts_with("Index of registry 'missing' not found"), "{}", error);
+
+    let config = index(dir.path(), "main", &[("liba", LIBA), ("broken", "versions: [")]);
+    let opened = Registry::open(&config, working_dir, true, &cache()).unwrap();
+    let error = opened.package("broken").unwrap_err();
+    assert!(error.to_string().contains("in registry 'main'"), "{}", error);
+
+    let mut dep = opened.dependency(&opened.package("liba").unwrap().unwrap());
+    let registries = [opened];
+    let (found, package) = registry::find_package(&registries, &dep).unwrap().unwrap();
+    assert_eq!((found.name.as_str(), package.versions.len()), ("main", 3));
+
+    dep.name = "libb".to_string();
+    let error = registry::find_package(&registries, &dep).unwrap_err();
+    assert_eq!(error.to_string(), "'libb' is not published in registry 'main'");
+    dep.registry = Some("other".to_string());
+    let error = registry::find_package(&registries, &dep).unwrap_err();
+    assert_eq!(error.to_string(), "'libb' uses unknown registry 'other'");
+    dep.registry = None;
+    assert!(registry::find_package(&registries, &dep).unwrap().is_none());
+}
+
+/// Create a bare Git repository with one commit, to host an index
+fn bare_index(dir: &Path) -> String {
+    let seed = common::git_repo(&dir.join("seed"), &[("init", None)]);
+    git2::build::RepoBuilder::new()
+        .bare(true)
+        .clone(&seed, &dir.join("index.git"))
+        .unwrap();
+    format!("file://{}", dir.join("index.git").display())
+}
+
+/// Create a package repository whose `HEAD` is tagged with its version
+fn library(dir: &Path, version: &str) -> (String, Package) {
+    let manifest = format!("schema_version: 1\nname: liba\nversion: {}\ndependencies: []\n", version);
+    let url = common::git_repo(dir, &[(&format!("v{}", version), Some(&manifest))]);
+    let mut pkg = Package::new();
+    pkg.name = Some("liba".to_string());
+    pkg.version = Some(version.to_string());
+    (url, pkg)
+}
+
+#[test]
+fn publishing_commits_the_version_to_a_git_index() {
+    common::setup();
+    let dir = TempDir::new().unwrap();
+    let lib_dir = dir.path().join("liba");
+    let (url, mut pkg) = library(&lib_dir, "1.0.0");
+    pkg.registries = vec![RegistryConfig {
+        name: "main".to_string(),
+        index: bare_index(dir.path()),
+    }];
+
+    let published = pkg.publish(lib_dir.to_str().unwrap(), None, Some(&url)).unwrap();
+
+    let repo = Repository::open(&lib_dir).unwrap();
+    let head = repo.head().unwrap().peel_to_commit().unwrap().id();
+    assert_eq!(published.tag, "v1.0.0");
+    assert_eq!(published.commit, head.to_string());
+    assert_eq!(
+        published.sha256.as_deref(),
+        Some(registry::source_checksum(&repo, head).unwrap().as_str())
+    );
+
+    let index = Repository::open_bare(dir.path().join("index.git")).unwrap();
+    let commit = index.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(commit.message(), Some("Publish liba 1.0.0"));
+    let blob = commit.tree().unwrap().get_path(Path::new("liba.yaml")).unwrap();
+    let entry: RegistryPackage =
+        serde_yaml::from_slice(index.find_blob(blob.id()).unwrap().content()).unwrap();
+    assert_eq!(entry.url, url);
+    assert_eq!(entry.versions.len(), 1);
+    assert_eq!(entry.versions[0].commit, published.commit);
+    assert_eq!(entry.versions[0].sha256, published.sha256);
+}
+
+#[test]
+fn published_versions_cannot_be_published_again() {
+    common::setup();
+    let dir = TempDir::new().unwrap();
+    let lib_dir = dir.path().join("liba");
+    let working_dir = lib_dir.to_str().unwrap();
+    let (url, mut pkg) = library(&lib_dir, "1.0.0");
+    fs::create_dir(dir.path().join("index")).unwrap();
+    pkg.registries = vec![RegistryConfig {
+        name: "main".to_string(),
+        index: "../index".to_string(),
+    }];
+
+    pkg.publish(working_dir, None, Some(&url)).unwrap();
+    let published = fs::read_to_string(dir.path().join("index/liba.yaml")).unwrap();
+
+    let error = pkg.publish(working_dir, None, Some(&url)).unwrap_err();
+    let message = error.to_string();
+    assert!(message.starts_with("'liba' 1.0.0 (v1.0.0 at "), "{}", message);
+    assert!(message.ends_with(") is already published in registry 'main'"), "{}", message);
+
+    let error = pkg
+        .publish(working_dir, None, Some("https://example.com/fork/liba.git"))
+        .unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains(&format!("is published in registry 'main' from '{}'", url)), "{}", message);
+    assert_eq!(fs::read_to_string(dir.path().join("index/liba.yaml")).unwrap(), published);
+}
+
+#[test]
+fn installs_check_the_published_checksum() {
+    // The index is a plain directory here, so it can be edited between the two installs
+    let (index_dir, _) = common::project();
+    let lib_dir = index_dir.path().join("liba");
+    let (url, mut lib) = library(&lib_dir, "1.0.0");
+    let config = RegistryConfig {
+        name: "main".to_string(),
+        index: index_dir.path().join("index").to_str().unwrap().to_string(),
+    };
+    fs::create_dir(index_dir.path().join("index")).unwrap();
+    lib.registries = vec![config.clone()];
+    lib.publish(lib_dir.to_str().unwrap(), None, Some(&url)).unwrap();
+
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.registries = vec![config.clone()];
+    let dep = pkg.find_in_registries("liba", None, working_dir, &common::config()).unwrap().unwrap();
+    pkg.add_dependency(dep, working_dir, &common::config()).unwrap();
+    assert!(dir.path().join("deps/liba@v1.0.0/package.yaml").exists());
+
+    let entry = index_dir.path().join("index/liba.yaml");
+    let content = fs::read_to_string(&entry).unwrap();
+    let published: RegistryPackage = serde_yaml::from_str(&content).unwrap();
+    let sha256 = published.versions[0].sha256.clone().unwrap();
+    fs::write(&entry, content.replace(&sha256, &"0".repeat(64))).unwrap();
+
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.registries = vec![config];
+    let dep = pkg.find_in_registries("liba", None, working_dir, &common::config()).unwrap().unwrap();
+    let error = pkg.add_dependency(dep, working_dir, &common::config()).unwrap_err();
+    assert!(
+        error.to_string().contains(&format!(
+            "Checksum mismatch for 'liba' 1.0.0: registry 'main' lists sha256 {}, got {}",
+            "0".repeat(64),
+            sha256
+        )),
+        "{}",
+        error
+    );
+    assert!(pkg.dependencies.is_empty());
+}
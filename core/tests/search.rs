@@ -0,0 +1,175 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::package::Package;
+use depo_core::search::{self, SearchFuture, SearchProvider};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Mutex, OnceLock};
+
+static REQUESTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static SERVER: OnceLock<String> = OnceLock::new();
+
+const FOUND: &str = r#"{"items": [{"name": "json", "full_name": "nlohmann/json", "clone_url": "https://github.com/nlohmann/json.git"}]}"#;
+
+/// Start a fake GitHub API and point Depo at it
+///
+/// The server answers `q=limited` with the 403 GitHub sends once the rate limit is
+/// exceeded, `q=throttled` with a 429, `q=forbidden` with an unrelated 403, and every
+/// other query with one repository. Each request line is recorded with its `User-Agent`.
+fn server() -> &'static str {
+    SERVER.get_or_init(|| {
+        common::setup();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v3/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(&stream).lines().map_while(Result::ok);
+                let request = lines.next().unwrap_or_default();
+                let user_agent = lines
+                    .take_while(|line| !line.is_empty())
+                    .find_map(|line| line.strip_prefix("user-agent: ").map(str::to_string))
+                    .unwrap_or_default();
+                REQUESTS.lock().unwrap().push(format!("{} [{}]", request, user_agent));
+
+                let (status, headers, body) = if request.contains("q=limited") {
+                    ("403 Forbidden", "x-ratelimit-reset: 1700000000\r\n", r#"{"message": "API rate limit exceeded"}"#)
+                } else if request.contains("q=throttled") {
+                    ("429 Too Many Requests", "", r#"{"message": "secondary rate limit"}"#)
+                } else if request.contains("q=forbidden") {
+                    ("403 Forbidden", "", r#"{"message": "Repository access blocked"}"#)
+                } else {
+                    ("200 OK", "", FOUND)
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}content-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        // SAFETY: `get_or_init` blocks every other test until the variables are set
+        unsafe {
+            std::env::set_var("DEPO_GITHUB_API_URL", &url);
+            std::env::set_var("DEPO_USER_AGENT", "depo-tests/1.0");
+            std::env::set_var("DEPO_SEARCH_CONFIG", "/nonexistent/search.yaml");
+            std::env::remove_var("GITHUB_TOKEN");
+        }
+        url
+    })
+}
+
+fn requests(query: &str) -> Vec<String> {
+    let needle = format!("q={}+", query);
+    REQUESTS.lock().unwrap().iter().filter(|r| r.contains(&needle)).cloned().collect()
+}
+
+#[tokio::test]
+async fn github_search_uses_the_configured_api_and_user_agent() {
+    server();
+    let found = Package::new().find_dependency("json", ".", &common::config()).await.unwrap();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].full_name, "nlohmann/json");
+    assert_eq!(found[0].url, "https://github.com/nlohmann/json.git");
+    assert_eq!(
+        requests("json"),
+        ["GET /api/v3/search/repositories?q=json+language%3AC%2B%2B&sort=stars&order=desc&per_page=5 HTTP/1.1 [depo-tests/1.0]"]
+    );
+}
+
+#[tokio::test]
+async fn github_rate_limits_are_reported() {
+    server();
+    let error = Package::new().find_dependency("limited", ".", &common::config()).await.unwrap_err();
+    let DepoError::RateLimited { service, reset, hint } = error else {
+        panic!("unexpected error: {:?}", error);
+    };
+    assert_eq!(service, "GitHub");
+    assert_eq!(reset, Some(1700000000));
+    assert_eq!(hint, "Please add a GitHub token to .pkg.env file");
+
+    let error = Package::new().find_dependency("throttled", ".", &common::config()).await.unwrap_err();
+    assert!(matches!(error, DepoError::RateLimited { reset: None, .. }), "{:?}", error);
+    assert_eq!(requests("limited").len(), 1);
+    assert_eq!(requests("throttled").len(), 1);
+}
+
+#[tokio::test]
+async fn other_refusals_are_not_rate_limits() {
+    server();
+    let error = Package::new().find_dependency("forbidden", ".", &common::config()).await.unwrap_err();
+    assert!(!matches!(error, DepoError::RateLimited { .. }), "{:?}", error);
+    assert!(error.to_string().contains("Repository access blocked"), "{}", error);
+}
+
+/// A provider answering every query with a fixed list of repository URLs, or failing
+struct Fixed(&'static str, Option<Vec<&'static str>>);
+
+impl SearchProvider for Fixed {
+    fn name(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn search<'a>(&'a self, _query: &'a str, limit: usize) -> SearchFuture<'a> {
+        Box::pin(async move {
+            let urls = self.1.as_ref().ok_or_else(|| anyhow::anyhow!("{} is down", self.0))?;
+            Ok(urls
+                .iter()
+                .take(limit)
+                .map(|url| Dependency::new("json", "nlohmann/json", url, None, ""))
+                .collect())
+        })
+    }
+}
+
+fn urls(found: &[Dependency]) -> Vec<&str> {
+    found.iter().map(|dep| dep.url.as_str()).collect()
+}
+
+#[tokio::test]
+async fn repositories_found_by_several_providers_are_listed_once() {
+    let providers: Vec<Box<dyn SearchProvider>> = vec![
+        Box::new(Fixed("first", Some(vec!["https://github.com/Nlohmann/JSON.git", "https://gitlab.com/json/json"]))),
+        Box::new(Fixed(
+            "second",
+            Some(vec![
+                "git@github.com:nlohmann/json",
+                "https://github.com/nlohmann/json/",
+                "https://gitlab.com/json/json.git",
+                "https://github.com/open-source-parsers/jsoncpp.git",
+            ]),
+        )),
+    ];
+
+    let found = search::search(&providers, "json", 5).await.unwrap();
+    assert_eq!(
+        urls(&found),
+        [
+            "https://github.com/Nlohmann/JSON.git",
+            "https://gitlab.com/json/json",
+            "https://github.com/open-source-parsers/jsoncpp.git"
+        ]
+    );
+    assert_eq!(search::repository_key("git@github.com:nlohmann/json"), "github.com/nlohmann/json");
+}
+
+#[tokio::test]
+async fn failing_providers_are_skipped_unless_all_fail() {
+    let providers: Vec<Box<dyn SearchProvider>> = vec![
+        Box::new(Fixed("down", None)),
+        Box::new(Fixed("up", Some(vec!["https://github.com/nlohmann/json.git"]))),
+    ];
+    let found = search::search(&providers, "json", 5).await.unwrap();
+    assert_eq!(urls(&found), ["https://github.com/nlohmann/json.git"]);
+
+    let providers: Vec<Box<dyn SearchProvider>> = vec![Box::new(Fixed("down", None)), Box::new(Fixed("also down", None))];
+    let error = search::search(&providers, "json", 5).await.unwrap_err();
+    assert_eq!(error.to_string(), "down is down");
+}
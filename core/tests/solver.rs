@@ -0,0 +1,173 @@
+use depo_core::dependency::Dependency;
+use depo_core::error::DepoError;
+use depo_core::solver::{Candidate, Solver, VersionProvider};
+use semver::Version;
+use std::collections::HashMap;
+
+/// A provider serving versions and their dependencies from memory
+#[derive(Default)]
+struct Registry {
+    versions: HashMap<String, Vec<(Candidate, Vec<Dependency>)>>,
+}
+
+impl Registry {
+    /// Publish a version of a library requiring the given `(name, constraint)` pairs
+    fn publish(mut self, name: &str, version: &str, requires: &[(&str, &str)]) -> Registry {
+        let candidate = Candidate {
+            version: Version::parse(version).unwrap(),
+            tag: format!("v{}", version),
+            commit: commit(name, version),
+        };
+        let dependencies = requires
+            .iter()
+            .map(|(name, constraint)| dep(name, Some(constraint)))
+            .collect();
+        let versions = self.versions.entry(name.to_string()).or_default();
+        versions.push((candidate, dependencies));
+        versions.sort_by(|a, b| b.0.version.cmp(&a.0.version));
+        self
+    }
+}
+
+impl VersionProvider for Registry {
+    fn versions(&mut self, dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        Ok(self
+            .versions
+            .get(&dep.name)
+            .map(|versions| versions.iter().map(|(c, _)| c.clone()).collect())
+            .unwrap_or_default())
+    }
+
+    fn dependencies(
+        &mut self,
+        dep: &Dependency,
+        candidate: &Candidate,
+    ) -> anyhow::Result<Vec<Dependency>> {
+        Ok(self.versions[&dep.name]
+            .iter()
+            .find(|(c, _)| c == candidate)
+            .map(|(_, deps)| deps.clone())
+            .unwrap())
+    }
+}
+
+fn commit(name: &str, version: &str) -> String {
+    format!("{}-{}", name, version)
+}
+
+fn dep(name: &str, constraint: Option<&str>) -> Dependency {
+    Dependency::new(
+        name,
+        name,
+        &format!("https://example.com/{}.git", name),
+        constraint.map(str::to_string),
+        "",
+    )
+}
+
+fn solved(registry: &mut Registry, roots: &[Dependency], name: &str) -> Option<String> {
+    let resolution = Solver::new(registry).solve(roots).unwrap();
+    resolution.get(name).map(|c| c.version.to_string())
+}
+
+#[test]
+fn diamond_dependencies_share_one_version() {
+    let mut registry = Registry::default()
+        .publish("a", "1.0.0", &[("c", ">=1.0")])
+        .publish("b", "1.0.0", &[("c", ">=1.2, <2.0")])
+        .publish("c", "1.0.0", &[])
+        .publish("c", "1.2.0", &[])
+        .publish("c", "1.5.0", &[])
+        .publish("c", "2.0.0", &[]);
+    let roots = [dep("a", Some("^1")), dep("b", Some("^1"))];
+
+    assert_eq!(solved(&mut registry, &roots, "c").as_deref(), Some("1.5.0"));
+    assert_eq!(solved(&mut registry, &roots, "a").as_deref(), Some("1.0.0"));
+}
+
+#[test]
+fn conflicts_are_resolved_by_backtracking() {
+    // The newest `a` requires a `c` that `b` cannot use, so an older `a` has to be chosen
+    let mut registry = Registry::default()
+        .publish("a", "1.0.0", &[("c", "^1")])
+        .publish("a", "2.0.0", &[("c", "^2")])
+        .publish("b", "1.0.0", &[("c", "^1")])
+        .publish("c", "1.3.0", &[])
+        .publish("c", "2.1.0", &[]);
+    let roots = [dep("a", None), dep("b", Some("^1"))];
+
+    assert_eq!(solved(&mut registry, &roots, "a").as_deref(), Some("1.0.0"));
+    assert_eq!(solved(&mut registry, &roots, "c").as_deref(), Some("1.3.0"));
+}
+
+#[test]
+fn unsatisfiable_graphs_explain_the_conflicting_chains() {
+    let mut registry = Registry::default()
+        .publish("a", "1.0.0", &[("c", "^1")])
+        .publish("b", "1.0.0", &[("c", "^2")])
+        .publish("c", "1.0.0", &[])
+        .publish("c", "2.0.0", &[]);
+    let roots = [dep("a", Some("^1")), dep("b", Some("^1"))];
+
+    let error = Solver::new(&mut registry).solve(&roots).unwrap_err();
+    let Some(DepoError::NoMatchingVersion { name, message }) = error.downcast_ref::<DepoError>()
+    else {
+        panic!("unexpected error: {:#}", error);
+    };
+    assert_eq!(name, "c");
+    assert_eq!(
+        message,
+        "Could not find a version of 'c' that satisfies all requirements:\n  \
+         package.yaml -> a 1.0.0 requires c ^1\n  \
+         package.yaml -> b 1.0.0 requires c ^2\n\
+         Available versions of 'c': 2.0.0, 1.0.0"
+    );
+}
+
+#[test]
+fn locked_versions_are_preferred_while_they_satisfy_the_constraints() {
+    let mut registry = Registry::default()
+        .publish("a", "1.0.0", &[])
+        .publish("a", "1.1.0", &[])
+        .publish("a", "1.2.0", &[]);
+
+    let mut solver = Solver::new(&mut registry);
+    solver.prefer("a", &commit("a", "1.1.0"));
+    let resolution = solver.solve(&[dep("a", Some("^1"))]).unwrap();
+    assert_eq!(resolution.get("a").unwrap().commit, commit("a", "1.1.0"));
+
+    let mut solver = Solver::new(&mut registry);
+    solver.prefer("a", &commit("a", "1.1.0"));
+    let resolution = solver.solve(&[dep("a", Some(">=1.2"))]).unwrap();
+    assert_eq!(resolution.get("a").unwrap().commit, commit("a", "1.2.0"));
+}
+
+#[test]
+fn fixed_libraries_override_the_constraints_of_other_packages() {
+    let mut registry = Registry::default()
+        .publish("a", "1.0.0", &[("c", "^1")])
+        .publish("c", "2.0.0", &[]);
+    let mut local = dep("c", None);
+    local.url = String::new();
+    local.path = Some("../c".to_string());
+
+    let resolution = Solver::new(&mut registry)
+        .solve(&[local, dep("a", Some("^1"))])
+        .unwrap();
+
+    assert!(resolution.get("c").is_none());
+    assert_eq!(resolution.get("a").unwrap().version.to_string(), "1.0.0");
+}
+
+#[test]
+fn constraints_on_libraries_without_version_tags_are_conflicts() {
+    let mut registry = Registry::default().publish("a", "1.0.0", &[("c", "^1")]);
+
+    let error = Solver::new(&mut registry)
+        .solve(&[dep("c", None), dep("a", Some("^1"))])
+        .unwrap_err();
+
+    let message = error.to_string();
+    assert!(message.contains("package.yaml -> a 1.0.0 requires c ^1"), "{}", message);
+    assert!(message.contains("'c' has no semantic version tags"), "{}", message);
+}
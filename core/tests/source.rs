@@ -0,0 +1,89 @@
+mod common;
+
+use depo_core::dependency::Dependency;
+use depo_core::lockfile::LockedDependency;
+use depo_core::package::InstallMode;
+use depo_core::serialization;
+use depo_core::solver::Candidate;
+use depo_core::source::{Source, Sources};
+use std::fs;
+
+/// A source serving every `fake://` dependency at version 1.0.0 without any network access
+struct FakeSource;
+
+impl Source for FakeSource {
+    fn handles(&self, dep: &Dependency) -> bool {
+        dep.url.starts_with("fake://")
+    }
+
+    fn versions(&self, _dep: &Dependency) -> anyhow::Result<Vec<Candidate>> {
+        Ok(Vec::new())
+    }
+
+    fn fetch(
+        &self,
+        dep: &mut Dependency,
+        working_dir: &str,
+        _locked: Option<&LockedDependency>,
+        _network: bool,
+    ) -> anyhow::Result<LockedDependency> {
+        dep.version = "1.0.0".to_string();
+        fs::create_dir_all(dep.install_path(working_dir))?;
+        Ok(LockedDependency {
+            name: dep.name.clone(),
+            url: dep.url.clone(),
+            version: dep.version.clone(),
+            commit: String::new(),
+            tag: None,
+            version_constraint: dep.version_constraint.clone(),
+            rev: None,
+            path: None,
+            archive: None,
+            sha256: None,
+            requires: Vec::new(),
+        })
+    }
+
+    fn installed_version(&self, dep: &Dependency, working_dir: &str) -> anyhow::Result<Option<String>> {
+        let mut installed = dep.clone();
+        installed.version = "1.0.0".to_string();
+        Ok(installed
+            .install_path(working_dir)
+            .is_dir()
+            .then_some(installed.version))
+    }
+}
+
+#[test]
+fn install_uses_a_registered_fake_source() {
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.dependencies
+        .push(Dependency::new("fake", "fake", "fake://fake", None, ""));
+
+    let mut sources = Sources::new();
+    sources.register(Box::new(FakeSource));
+    pkg.install_with_sources(working_dir, InstallMode::Normal, &sources, &common::config())
+        .unwrap();
+
+    assert_eq!(pkg.dependencies[0].version, "1.0.0");
+    assert!(dir.path().join("deps/fake@1.0.0").is_dir());
+    let lockfile = serialization::load_lockfile(working_dir).unwrap();
+    assert_eq!(lockfile.find("fake").unwrap().version, "1.0.0");
+}
+
+#[test]
+fn install_fails_without_a_source_for_a_dependency() {
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.dependencies
+        .push(Dependency::new("fake", "fake", "fake://fake", None, ""));
+
+    let error = pkg
+        .install_with_sources(working_dir, InstallMode::Normal, &Sources::new(), &common::config())
+        .unwrap_err();
+
+    assert!(error.to_string().contains("No source can provide dependency 'fake'"));
+    assert!(pkg.dependencies[0].version.is_empty());
+    assert!(!dir.path().join("deps/fake@1.0.0").exists());
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use depo_core::config::Config;
+use depo_core::dependency::Dependency;
+use depo_core::package::InstallMode;
+use depo_core::vendor::VendorManifest;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn vendoring_copies_sources_and_drops_stale_copies() {
+    let repos = TempDir::new().unwrap();
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    for name in ["veca", "vecb"] {
+        let url = common::git_repo(&repos.path().join(name), &[("v1.0.0", None)]);
+        pkg.add_dependency(Dependency::new(name, name, &url, None, ""), working_dir, &common::config())
+            .unwrap();
+    }
+    let installed = dir.path().join("deps/veca@v1.0.0");
+    fs::create_dir_all(installed.join("build")).unwrap();
+    fs::write(installed.join("build/veca.o"), "object").unwrap();
+    fs::create_dir_all(installed.join("src/build")).unwrap();
+    fs::write(installed.join("src/build/config.h"), "#pragma once").unwrap();
+
+    let manifest = pkg.vendor(working_dir, Some("third_party")).unwrap();
+    assert_eq!(manifest.dependencies.len(), 2);
+    assert_eq!(pkg.vendor_dir.as_deref(), Some("third_party"));
+    let vendored = dir.path().join("third_party/veca@v1.0.0");
+    assert_eq!(fs::read_to_string(vendored.join("VERSION")).unwrap(), "v1.0.0");
+    assert!(vendored.join("src/build/config.h").exists());
+    assert!(!vendored.join("build").exists());
+    assert!(!vendored.join(".git").exists());
+    assert!(fs::read_to_string(dir.path().join("package.yaml")).unwrap().contains("vendor_dir: third_party"));
+
+    pkg.remove_dependency("vecb", working_dir).unwrap();
+    let manifest = pkg.vendor(working_dir, None).unwrap();
+    assert_eq!(manifest.dependencies.len(), 1);
+    assert!(!dir.path().join("third_party/vecb@v1.0.0").exists());
+    let saved = VendorManifest::load(&dir.path().join("third_party")).unwrap().unwrap();
+    assert_eq!(saved.dependencies[0].name, "veca");
+    assert_eq!(saved.dependencies[0].commit, manifest.dependencies[0].commit);
+}
+
+#[test]
+fn vendored_copies_are_used_in_place_of_deps() {
+    let repos = TempDir::new().unwrap();
+    let url = common::git_repo(&repos.path().join("vecc"), &[("v1.0.0", None)]);
+    let (dir, mut pkg) = common::project();
+    let working_dir = common::path(&dir);
+    pkg.add_dependency(Dependency::new("vecc", "vecc", &url, None, ""), working_dir, &common::config())
+        .unwrap();
+    pkg.vendor(working_dir, None).unwrap();
+
+    // Neither the repository nor the installed sources are needed any more
+    fs::remove_dir_all(repos.path()).unwrap();
+    fs::remove_dir_all(dir.path().join("deps")).unwrap();
+    let offline = Config {
+        offline: true,
+        ..common::config()
+    };
+    pkg.install(working_dir, InstallMode::Locked, &offline).unwrap();
+    assert!(!dir.path().join("deps/vecc@v1.0.0").exists());
+
+    let order = pkg.build_order(working_dir).unwrap();
+    assert_eq!(order[0].source_path(working_dir), dir.path().join("vendor/vecc@v1.0.0"));
+}